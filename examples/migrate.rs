@@ -0,0 +1,31 @@
+//! Upgrade every saved record offline, with the game stopped:
+//! `cargo run --example migrate -- data` for JSON files, or `-- game.db` for SQLite.
+//! A real game registers the same migrations here as it does at startup
+use lumina::store::{Migrations, Store, StoreError};
+
+fn migrations() -> Migrations {
+    // accounts saved before characters had creation answers
+    Migrations::new().add("accounts", 1, |mut account| {
+        for character in account["characters"].as_array_mut().into_iter().flatten() {
+            if character.get("answers").is_none() {
+                character["answers"] = serde_json::json!({});
+            }
+        }
+        Ok(account)
+    })
+}
+
+#[tokio::main]
+async fn main() -> Result<(), StoreError> {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("usage: migrate <directory | file.db>");
+        std::process::exit(2);
+    };
+    let store = if path.ends_with(".db") { Store::sqlite(&path)? } else { Store::json(&path)? };
+    let report = store.migrations(migrations()).migrate_all().await?;
+    println!("{}", report);
+    if !report.quarantined.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,15 @@
+use lumina::server::Server;
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    Server::builder()
+        .bind("0.0.0.0:4000")
+        .greeting("Welcome to a minimal lumina game!\n")
+        .command("say", |ctx, args| ctx.server.broadcast(&format!("{} says '{}'\n", ctx.id, args)))
+        .command("quit", |ctx, _| {
+            ctx.server.registry.kick(ctx.id, "Goodbye!\n");
+        })
+        .build()
+        .run()
+        .await
+}
@@ -0,0 +1,256 @@
+//! This module provides player accounts: an [`Account`] has a password and owns several characters.
+//! Passwords are hashed with argon2id, and an account is locked for a while after repeated failed logins.
+//! New sessions log in through the [`Nanny`] before they reach the game
+mod nanny;
+#[cfg(test)]
+mod tests;
+pub mod wizard;
+
+pub use nanny::{Login, Nanny, Step};
+pub use wizard::Wizard;
+
+use crate::entity::EntityId;
+use argon2::password_hash::{PasswordHasher, PasswordVerifier};
+use argon2::Argon2;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use wizard::Answer;
+
+/// A character owned by an account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Character {
+    pub name: String,
+    pub entity: EntityId,
+    /// What the player picked in the creation [`Wizard`], by step key
+    #[serde(default)]
+    pub answers: BTreeMap<String, Answer>,
+}
+
+/// A player's login, owning the characters they play
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Account {
+    pub name: String,
+    /// The argon2id hash in PHC format, `$argon2id$v=19$...`
+    password: String,
+    pub characters: Vec<Character>,
+}
+
+impl Account {
+    /// A new account without characters. `password` is a hash from [`hash_password`]
+    pub fn new(name: &str, password: String) -> Self {
+        Account { name: name.to_string(), password, characters: Vec::new() }
+    }
+
+    /// Check a password against the stored hash. This is deliberately slow, don't call it on an async task
+    pub fn check_password(&self, password: &str) -> bool {
+        verify_password(password, &self.password)
+    }
+
+    pub fn character(&self, name: &str) -> Option<&Character> {
+        self.characters.iter().find(|c| c.name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Why an account operation failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccountError {
+    /// An account with that name already exists
+    Exists,
+    NotFound,
+    /// A character with that name already exists, on this account or another
+    NameTaken,
+    InvalidName(&'static str),
+    /// The password couldn't be hashed
+    Hash(String),
+}
+
+impl Display for AccountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccountError::Exists => f.write_str("that account already exists"),
+            AccountError::NotFound => f.write_str("no such account"),
+            AccountError::NameTaken => f.write_str("that name is already taken"),
+            AccountError::InvalidName(reason) => f.write_str(reason),
+            AccountError::Hash(e) => write!(f, "couldn't hash the password: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AccountError {}
+
+/// Hash a password with argon2id and a random salt. This is deliberately slow, don't call it on an async task
+pub fn hash_password(password: &str) -> Result<String, AccountError> {
+    let hash = Argon2::default().hash_password(password.as_bytes()).map_err(|e| AccountError::Hash(e.to_string()))?;
+    Ok(hash.to_string())
+}
+
+/// Check a password against a hash from [`hash_password`]
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    Argon2::default().verify_password(password.as_bytes(), hash).is_ok()
+}
+
+/// Account and character names are 3 to 12 letters, stored capitalized
+pub fn normalize_name(name: &str) -> Result<String, AccountError> {
+    let name = name.trim();
+    if !(3..=12).contains(&name.chars().count()) {
+        return Err(AccountError::InvalidName("names must be 3 to 12 letters long"));
+    }
+    if !name.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(AccountError::InvalidName("names may only contain letters"));
+    }
+    let lower = name.to_ascii_lowercase();
+    Ok(lower[..1].to_ascii_uppercase() + &lower[1..])
+}
+
+/// How many failed logins lock an account, and for how long
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lockout {
+    pub attempts: u32,
+    pub duration: Duration,
+}
+
+impl Default for Lockout {
+    fn default() -> Self {
+        Lockout { attempts: 5, duration: Duration::from_secs(15 * 60) }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Failures {
+    count: u32,
+    locked_until: Option<Instant>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    /// Keyed by lowercase name
+    accounts: HashMap<String, Account>,
+    /// Lowercase character name to lowercase account name
+    owners: HashMap<String, String>,
+}
+
+/// Every account, with the failed logins against each
+#[derive(Debug, Default)]
+pub struct Accounts {
+    inner: Mutex<Inner>,
+    failures: Mutex<HashMap<String, Failures>>,
+}
+
+impl Accounts {
+    pub fn new() -> Self {
+        Accounts::default()
+    }
+
+    /// Accounts loaded from storage. Fails if two of them own characters with the same name
+    pub fn from_accounts(accounts: impl IntoIterator<Item = Account>) -> Result<Self, AccountError> {
+        let loaded = Accounts::new();
+        for account in accounts {
+            loaded.insert(account)?;
+        }
+        Ok(loaded)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Account> {
+        self.inner.lock().unwrap().accounts.get(&name.to_ascii_lowercase()).cloned()
+    }
+
+    pub fn exists(&self, name: &str) -> bool {
+        self.inner.lock().unwrap().accounts.contains_key(&name.to_ascii_lowercase())
+    }
+
+    /// Every account, sorted by name
+    pub fn all(&self) -> Vec<Account> {
+        let mut accounts: Vec<_> = self.inner.lock().unwrap().accounts.values().cloned().collect();
+        accounts.sort_by(|a, b| a.name.cmp(&b.name));
+        accounts
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().accounts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Add a new account
+    pub fn insert(&self, account: Account) -> Result<(), AccountError> {
+        let mut inner = self.inner.lock().unwrap();
+        let key = account.name.to_ascii_lowercase();
+        if inner.accounts.contains_key(&key) {
+            return Err(AccountError::Exists);
+        }
+        let names: Vec<_> = account.characters.iter().map(|c| c.name.to_ascii_lowercase()).collect();
+        if names.iter().any(|n| inner.owners.contains_key(n)) {
+            return Err(AccountError::NameTaken);
+        }
+        for name in names {
+            inner.owners.insert(name, key.clone());
+        }
+        inner.accounts.insert(key, account);
+        Ok(())
+    }
+
+    /// Replace an account's password hash
+    pub fn set_password(&self, name: &str, password: String) -> Result<(), AccountError> {
+        let mut inner = self.inner.lock().unwrap();
+        let account = inner.accounts.get_mut(&name.to_ascii_lowercase()).ok_or(AccountError::NotFound)?;
+        account.password = password;
+        Ok(())
+    }
+
+    /// Give an account a new character. Character names are unique across every account
+    pub fn add_character(&self, name: &str, character: Character) -> Result<(), AccountError> {
+        let mut inner = self.inner.lock().unwrap();
+        let key = name.to_ascii_lowercase();
+        let character_key = character.name.to_ascii_lowercase();
+        if inner.owners.contains_key(&character_key) {
+            return Err(AccountError::NameTaken);
+        }
+        let account = inner.accounts.get_mut(&key).ok_or(AccountError::NotFound)?;
+        account.characters.push(character);
+        inner.owners.insert(character_key, key);
+        Ok(())
+    }
+
+    /// The account that owns the character called `name`
+    pub fn owner(&self, name: &str) -> Option<String> {
+        let inner = self.inner.lock().unwrap();
+        let key = inner.owners.get(&name.to_ascii_lowercase())?;
+        inner.accounts.get(key).map(|a| a.name.clone())
+    }
+
+    /// How much longer the account is locked after failed logins
+    pub fn locked_for(&self, name: &str) -> Option<Duration> {
+        let mut failures = self.failures.lock().unwrap();
+        let key = name.to_ascii_lowercase();
+        let until = failures.get(&key)?.locked_until?;
+        let left = until.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            failures.remove(&key);
+            return None;
+        }
+        Some(left)
+    }
+
+    /// Count a failed login. Returns how long the account is now locked for, if this failure locked it
+    pub fn record_failure(&self, name: &str, lockout: Lockout) -> Option<Duration> {
+        let mut failures = self.failures.lock().unwrap();
+        let entry = failures.entry(name.to_ascii_lowercase()).or_default();
+        entry.count += 1;
+        if entry.count < lockout.attempts {
+            return None;
+        }
+        entry.count = 0;
+        entry.locked_until = Some(Instant::now() + lockout.duration);
+        Some(lockout.duration)
+    }
+
+    /// Forget failed logins after a successful one
+    pub fn clear_failures(&self, name: &str) {
+        self.failures.lock().unwrap().remove(&name.to_ascii_lowercase());
+    }
+}
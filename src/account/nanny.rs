@@ -0,0 +1,411 @@
+//! The login "nanny": what a session goes through before it reaches the game.
+//! Existing players give their name and password, read the MOTD and pick a character from a menu;
+//! new players confirm their name and choose a password first.
+//! New characters are named and then go through the creation [`Wizard`](super::Wizard)
+use super::wizard::{Answer, Creation, Progress};
+use super::{hash_password, normalize_name, Account, AccountError, Accounts, Character};
+use crate::rooms::Vnum;
+use crate::server::Server;
+use crate::session::Session;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum State {
+    Name,
+    Password { account: String },
+    ConfirmName { name: String },
+    NewPassword { name: String },
+    ConfirmPassword { name: String, password: String },
+    Motd { account: String },
+    Menu { account: String },
+    CharacterName { account: String },
+    Create { account: String, name: String, creation: Creation },
+    /// The finished creation is kept so saying no starts it over without rolling again
+    ConfirmCharacter { account: String, name: String, answers: BTreeMap<String, Answer>, creation: Creation },
+    OldPassword { account: String, in_game: bool },
+    ChangePassword { account: String, in_game: bool },
+    ConfirmChange { account: String, password: String, in_game: bool },
+}
+
+/// A session that finished logging in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Login {
+    pub account: String,
+    pub character: Character,
+    /// Whether the character was just created
+    pub created: bool,
+    /// Where a new character was placed, if the creation wizard asked for a starting room
+    pub start: Option<Vnum>,
+}
+
+/// What the connection should do after the nanny handled a line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// Send the next line to the nanny too. It wrote its own prompt
+    Continue,
+    /// Play this character
+    Play(Login),
+    /// Go back to the game after changing the password
+    Resume,
+    /// Disconnect, after flushing what was written
+    Quit,
+}
+
+/// One session's progress through the login
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nanny {
+    state: State,
+}
+
+impl Default for Nanny {
+    fn default() -> Self {
+        Nanny::new()
+    }
+}
+
+/// The shortest password accepted
+const MIN_PASSWORD: usize = 6;
+
+/// Run password hashing on the blocking pool, it takes tens of milliseconds on purpose
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    tokio::task::spawn_blocking(f).await.expect("password hashing panicked")
+}
+
+fn minutes(duration: std::time::Duration) -> u64 {
+    duration.as_secs().div_ceil(60)
+}
+
+impl Nanny {
+    /// Start at the name prompt
+    pub fn new() -> Self {
+        Nanny { state: State::Name }
+    }
+
+    /// Change the password of a player who is already in the game
+    pub fn change_password(account: &str) -> Self {
+        Nanny { state: State::OldPassword { account: account.to_string(), in_game: true } }
+    }
+
+    fn accounts(server: &Server) -> &Accounts {
+        server.accounts.as_ref().expect("the nanny needs accounts")
+    }
+
+    /// Whether the current step asks for a password
+    fn is_hidden(&self) -> bool {
+        matches!(
+            self.state,
+            State::Password { .. }
+                | State::NewPassword { .. }
+                | State::ConfirmPassword { .. }
+                | State::OldPassword { .. }
+                | State::ChangePassword { .. }
+                | State::ConfirmChange { .. }
+        )
+    }
+
+    /// Write the prompt for the current step, hiding what the player types if it's a password
+    pub fn prompt(&self, server: &Server, session: &mut Session) {
+        session.hide_input(self.is_hidden());
+        match &self.state {
+            State::Name => session.prompt("By what name do you wish to be known? "),
+            State::Password { .. } => session.prompt("Password: "),
+            State::ConfirmName { name } => session.prompt(&format!("Did I get that right, {} (Y/N)? ", name)),
+            State::NewPassword { .. } => session.prompt("Choose a password: "),
+            State::ConfirmPassword { .. } | State::ConfirmChange { .. } => session.prompt("Type it again: "),
+            State::Motd { .. } => {
+                session.write(&server.config.motd);
+                session.prompt("[Press return to continue] ");
+            }
+            State::Menu { account } => {
+                let characters = Nanny::accounts(server).get(account).map(|a| a.characters).unwrap_or_default();
+                let mut menu = String::from("\n");
+                if characters.is_empty() {
+                    menu.push_str("You have no characters yet.\n");
+                } else {
+                    menu.push_str("Your characters:\n");
+                    for (i, character) in characters.iter().enumerate() {
+                        menu.push_str(&format!("  {}) {}\n", i + 1, character.name));
+                    }
+                }
+                menu.push_str("N) Create a new character\nP) Change your password\nQ) Quit\n");
+                session.write(&menu);
+                session.prompt("Your choice? ");
+            }
+            State::CharacterName { .. } => session.prompt("What is your character's name? "),
+            State::Create { creation, .. } => creation.prompt(&server.config.creation, session),
+            State::ConfirmCharacter { name, answers, .. } => {
+                let mut summary = format!("\nName: {}\n", name);
+                for (key, answer) in answers {
+                    let answer = match answer {
+                        Answer::Text(text) => text.clone(),
+                        Answer::Room(vnum) => server.world.with(*vnum, |r| r.name.clone()).unwrap_or(vnum.to_string()),
+                        Answer::Scores(scores) => {
+                            scores.iter().map(|(a, n)| format!("{} {}", a, n)).collect::<Vec<_>>().join(", ")
+                        }
+                    };
+                    summary.push_str(&format!("{}: {}\n", key, answer));
+                }
+                session.write(&summary);
+                session.prompt("Create this character (Y/N)? ");
+            }
+            State::OldPassword { .. } => session.prompt("Current password: "),
+            State::ChangePassword { .. } => session.prompt("New password: "),
+        }
+    }
+
+    fn goto(&mut self, state: State, server: &Server, session: &mut Session) -> Step {
+        self.state = state;
+        // An empty MOTD is skipped
+        if let State::Motd { account } = &self.state {
+            if server.config.motd.is_empty() {
+                self.state = State::Menu { account: account.clone() };
+            }
+        }
+        self.prompt(server, session);
+        Step::Continue
+    }
+
+    /// Handle one line from the player
+    pub async fn input(&mut self, server: &Server, session: &mut Session, line: &str) -> Step {
+        let accounts = Nanny::accounts(server);
+        let line = line.trim();
+        if self.is_hidden() {
+            // The client didn't echo the line feed either
+            session.write("\n");
+        }
+        match self.state.clone() {
+            State::Name if line.is_empty() => self.goto(State::Name, server, session),
+            State::Name => {
+                let name = match normalize_name(line) {
+                    Ok(name) => name,
+                    Err(e) => {
+                        session.write(&format!("Sorry, {}.\n", e));
+                        return self.goto(State::Name, server, session);
+                    }
+                };
+                if let Some(left) = accounts.locked_for(&name) {
+                    session.write(&format!("That account is locked, try again in {} minutes.\n", minutes(left)));
+                    return Step::Quit;
+                }
+                match accounts.get(&name) {
+                    Some(account) => self.goto(State::Password { account: account.name }, server, session),
+                    None => self.goto(State::ConfirmName { name }, server, session),
+                }
+            }
+            State::Password { account } => {
+                if self.check(accounts, &account, line).await {
+                    accounts.clear_failures(&account);
+                    return self.goto(State::Motd { account }, server, session);
+                }
+                session.write("Wrong password.\n");
+                if let Some(locked) = accounts.record_failure(&account, server.config.lockout) {
+                    let minutes = minutes(locked);
+                    session.write(&format!("Too many failed logins, the account is locked for {} minutes.\n", minutes));
+                    return Step::Quit;
+                }
+                self.goto(State::Password { account }, server, session)
+            }
+            State::ConfirmName { name } => match line.chars().next().map(|c| c.to_ascii_lowercase()) {
+                Some('y') => self.goto(State::NewPassword { name }, server, session),
+                Some('n') => self.goto(State::Name, server, session),
+                _ => self.goto(State::ConfirmName { name }, server, session),
+            },
+            State::NewPassword { name } => match self.new_password(session, line) {
+                Some(password) => self.goto(State::ConfirmPassword { name, password }, server, session),
+                None => self.goto(State::NewPassword { name }, server, session),
+            },
+            State::ConfirmPassword { name, password } => {
+                if line != password {
+                    session.write("The passwords don't match.\n");
+                    return self.goto(State::NewPassword { name }, server, session);
+                }
+                let Some(hash) = self.hash(session, password).await else {
+                    return self.goto(State::NewPassword { name }, server, session);
+                };
+                match accounts.insert(Account::new(&name, hash)) {
+                    Ok(()) => {
+                        Nanny::save(server, session, &name).await;
+                        self.goto(State::Motd { account: name }, server, session)
+                    }
+                    Err(_) => {
+                        session.write("Someone just took that name.\n");
+                        self.goto(State::Name, server, session)
+                    }
+                }
+            }
+            State::Motd { account } => self.goto(State::Menu { account }, server, session),
+            State::Menu { account } => self.menu(server, session, account, line),
+            State::CharacterName { account } => {
+                let name = match normalize_name(line) {
+                    Ok(name) => name,
+                    Err(e) => {
+                        session.write(&format!("Sorry, {}.\n", e));
+                        return self.goto(State::CharacterName { account }, server, session);
+                    }
+                };
+                if accounts.owner(&name).is_some() {
+                    session.write("That name is taken, pick another.\n");
+                    return self.goto(State::CharacterName { account }, server, session);
+                }
+                let wizard = &server.config.creation;
+                let creation = Creation::new(wizard);
+                if creation.is_done(wizard) {
+                    return self.create(server, session, account, name, BTreeMap::new()).await;
+                }
+                self.goto(State::Create { account, name, creation }, server, session)
+            }
+            State::Create { account, name, mut creation } => {
+                match creation.input(&server.config.creation, session, line) {
+                    Progress::Asking => {
+                        self.state = State::Create { account, name, creation };
+                        Step::Continue
+                    }
+                    Progress::Back => self.goto(State::CharacterName { account }, server, session),
+                    Progress::Done(answers) => {
+                        self.goto(State::ConfirmCharacter { account, name, answers, creation }, server, session)
+                    }
+                }
+            }
+            State::ConfirmCharacter { account, name, answers, mut creation } => {
+                match line.chars().next().map(|c| c.to_ascii_lowercase()) {
+                    Some('y') => self.create(server, session, account, name, answers).await,
+                    Some('n') => {
+                        creation.restart(&server.config.creation);
+                        self.goto(State::Create { account, name, creation }, server, session)
+                    }
+                    _ => self.goto(State::ConfirmCharacter { account, name, answers, creation }, server, session),
+                }
+            }
+            State::OldPassword { account, in_game } => {
+                if self.check(accounts, &account, line).await {
+                    accounts.clear_failures(&account);
+                    return self.goto(State::ChangePassword { account, in_game }, server, session);
+                }
+                session.write("Wrong password.\n");
+                if let Some(locked) = accounts.record_failure(&account, server.config.lockout) {
+                    let minutes = minutes(locked);
+                    session.write(&format!("Too many failed logins, the account is locked for {} minutes.\n", minutes));
+                    return Step::Quit;
+                }
+                self.back(server, session, account, in_game)
+            }
+            State::ChangePassword { account, in_game } => match self.new_password(session, line) {
+                Some(password) => self.goto(State::ConfirmChange { account, password, in_game }, server, session),
+                None => self.goto(State::ChangePassword { account, in_game }, server, session),
+            },
+            State::ConfirmChange { account, password, in_game } => {
+                if line != password {
+                    session.write("The passwords don't match.\n");
+                    return self.goto(State::ChangePassword { account, in_game }, server, session);
+                }
+                if let Some(hash) = self.hash(session, password).await {
+                    if accounts.set_password(&account, hash).is_ok() {
+                        Nanny::save(server, session, &account).await;
+                        session.write("Password changed.\n");
+                    }
+                }
+                self.back(server, session, account, in_game)
+            }
+        }
+    }
+
+    fn menu(&mut self, server: &Server, session: &mut Session, account: String, line: &str) -> Step {
+        let characters = Nanny::accounts(server).get(&account).map(|a| a.characters).unwrap_or_default();
+        if let Some(character) = line.parse::<usize>().ok().and_then(|n| characters.get(n.checked_sub(1)?)) {
+            return Step::Play(Login { account, character: character.clone(), created: false, start: None });
+        }
+        match line.to_ascii_lowercase().as_str() {
+            "n" => self.goto(State::CharacterName { account }, server, session),
+            "p" => self.goto(State::OldPassword { account, in_game: false }, server, session),
+            "q" => {
+                session.write("Goodbye!\n");
+                Step::Quit
+            }
+            _ => {
+                session.write("That isn't one of the choices.\n");
+                self.goto(State::Menu { account }, server, session)
+            }
+        }
+    }
+
+    /// Add the finished character to the account and play it
+    async fn create(
+        &mut self,
+        server: &Server,
+        session: &mut Session,
+        account: String,
+        name: String,
+        answers: BTreeMap<String, Answer>,
+    ) -> Step {
+        let start = server.config.creation.start(&answers);
+        let character = Character { name, entity: server.entities.next(), answers };
+        match Nanny::accounts(server).add_character(&account, character.clone()) {
+            Ok(()) => {
+                Nanny::save(server, session, &account).await;
+                let start = start.filter(|&vnum| match server.world.move_entity(character.entity, vnum) {
+                    Ok(_) => true,
+                    Err(e) => {
+                        server.report_error(&format!("Couldn't place {} in room {}: {}", character.name, vnum, e));
+                        false
+                    }
+                });
+                Step::Play(Login { account, character, created: true, start })
+            }
+            Err(AccountError::NameTaken) => {
+                session.write("Someone just took that name, pick another.\n");
+                self.goto(State::CharacterName { account }, server, session)
+            }
+            Err(e) => {
+                session.write(&format!("Sorry, {}.\n", e));
+                self.goto(State::Menu { account }, server, session)
+            }
+        }
+    }
+
+    /// Save an account to the server's [`Store`](crate::store::Store), if it has one.
+    /// The change stands in memory either way, the player is only told it may not last
+    async fn save(server: &Server, session: &mut Session, name: &str) {
+        let (Some(store), Some(account)) = (&server.store, Nanny::accounts(server).get(name)) else {
+            return;
+        };
+        if let Err(e) = store.save_account(&account).await {
+            server.report_error(&format!("Couldn't save account {}: {}", account.name, e));
+            session.write("Your account couldn't be saved, please tell an administrator.\n");
+        }
+    }
+
+    /// Leave a password change, to the menu or the game
+    fn back(&mut self, server: &Server, session: &mut Session, account: String, in_game: bool) -> Step {
+        if in_game {
+            session.hide_input(false);
+            return Step::Resume;
+        }
+        self.goto(State::Menu { account }, server, session)
+    }
+
+    async fn check(&self, accounts: &Accounts, name: &str, password: &str) -> bool {
+        let Some(account) = accounts.get(name) else {
+            return false;
+        };
+        let password = password.to_string();
+        blocking(move || account.check_password(&password)).await
+    }
+
+    fn new_password(&self, session: &mut Session, line: &str) -> Option<String> {
+        if line.chars().count() < MIN_PASSWORD {
+            session.write(&format!("Passwords must be at least {} characters long.\n", MIN_PASSWORD));
+            return None;
+        }
+        Some(line.to_string())
+    }
+
+    async fn hash(&self, session: &mut Session, password: String) -> Option<String> {
+        match blocking(move || hash_password(&password)).await {
+            Ok(hash) => Some(hash),
+            Err(e) => {
+                session.write(&format!("Sorry, {}.\n", e));
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use super::wizard::{Answer, Creation, Progress, StatRoll};
+use super::{hash_password, normalize_name, verify_password, Account, AccountError, Accounts, Character, Lockout, Wizard};
+use crate::entity::EntityId;
+use crate::rooms::{Room, Vnum, World};
+use crate::session::Session;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+fn character(name: &str, entity: u64) -> Character {
+    Character { name: name.to_string(), entity: EntityId(entity), answers: BTreeMap::new() }
+}
+
+#[test]
+fn passwords_and_names() {
+    let hash = hash_password("hunter42").unwrap();
+    assert!(hash.starts_with("$argon2id$"));
+    assert!(verify_password("hunter42", &hash));
+    assert!(!verify_password("hunter43", &hash));
+    assert!(!verify_password("hunter42", "not a hash"));
+
+    assert_eq!(normalize_name(" aLiCe "), Ok("Alice".to_string()));
+    assert!(matches!(normalize_name("Al"), Err(AccountError::InvalidName(_))));
+    assert!(matches!(normalize_name("Al1ce"), Err(AccountError::InvalidName(_))));
+}
+
+#[test]
+fn characters_and_lockout() {
+    let accounts = Accounts::new();
+    accounts.insert(Account::new("Alice", String::new())).unwrap();
+    accounts.insert(Account::new("Bob", String::new())).unwrap();
+    assert_eq!(accounts.insert(Account::new("alice", String::new())), Err(AccountError::Exists));
+    accounts.add_character("alice", character("Ann", 1)).unwrap();
+    accounts.add_character("alice", character("Amy", 2)).unwrap();
+    let taken = accounts.add_character("bob", character("ann", 3));
+    assert_eq!(taken, Err(AccountError::NameTaken));
+    assert_eq!(accounts.owner("AMY").as_deref(), Some("Alice"));
+    assert_eq!(accounts.get("ALICE").unwrap().character("amy").unwrap().entity, EntityId(2));
+    let reloaded = Accounts::from_accounts(accounts.all()).unwrap();
+    assert_eq!(reloaded.owner("ann").as_deref(), Some("Alice"));
+
+    let lockout = Lockout { attempts: 2, duration: Duration::from_secs(60) };
+    assert_eq!(accounts.record_failure("Bob", lockout), None);
+    accounts.clear_failures("bob");
+    assert_eq!(accounts.record_failure("Bob", lockout), None);
+    assert_eq!(accounts.record_failure("bob", lockout), Some(lockout.duration));
+    assert!(accounts.locked_for("BOB").is_some());
+    assert!(accounts.locked_for("Alice").is_none());
+}
+
+const WIZARD: &str = r#"{"steps": [
+    {"key": "race", "prompt": "Race?", "type": "choice", "options": [{"value": "elf"}, {"value": "dwarf"}]},
+    {"key": "class", "prompt": "Class?", "type": "choice", "options": [
+        {"value": "ranger", "requires": {"race": ["elf"]}},
+        {"value": "warrior", "label": "Fighter"}
+    ]},
+    {"key": "beard", "prompt": "Beard?", "type": "text", "max": 20, "requires": {"race": ["dwarf"]}},
+    {"key": "stats", "prompt": "Stats?", "type": "point_buy", "attributes": ["str", "dex"], "points": 20, "min": 5, "max": 12}
+]}"#;
+
+#[test]
+fn wizard_validation() {
+    let wizard = Wizard::from_json(WIZARD).unwrap();
+    assert_eq!(wizard.steps.len(), 4);
+    assert!(Wizard::from_json(r#"{"steps": [{"key": "a", "prompt": "", "type": "choice", "options": []}]}"#).is_err());
+    let later = r#"{"steps": [{"key": "a", "prompt": "", "type": "text", "max": 5, "requires": {"b": ["x"]}},
+        {"key": "b", "prompt": "", "type": "text", "max": 5}]}"#;
+    assert!(Wizard::from_json(later).unwrap_err().contains("'b'"));
+    let twice = r#"{"steps": [{"key": "a", "prompt": "", "type": "text", "max": 5},
+        {"key": "a", "prompt": "", "type": "text", "max": 5}]}"#;
+    assert!(Wizard::from_json(twice).unwrap_err().contains("twice"));
+    let expensive = r#"{"steps": [{"key": "s", "prompt": "", "type": "point_buy", "attributes": ["a", "b"],
+        "points": 5, "min": 3, "max": 10}]}"#;
+    assert!(Wizard::from_json(expensive).is_err());
+
+    let dice = StatRoll { count: 4, sides: 6, keep: Some(3) };
+    let mut rng = rand::rng();
+    assert!((0..100).map(|_| dice.roll(&mut rng)).all(|n| (3..=18).contains(&n)));
+}
+
+#[test]
+fn wizard_rooms() {
+    let json = r#"{"steps": [{"key": "start", "prompt": "Where?", "type": "room", "options": [
+        {"vnum": 3001, "label": "Temple"}, {"vnum": 3054, "label": "Gate", "description": "By the city wall"}]}]}"#;
+    let wizard = Wizard::from_json(json).unwrap();
+    let world = World::new();
+    world.insert(Room::new(Vnum(3001), "The Temple of Midgaard"));
+    assert!(wizard.check_rooms(&world).unwrap_err().contains("#3054"));
+    world.insert(Room::new(Vnum(3054), "By the Temple Altar"));
+    assert_eq!(wizard.check_rooms(&world), Ok(()));
+
+    let mut session = Session::new();
+    let mut creation = Creation::new(&wizard);
+    creation.prompt(&wizard, &mut session);
+    assert!(String::from_utf8_lossy(&session.take_output()).contains("2) Gate - By the city wall"));
+    let Progress::Done(answers) = creation.input(&wizard, &mut session, "gate") else {
+        panic!("creation should be done");
+    };
+    assert_eq!(answers.get("start"), Some(&Answer::Room(Vnum(3054))));
+    assert_eq!(wizard.start(&answers), Some(Vnum(3054)));
+}
+
+#[test]
+fn wizard_navigation() {
+    let wizard = Wizard::from_json(WIZARD).unwrap();
+    let mut session = Session::new();
+    let mut creation = Creation::new(&wizard);
+    assert_eq!(creation.input(&wizard, &mut session, "back"), Progress::Back);
+    assert_eq!(creation.input(&wizard, &mut session, "3"), Progress::Asking);
+    assert!(String::from_utf8_lossy(&session.take_output()).contains("isn't one of the choices"));
+
+    // dwarves can't be rangers, and get asked about their beard
+    creation.input(&wizard, &mut session, "dwarf");
+    assert!(!String::from_utf8_lossy(&session.take_output()).contains("ranger"));
+    creation.input(&wizard, &mut session, "fig");
+    creation.input(&wizard, &mut session, "Braided");
+    creation.input(&wizard, &mut session, "str 12 dex 12");
+    assert!(String::from_utf8_lossy(&session.take_output()).contains("only have 20"));
+
+    // back to the race, then as an elf the beard is skipped
+    creation.input(&wizard, &mut session, "back");
+    creation.input(&wizard, &mut session, "back");
+    creation.input(&wizard, &mut session, "back");
+    creation.input(&wizard, &mut session, "elf");
+    creation.input(&wizard, &mut session, "1");
+    let Progress::Done(answers) = creation.input(&wizard, &mut session, "dex 10 str 8") else {
+        panic!("creation should be done");
+    };
+    assert_eq!(answers.get("class"), Some(&Answer::Text("ranger".to_string())));
+    assert!(!answers.contains_key("beard"));
+    let scores = BTreeMap::from([("dex".to_string(), 10), ("str".to_string(), 8)]);
+    assert_eq!(answers.get("stats"), Some(&Answer::Scores(scores)));
+
+    creation.input(&wizard, &mut session, "restart");
+    assert_eq!(creation, Creation::new(&wizard));
+
+    // Going back or starting over doesn't give the rerolls back
+    let rolled = r#"{"steps": [{"key": "race", "prompt": "Race?", "type": "choice", "options": [{"value": "elf"}]},
+        {"key": "stats", "prompt": "Roll", "type": "roll", "attributes": ["str"], "dice": {"count": 3, "sides": 6},
+         "rerolls": 1}]}"#;
+    let wizard = Wizard::from_json(rolled).unwrap();
+    let mut creation = Creation::new(&wizard);
+    creation.input(&wizard, &mut session, "elf");
+    creation.input(&wizard, &mut session, "reroll");
+    assert!(String::from_utf8_lossy(&session.take_output()).contains("(0 left)"));
+    for line in ["back", "elf", "restart", "elf"] {
+        creation.input(&wizard, &mut session, line);
+    }
+    let out = String::from_utf8_lossy(&session.take_output()).into_owned();
+    assert!(out.contains("(0 left)") && !out.contains("(1 left)"), "{}", out);
+    creation.input(&wizard, &mut session, "reroll");
+    assert!(String::from_utf8_lossy(&session.take_output()).contains("no rerolls left"));
+}
@@ -0,0 +1,471 @@
+//! This module provides the character creation [`Wizard`]: steps defined in data, usually a JSON file,
+//! each asking for a choice (race, class, pronouns...), a starting room, some text,
+//! or attribute scores by point-buy or rolling.
+//! Players can type `back` at any step to return to the previous one, or `restart` to start over
+//!
+//! ```json
+//! {"steps": [
+//!     {"key": "race", "prompt": "Choose your race", "type": "choice", "options": [
+//!         {"value": "elf", "description": "Graceful and long-lived"},
+//!         {"value": "dwarf", "description": "Stout and stubborn"}
+//!     ]},
+//!     {"key": "class", "prompt": "Choose your class", "type": "choice", "options": [
+//!         {"value": "ranger", "requires": {"race": ["elf"]}},
+//!         {"value": "warrior"}
+//!     ]},
+//!     {"key": "stats", "prompt": "Spend your points", "type": "point_buy",
+//!      "attributes": ["str", "dex", "con"], "points": 30, "min": 5, "max": 15},
+//!     {"key": "start", "prompt": "Where do you begin?", "type": "room", "options": [
+//!         {"vnum": 3001, "label": "The Temple of Midgaard"}
+//!     ]}
+//! ]}
+//! ```
+use crate::rooms::{Vnum, World};
+use crate::session::Session;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The steps of character creation, in order
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Wizard {
+    pub steps: Vec<Question>,
+}
+
+/// One step of the wizard
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Question {
+    /// What the answer is stored as, e.g. `race`
+    pub key: String,
+    pub prompt: String,
+    /// Only ask if earlier answers match, see [`Choice::requires`]
+    #[serde(default)]
+    pub requires: BTreeMap<String, Vec<String>>,
+    #[serde(flatten)]
+    pub kind: Kind,
+}
+
+/// What kind of answer a [`Question`] wants
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Kind {
+    /// Pick one of a list
+    Choice { options: Vec<Choice> },
+    /// Pick the room the character starts in, checked against the world by [`Wizard::check_rooms`]
+    Room { options: Vec<RoomChoice> },
+    /// Free text, such as a short description
+    Text {
+        #[serde(default)]
+        min: usize,
+        max: usize,
+    },
+    /// Spend up to `points` across the attributes, each between `min` and `max`
+    PointBuy {
+        attributes: Vec<String>,
+        points: u32,
+        #[serde(default)]
+        min: u32,
+        max: u32,
+    },
+    /// Roll each attribute, keeping the result or rerolling a limited number of times
+    Roll {
+        attributes: Vec<String>,
+        dice: StatRoll,
+        #[serde(default)]
+        rerolls: u32,
+    },
+}
+
+/// One option of a [`Kind::Choice`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Choice {
+    /// What is stored, e.g. a race name or a room id
+    pub value: String,
+    /// What the player sees, the value if not given
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub description: String,
+    /// Only offered if earlier answers match, e.g. `{"race": ["elf", "human"]}`
+    #[serde(default)]
+    pub requires: BTreeMap<String, Vec<String>>,
+}
+
+impl Choice {
+    pub fn label(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.value)
+    }
+}
+
+/// One option of a [`Kind::Room`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomChoice {
+    pub vnum: Vnum,
+    /// What the player sees, e.g. the room's name
+    pub label: String,
+    #[serde(default)]
+    pub description: String,
+    /// Only offered if earlier answers match, see [`Choice::requires`]
+    #[serde(default)]
+    pub requires: BTreeMap<String, Vec<String>>,
+}
+
+/// An option as the player sees it, from either kind of choice
+struct Offer<'a> {
+    label: &'a str,
+    description: &'a str,
+    /// Also accepted as typed, besides the label
+    value: Option<&'a str>,
+    answer: Answer,
+}
+
+/// Roll `count` dice with `sides` sides, adding up the `keep` highest (all of them if not given)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatRoll {
+    pub count: u32,
+    pub sides: u32,
+    #[serde(default)]
+    pub keep: Option<u32>,
+}
+
+impl StatRoll {
+    pub fn roll(&self, rng: &mut impl Rng) -> u32 {
+        let mut rolls: Vec<u32> = (0..self.count).map(|_| rng.random_range(1..=self.sides.max(1))).collect();
+        rolls.sort_unstable_by(|a, b| b.cmp(a));
+        rolls.iter().take(self.keep.unwrap_or(self.count) as usize).sum()
+    }
+}
+
+/// A player's answer to a [`Question`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Answer {
+    /// A choice's value, or free text
+    Text(String),
+    /// Attribute scores
+    Scores(BTreeMap<String, u32>),
+    /// A starting room
+    Room(Vnum),
+}
+
+/// Whether earlier answers satisfy a `requires` map
+fn allowed(requires: &BTreeMap<String, Vec<String>>, answers: &BTreeMap<String, Answer>) -> bool {
+    requires.iter().all(|(key, values)| {
+        matches!(answers.get(key), Some(Answer::Text(answer)) if values.iter().any(|v| v.eq_ignore_ascii_case(answer)))
+    })
+}
+
+impl Wizard {
+    /// Parse and check a wizard from JSON
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let wizard: Wizard = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        wizard.validate()?;
+        Ok(wizard)
+    }
+
+    /// Check that keys are unique, requirements refer to earlier steps and every step can be answered
+    pub fn validate(&self) -> Result<(), String> {
+        let mut keys = BTreeSet::new();
+        for question in &self.steps {
+            let earlier = |requires: &BTreeMap<String, Vec<String>>| {
+                match requires.keys().find(|k| !keys.contains(k.as_str())) {
+                    Some(key) => Err(format!("step '{}' requires '{}', not an earlier step", question.key, key)),
+                    None => Ok(()),
+                }
+            };
+            earlier(&question.requires)?;
+            match &question.kind {
+                Kind::Choice { options } if options.is_empty() => {
+                    return Err(format!("step '{}' has no options", question.key));
+                }
+                Kind::Choice { options } => options.iter().try_for_each(|o| earlier(&o.requires))?,
+                Kind::Room { options } if options.is_empty() => {
+                    return Err(format!("step '{}' has no options", question.key));
+                }
+                Kind::Room { options } => options.iter().try_for_each(|o| earlier(&o.requires))?,
+                Kind::Text { min, max } if min > max => {
+                    return Err(format!("step '{}' has a minimum length above its maximum", question.key));
+                }
+                Kind::PointBuy { attributes, points, min, max } => {
+                    let count = attributes.len() as u32;
+                    if attributes.is_empty() || min > max || min * count > *points {
+                        return Err(format!("step '{}' can't be bought with {} points", question.key, points));
+                    }
+                }
+                Kind::Roll { attributes, dice, .. } if attributes.is_empty() || dice.count == 0 || dice.sides == 0 => {
+                    return Err(format!("step '{}' has nothing to roll", question.key));
+                }
+                _ => {}
+            }
+            if !keys.insert(question.key.as_str()) {
+                return Err(format!("step '{}' appears twice", question.key));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that every room offered exists in `world`
+    pub fn check_rooms(&self, world: &World) -> Result<(), String> {
+        for question in &self.steps {
+            if let Kind::Room { options } = &question.kind {
+                if let Some(missing) = options.iter().find(|o| !world.contains(o.vnum)) {
+                    return Err(format!("step '{}' offers room {}, which doesn't exist", question.key, missing.vnum));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The room chosen at the last room step answered, where a new character starts
+    pub fn start(&self, answers: &BTreeMap<String, Answer>) -> Option<Vnum> {
+        self.steps.iter().rev().find_map(|question| match (&question.kind, answers.get(&question.key)) {
+            (Kind::Room { .. }, Some(Answer::Room(vnum))) => Some(*vnum),
+            _ => None,
+        })
+    }
+}
+
+/// What happened to a [`Creation`] after a line of input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Progress {
+    /// Still asking, the next prompt is written
+    Asking,
+    /// `back` at the first step
+    Back,
+    /// Every step is answered
+    Done(BTreeMap<String, Answer>),
+}
+
+/// One player's way through a [`Wizard`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Creation {
+    /// The step being asked, `steps.len()` once done
+    step: usize,
+    /// Steps answered so far, for `back`
+    asked: Vec<usize>,
+    answers: BTreeMap<String, Answer>,
+    /// Scores rolled by step key. Kept through `back` and `restart`, so going back can't buy more rerolls
+    rolls: BTreeMap<String, Rolled>,
+}
+
+/// Scores rolled for a [`Kind::Roll`] step, and the rerolls left
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rolled {
+    scores: BTreeMap<String, u32>,
+    rerolls: u32,
+}
+
+impl Creation {
+    pub fn new(wizard: &Wizard) -> Self {
+        Creation::with_rolls(wizard, BTreeMap::new())
+    }
+
+    fn with_rolls(wizard: &Wizard, rolls: BTreeMap<String, Rolled>) -> Self {
+        let mut creation = Creation { step: 0, asked: Vec::new(), answers: BTreeMap::new(), rolls };
+        creation.enter(wizard);
+        creation
+    }
+
+    /// Go back to the first step, keeping what was rolled
+    pub fn restart(&mut self, wizard: &Wizard) {
+        *self = Creation::with_rolls(wizard, std::mem::take(&mut self.rolls));
+    }
+
+    /// Whether every step is answered, or there were none to ask
+    pub fn is_done(&self, wizard: &Wizard) -> bool {
+        self.step >= wizard.steps.len()
+    }
+
+    /// Move to the first step from `self.step` on that applies, rolling scores the first time it asks for them
+    fn enter(&mut self, wizard: &Wizard) {
+        while let Some(question) = wizard.steps.get(self.step) {
+            if self.applies(question) {
+                if let Kind::Roll { rerolls, .. } = question.kind {
+                    if !self.rolls.contains_key(&question.key) {
+                        let scores = Creation::roll(question);
+                        self.rolls.insert(question.key.clone(), Rolled { scores, rerolls });
+                    }
+                }
+                return;
+            }
+            self.step += 1;
+        }
+    }
+
+    fn roll(question: &Question) -> BTreeMap<String, u32> {
+        let Kind::Roll { attributes, dice, .. } = &question.kind else {
+            return BTreeMap::new();
+        };
+        let mut rng = rand::rng();
+        attributes.iter().map(|a| (a.clone(), dice.roll(&mut rng))).collect()
+    }
+
+    /// Whether a question is asked given the answers so far. A choice with no options left is skipped
+    fn applies(&self, question: &Question) -> bool {
+        let choice = matches!(question.kind, Kind::Choice { .. } | Kind::Room { .. });
+        allowed(&question.requires, &self.answers) && (!choice || !self.choices(question).is_empty())
+    }
+
+    /// The options a choice or room step offers given the answers so far
+    fn choices<'a>(&self, question: &'a Question) -> Vec<Offer<'a>> {
+        match &question.kind {
+            Kind::Choice { options } => options
+                .iter()
+                .filter(|o| allowed(&o.requires, &self.answers))
+                .map(|o| Offer {
+                    label: o.label(),
+                    description: &o.description,
+                    value: Some(&o.value),
+                    answer: Answer::Text(o.value.clone()),
+                })
+                .collect(),
+            Kind::Room { options } => options
+                .iter()
+                .filter(|o| allowed(&o.requires, &self.answers))
+                .map(|o| {
+                    let answer = Answer::Room(o.vnum);
+                    Offer { label: &o.label, description: &o.description, value: None, answer }
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Write the current step's question
+    pub fn prompt(&self, wizard: &Wizard, session: &mut Session) {
+        let Some(question) = wizard.steps.get(self.step) else {
+            return;
+        };
+        let mut text = format!("\n{}\n", question.prompt);
+        match &question.kind {
+            Kind::Choice { .. } | Kind::Room { .. } => {
+                for (i, choice) in self.choices(question).iter().enumerate() {
+                    text.push_str(&format!("  {}) {}", i + 1, choice.label));
+                    if !choice.description.is_empty() {
+                        text.push_str(&format!(" - {}", choice.description));
+                    }
+                    text.push('\n');
+                }
+            }
+            Kind::Text { min, max } => text.push_str(&format!("({} to {} characters)\n", min, max)),
+            Kind::PointBuy { attributes, points, min, max } => {
+                let example: Vec<_> = attributes.iter().map(|a| format!("{} {}", a, min)).collect();
+                let example = example.join(" ");
+                text.push_str(&format!("Spend up to {} points, {} to {} each, e.g. `{}`\n", points, min, max, example));
+            }
+            Kind::Roll { .. } => {
+                let rolled = self.rolls.get(&question.key);
+                let scores = rolled.iter().flat_map(|r| &r.scores).map(|(a, n)| format!("{} {}", a, n));
+                text.push_str(&format!("You rolled: {}\n", scores.collect::<Vec<_>>().join(", ")));
+                let rerolls = rolled.map_or(0, |r| r.rerolls);
+                text.push_str(&format!("Type `keep`, or `reroll` ({} left)\n", rerolls));
+            }
+        }
+        text.push_str("(`back` returns to the previous step, `restart` starts over)\n");
+        session.write(&text);
+        session.prompt("> ");
+    }
+
+    /// Handle one line from the player, writing the next prompt or what was wrong
+    pub fn input(&mut self, wizard: &Wizard, session: &mut Session, line: &str) -> Progress {
+        let line = line.trim();
+        match line.to_ascii_lowercase().as_str() {
+            "back" => {
+                let Some(previous) = self.asked.pop() else {
+                    return Progress::Back;
+                };
+                self.answers.remove(&wizard.steps[previous].key);
+                self.step = previous;
+                self.enter(wizard);
+            }
+            "restart" => self.restart(wizard),
+            _ => {
+                let Some(question) = wizard.steps.get(self.step) else {
+                    return Progress::Done(self.answers.clone());
+                };
+                match self.answer(question, line) {
+                    Ok(Some(answer)) => {
+                        self.answers.insert(question.key.clone(), answer);
+                        self.asked.push(self.step);
+                        self.step += 1;
+                        self.enter(wizard);
+                    }
+                    Ok(None) => {}
+                    Err(problem) => session.write(&format!("{}\n", problem)),
+                }
+            }
+        }
+        if self.step >= wizard.steps.len() {
+            return Progress::Done(self.answers.clone());
+        }
+        self.prompt(wizard, session);
+        Progress::Asking
+    }
+
+    /// Check an answer. `Ok(None)` means ask again without complaint, e.g. after a reroll
+    fn answer(&mut self, question: &Question, line: &str) -> Result<Option<Answer>, String> {
+        match &question.kind {
+            Kind::Choice { .. } | Kind::Room { .. } => {
+                let choices = self.choices(question);
+                let lower = line.to_ascii_lowercase();
+                let picked = match line.parse::<usize>() {
+                    Ok(n) => n.checked_sub(1).and_then(|i| choices.get(i)),
+                    Err(_) if lower.is_empty() => None,
+                    Err(_) => choices
+                        .iter()
+                        .find(|c| {
+                            c.value.is_some_and(|v| v.eq_ignore_ascii_case(line)) || c.label.eq_ignore_ascii_case(line)
+                        })
+                        .or_else(|| choices.iter().find(|c| c.label.to_ascii_lowercase().starts_with(&lower))),
+                };
+                let picked = picked.ok_or("That isn't one of the choices.")?;
+                Ok(Some(picked.answer.clone()))
+            }
+            Kind::Text { min, max } => {
+                if !(*min..=*max).contains(&line.chars().count()) {
+                    return Err(format!("That should be {} to {} characters long.", min, max));
+                }
+                Ok(Some(Answer::Text(line.to_string())))
+            }
+            Kind::PointBuy { attributes, points, min, max } => {
+                let words: Vec<_> = line.split_whitespace().collect();
+                let mut scores = BTreeMap::new();
+                for pair in words.chunks(2) {
+                    let [attribute, score] = pair else {
+                        return Err("Give each attribute followed by its score.".to_string());
+                    };
+                    let attribute = attributes
+                        .iter()
+                        .find(|a| a.eq_ignore_ascii_case(attribute))
+                        .ok_or_else(|| format!("There is no attribute called '{}'.", attribute))?;
+                    let score = score.parse::<u32>().ok().filter(|s| (*min..=*max).contains(s));
+                    let score = score.ok_or_else(|| format!("Scores must be {} to {}.", min, max))?;
+                    scores.insert(attribute.clone(), score);
+                }
+                if let Some(missing) = attributes.iter().find(|a| !scores.contains_key(*a)) {
+                    return Err(format!("You haven't given a score for {}.", missing));
+                }
+                let spent: u32 = scores.values().sum();
+                if spent > *points {
+                    return Err(format!("That costs {} points, you only have {}.", spent, points));
+                }
+                Ok(Some(Answer::Scores(scores)))
+            }
+            Kind::Roll { .. } => {
+                let Some(rolled) = self.rolls.get_mut(&question.key) else {
+                    return Ok(None);
+                };
+                match line.to_ascii_lowercase().as_str() {
+                    "keep" => Ok(Some(Answer::Scores(rolled.scores.clone()))),
+                    "reroll" if rolled.rerolls > 0 => {
+                        rolled.rerolls -= 1;
+                        rolled.scores = Creation::roll(question);
+                        Ok(None)
+                    }
+                    "reroll" => Err("You have no rerolls left.".to_string()),
+                    _ => Err("Type `keep` or `reroll`.".to_string()),
+                }
+            }
+        }
+    }
+}
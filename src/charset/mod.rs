@@ -0,0 +1,250 @@
+//! This module provides text encodings for sessions and the [CHARSET](https://www.rfc-editor.org/rfc/rfc2066) telnet option
+#[cfg(test)]
+mod tests;
+
+use serde::{Deserialize, Serialize};
+
+/// `REQUEST` subnegotiation command
+pub const REQUEST: u8 = 1;
+/// `ACCEPTED` subnegotiation command
+pub const ACCEPTED: u8 = 2;
+/// `REJECTED` subnegotiation command
+pub const REJECTED: u8 = 3;
+/// `TTABLE-IS` subnegotiation command
+pub const TTABLE_IS: u8 = 4;
+/// `TTABLE-REJECTED` subnegotiation command
+pub const TTABLE_REJECTED: u8 = 5;
+
+/// A text encoding a session can use on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Charset {
+    #[default]
+    Utf8,
+    Latin1,
+    Cp437,
+    Ascii,
+}
+
+/// The high half (`0x80..=0xFF`) of [code page 437](https://en.wikipedia.org/wiki/Code_page_437)
+const CP437: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+/// ASCII spellings of `U+00C0..=U+00FF`, used when a letter can't be encoded
+const LATIN1_FOLDED: [&str; 64] = [
+    "A", "A", "A", "A", "A", "A", "AE", "C", "E", "E", "E", "E", "I", "I", "I", "I",
+    "D", "N", "O", "O", "O", "O", "O", "x", "O", "U", "U", "U", "U", "Y", "Th", "ss",
+    "a", "a", "a", "a", "a", "a", "ae", "c", "e", "e", "e", "e", "i", "i", "i", "i",
+    "d", "n", "o", "o", "o", "o", "o", "/", "o", "u", "u", "u", "u", "y", "th", "y",
+];
+
+impl Charset {
+    /// Every charset lumina can speak, in order of preference
+    pub const ALL: [Charset; 4] = [Charset::Utf8, Charset::Latin1, Charset::Cp437, Charset::Ascii];
+
+    /// The IANA name sent during negotiation
+    pub fn name(&self) -> &'static str {
+        match self {
+            Charset::Utf8 => "UTF-8",
+            Charset::Latin1 => "ISO-8859-1",
+            Charset::Cp437 => "IBM437",
+            Charset::Ascii => "US-ASCII",
+        }
+    }
+
+    /// Look up a charset by any of its common names (case insensitive)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_uppercase().as_str() {
+            "UTF-8" | "UTF8" => Some(Charset::Utf8),
+            "ISO-8859-1" | "ISO8859-1" | "ISO_8859-1" | "LATIN1" | "LATIN-1" | "L1" | "CP819" => {
+                Some(Charset::Latin1)
+            }
+            "IBM437" | "CP437" | "437" => Some(Charset::Cp437),
+            "US-ASCII" | "ASCII" | "ANSI_X3.4-1968" => Some(Charset::Ascii),
+            _ => None,
+        }
+    }
+
+    fn encode_char(&self, c: char) -> Option<u8> {
+        match self {
+            Charset::Utf8 => None,
+            Charset::Ascii => c.is_ascii().then_some(c as u8),
+            Charset::Latin1 => u8::try_from(u32::from(c)).ok(),
+            Charset::Cp437 => {
+                if c.is_ascii() {
+                    Some(c as u8)
+                } else {
+                    CP437.iter().position(|&x| x == c).map(|i| 0x80 + i as u8)
+                }
+            }
+        }
+    }
+
+    /// Encode `text`, replacing characters this charset can't represent with
+    /// an ASCII lookalike, or `?` if there isn't one
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        if *self == Charset::Utf8 {
+            return text.as_bytes().to_vec();
+        }
+        let mut out = Vec::with_capacity(text.len());
+        for c in text.chars() {
+            if let Some(b) = self.encode_char(c) {
+                out.push(b);
+            } else {
+                match fallback(c) {
+                    Some(s) => out.extend(s.chars().filter_map(|f| self.encode_char(f))),
+                    None => out.push(b'?'),
+                }
+            }
+        }
+        out
+    }
+
+    /// Decode a complete chunk of bytes. Invalid sequences become `U+FFFD`
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        match self {
+            Charset::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            Charset::Latin1 => bytes.iter().map(|&b| char::from(b)).collect(),
+            Charset::Cp437 => bytes
+                .iter()
+                .map(|&b| if b < 0x80 { char::from(b) } else { CP437[usize::from(b - 0x80)] })
+                .collect(),
+            Charset::Ascii => bytes
+                .iter()
+                .map(|&b| if b < 0x80 { char::from(b) } else { char::REPLACEMENT_CHARACTER })
+                .collect(),
+        }
+    }
+}
+
+/// An ASCII approximation for characters that are commonly missing from legacy charsets
+fn fallback(c: char) -> Option<&'static str> {
+    let s = match c {
+        '\u{c0}'..='\u{ff}' => LATIN1_FOLDED[c as usize - 0xc0],
+        '\u{a0}' => " ",
+        '«' | '»' | '“' | '”' | '„' | '″' => "\"",
+        '‘' | '’' | '‚' | '′' => "'",
+        '–' | '—' | '―' | '−' | '─' | '━' | '═' => "-",
+        '│' | '┃' | '║' => "|",
+        '\u{2500}'..='\u{257f}' => "+",
+        '░' | '▒' | '▓' | '█' => "#",
+        '…' => "...",
+        '•' | '·' | '∙' => "*",
+        '€' => "EUR",
+        '©' => "(C)",
+        '®' => "(R)",
+        '™' => "(TM)",
+        _ => return None,
+    };
+    Some(s)
+}
+
+/// An incremental decoder that keeps incomplete multi-byte sequences between reads
+#[derive(Debug, Default)]
+pub struct Decoder {
+    charset: Charset,
+    pending: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn new(charset: Charset) -> Self {
+        Decoder { charset, pending: Vec::new() }
+    }
+
+    pub fn charset(&self) -> Charset {
+        self.charset
+    }
+
+    /// Switch charsets. Any pending partial sequence is dropped
+    pub fn set_charset(&mut self, charset: Charset) {
+        self.charset = charset;
+        self.pending.clear();
+    }
+
+    /// Decode the next chunk of input
+    pub fn decode(&mut self, bytes: &[u8]) -> String {
+        if self.charset != Charset::Utf8 {
+            return self.charset.decode(bytes);
+        }
+        self.pending.extend_from_slice(bytes);
+        let keep = incomplete_tail(&self.pending);
+        let rest = self.pending.split_off(self.pending.len() - keep);
+        let out = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending = rest;
+        out
+    }
+}
+
+/// The length of a UTF-8 sequence cut off at the end of `bytes`
+fn incomplete_tail(bytes: &[u8]) -> usize {
+    for back in 1..=bytes.len().min(3) {
+        let b = bytes[bytes.len() - back];
+        if b & 0xc0 == 0x80 {
+            continue;
+        }
+        let needed = match b {
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 1,
+        };
+        return if needed > back { back } else { 0 };
+    }
+    0
+}
+
+/// Build the payload of a `REQUEST` offering `charsets` in order of preference
+pub fn request(charsets: &[Charset]) -> Vec<u8> {
+    let mut out = vec![REQUEST];
+    for c in charsets {
+        out.push(b';');
+        out.extend_from_slice(c.name().as_bytes());
+    }
+    out
+}
+
+/// A CHARSET subnegotiation received from the client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    /// The client wants us to pick one of these names
+    Request(Vec<String>),
+    /// The client agreed to use this charset
+    Accepted(String),
+    Rejected,
+    /// The client sent a translation table, which isn't supported
+    TranslationTable,
+    Unknown,
+}
+
+impl Message {
+    pub fn parse(data: &[u8]) -> Self {
+        let Some((&cmd, rest)) = data.split_first() else {
+            return Message::Unknown;
+        };
+        match cmd {
+            REQUEST => {
+                let rest = rest.strip_prefix(b"[TTABLE]".as_slice()).map_or(rest, |r| r.get(1..).unwrap_or_default());
+                let Some((&sep, names)) = rest.split_first() else {
+                    return Message::Request(Vec::new());
+                };
+                let names = names
+                    .split(|&b| b == sep)
+                    .filter(|n| !n.is_empty())
+                    .map(|n| String::from_utf8_lossy(n).into_owned())
+                    .collect();
+                Message::Request(names)
+            }
+            ACCEPTED => Message::Accepted(String::from_utf8_lossy(rest).into_owned()),
+            REJECTED => Message::Rejected,
+            TTABLE_IS => Message::TranslationTable,
+            _ => Message::Unknown,
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use super::{Charset, Decoder, Message};
+
+#[test]
+fn fallbacks() {
+    assert_eq!(Charset::Ascii.encode("Ærwyn’s “blade” … ☃"), b"AErwyn's \"blade\" ... ?");
+    assert_eq!(Charset::Cp437.encode("╔═╗ é ã"), b"\xc9\xcd\xbb \x82 a");
+    assert_eq!(Charset::Cp437.decode(b"\xc9\xcd\xbb"), "╔═╗");
+}
+
+#[test]
+fn split_utf8() {
+    let mut decoder = Decoder::new(Charset::Utf8);
+    let bytes = "née".as_bytes();
+    assert_eq!(decoder.decode(&bytes[..2]), "n");
+    assert_eq!(decoder.decode(&bytes[2..]), "ée");
+}
+
+#[test]
+fn parse_request() {
+    assert_eq!(
+        Message::parse(b"\x01;UTF-8;ISO-8859-1"),
+        Message::Request(vec!["UTF-8".to_string(), "ISO-8859-1".to_string()])
+    );
+}
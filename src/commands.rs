@@ -1 +1,133 @@
-// Work in progress
\ No newline at end of file
+//! This module provides the command registry that player input is dispatched to
+use crate::account::Nanny;
+use crate::entity::EntityId;
+use crate::server::links::{Bound, PossessError};
+use crate::server::snoop::SnoopError;
+use crate::server::{Server, SessionId};
+use crate::session::Session;
+use std::collections::HashMap;
+
+/// Everything a handler can act on: the player's session and the server it's connected to
+pub struct Context<'a> {
+    pub server: &'a Server,
+    pub id: SessionId,
+    pub session: &'a mut Session,
+}
+
+impl Context<'_> {
+    /// Play `entity` from this session, see [`Server::bind`]
+    pub fn bind(&mut self, entity: EntityId) -> Bound {
+        self.server.bind(self.id, self.session, entity)
+    }
+
+    /// Stop playing the current character, see [`Server::unbind`]
+    pub fn unbind(&mut self) -> Option<EntityId> {
+        self.server.unbind(self.id, self.session)
+    }
+
+    /// Control another entity in place of the player's character, see [`Server::possess`]
+    pub fn possess(&mut self, entity: EntityId) -> Result<(), PossessError> {
+        self.server.possess(self.id, self.session, entity)
+    }
+
+    /// Return to the player's own character, see [`Server::unpossess`]
+    pub fn unpossess(&mut self) -> Option<EntityId> {
+        self.server.unpossess(self.id, self.session)
+    }
+
+    /// Ask for the player's current and new password, if they logged in to an account. Returns `false` if they didn't
+    pub fn change_password(&mut self) -> bool {
+        let Some(account) = self.session.account() else {
+            return false;
+        };
+        let nanny = Nanny::change_password(account);
+        self.server.start_login(self.id, self.session, nanny);
+        true
+    }
+
+    /// Whether the player has `permission`, see [`Server::allows`]
+    pub fn allows(&self, permission: &str) -> bool {
+        self.server.allows(self.session, permission)
+    }
+
+    /// Watch another session's output, see [`Server::snoop`]
+    pub fn snoop(&mut self, target: SessionId) -> Result<(), SnoopError> {
+        self.server.snoop(self.id, target)
+    }
+}
+
+/// A command handler, called with the command's arguments (everything after the command word, trimmed)
+pub type Handler = Box<dyn Fn(&mut Context, &str) + Send + Sync>;
+
+struct Command {
+    name: String,
+    handler: Handler,
+}
+
+/// A set of commands. Players can type any unambiguous prefix of a command's name;
+/// when several commands share a prefix, the one registered first wins.
+/// A restricted command is hidden from players without its permission, as if it didn't exist
+#[derive(Default)]
+pub struct Commands {
+    commands: Vec<Command>,
+    /// Command name to the permission it needs
+    restrictions: HashMap<String, String>,
+}
+
+impl Commands {
+    pub fn new() -> Self {
+        Commands::default()
+    }
+
+    /// Add a command. Names are matched case-insensitively
+    pub fn register<F>(&mut self, name: &str, handler: F) -> &mut Self
+    where
+        F: Fn(&mut Context, &str) + Send + Sync + 'static,
+    {
+        self.commands.push(Command { name: name.to_ascii_lowercase(), handler: Box::new(handler) });
+        self
+    }
+
+    /// Whether a command is registered with exactly this name
+    pub fn contains(&self, name: &str) -> bool {
+        self.commands.iter().any(|c| c.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Only let players with `permission` use the command called `name`, registered already or not
+    pub fn restrict(&mut self, name: &str, permission: &str) -> &mut Self {
+        self.restrictions.insert(name.to_ascii_lowercase(), permission.to_ascii_lowercase());
+        self
+    }
+
+    /// The permission a command needs, if it is restricted
+    pub fn permission(&self, name: &str) -> Option<&str> {
+        self.restrictions.get(&name.to_ascii_lowercase()).map(String::as_str)
+    }
+
+    fn find(&self, word: &str, usable: impl Fn(&Command) -> bool) -> Option<&Command> {
+        let word = word.to_ascii_lowercase();
+        let mut commands = self.commands.iter().filter(|c| usable(c));
+        commands.clone().find(|c| c.name == word).or_else(|| commands.find(|c| c.name.starts_with(&word)))
+    }
+
+    /// Split `line` into a command word and arguments and run the matching command.
+    /// Returns `false`, after telling the player, if nothing matched
+    pub fn dispatch(&self, ctx: &mut Context, line: &str) -> bool {
+        let line = line.trim();
+        if line.is_empty() {
+            return true;
+        }
+        let (word, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let usable = |c: &Command| self.permission(&c.name).is_none_or(|p| ctx.allows(p));
+        match self.find(word, usable) {
+            Some(command) => {
+                (command.handler)(ctx, args.trim());
+                true
+            }
+            None => {
+                ctx.session.write("Huh?\n");
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! This module provides identifiers for things that exist in the game world: characters, mobiles and objects,
+//! and the prototypes mobiles and objects are made from
+use crate::rooms::ExtraDescription;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A stable identifier for an entity, unique for the lifetime of the world
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct EntityId(pub u64);
+
+impl Display for EntityId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "E{}", self.0)
+    }
+}
+
+/// Hands out entity ids that were never used before, shared by everything that creates entities
+#[derive(Debug)]
+pub struct Allocator {
+    next: AtomicU64,
+}
+
+impl Default for Allocator {
+    fn default() -> Self {
+        Allocator { next: AtomicU64::new(1) }
+    }
+}
+
+impl Allocator {
+    pub fn new() -> Self {
+        Allocator::default()
+    }
+
+    pub fn next(&self) -> EntityId {
+        EntityId(self.next.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Never hand out `used` or anything below it, e.g. after loading saved entities
+    pub fn reserve(&self, used: EntityId) {
+        self.next.fetch_max(used.0 + 1, Ordering::Relaxed);
+    }
+}
+
+/// A roll such as `10d10+100`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Dice {
+    pub count: u32,
+    pub sides: u32,
+    pub bonus: i32,
+}
+
+impl Dice {
+    pub fn roll(&self) -> i64 {
+        let rolled: i64 = (0..self.count).map(|_| rand::random_range(1..=self.sides.max(1)) as i64).sum();
+        rolled + self.bonus as i64
+    }
+}
+
+impl Display for Dice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}d{}{:+}", self.count, self.sides, self.bonus)
+    }
+}
+
+/// A shop kept by a mobile
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Shop {
+    /// The item types it buys
+    pub buys: Vec<u32>,
+    /// What it charges when selling, in percent of the cost
+    pub profit_buy: u32,
+    /// What it pays when buying, in percent of the cost
+    pub profit_sell: u32,
+    /// Opening and closing hours
+    pub hours: (u32, u32),
+}
+
+/// What every mobile made from it starts as. Flags are bit sets as in Diku-style area files, `A` being bit 0
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct MobPrototype {
+    pub vnum: u32,
+    pub keywords: Vec<String>,
+    /// How it's named in a sentence, e.g. `the wizard`
+    pub short: String,
+    /// What it looks like in a room
+    pub long: String,
+    pub description: String,
+    #[serde(default)]
+    pub race: String,
+    pub level: u32,
+    pub alignment: i32,
+    #[serde(default)]
+    pub act: u64,
+    #[serde(default)]
+    pub affected: u64,
+    pub hitroll: i32,
+    pub hit: Dice,
+    pub mana: Dice,
+    pub damage: Dice,
+    #[serde(default)]
+    pub damage_type: String,
+    /// Against pierce, bash, slash and magic
+    pub armor: [i32; 4],
+    #[serde(default)]
+    pub sex: String,
+    pub gold: u64,
+    #[serde(default)]
+    pub size: String,
+    pub shop: Option<Shop>,
+    /// The special procedure it runs, e.g. `spec_cast_mage`
+    pub special: Option<String>,
+}
+
+/// A bonus an object gives, e.g. +2 to strength
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Apply {
+    pub location: u32,
+    pub modifier: i32,
+}
+
+/// What every object made from it starts as
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ObjPrototype {
+    pub vnum: u32,
+    pub keywords: Vec<String>,
+    /// How it's named in a sentence, e.g. `a loaf of bread`
+    pub short: String,
+    /// What it looks like on the ground
+    pub long: String,
+    #[serde(default)]
+    pub material: String,
+    /// e.g. `weapon` or `container`, or the type's number in older files
+    pub item_type: String,
+    #[serde(default)]
+    pub extra: u64,
+    #[serde(default)]
+    pub wear: u64,
+    /// Meaning depends on the type, e.g. a weapon's class, damage dice and attack
+    pub values: Vec<String>,
+    pub level: u32,
+    pub weight: u32,
+    pub cost: u64,
+    #[serde(default)]
+    pub applies: Vec<Apply>,
+    #[serde(default)]
+    pub extra_descriptions: Vec<ExtraDescription>,
+}
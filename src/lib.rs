@@ -9,4 +9,14 @@ mod tests {
 
 pub mod rooms;
 pub mod msdp;
-mod commands;
\ No newline at end of file
+pub mod charset;
+pub mod account;
+pub mod telnet;
+pub mod session;
+pub mod mxp;
+pub mod sound;
+pub mod commands;
+pub mod entity;
+pub mod permission;
+pub mod server;
+pub mod store;
\ No newline at end of file
@@ -1,13 +1,12 @@
 use super::error::{Error, Result};
 
-use nom::bytes::complete::{is_a, is_not, take, take_while};
-use nom::character::complete::{alphanumeric1, digit1};
-use nom::combinator::{map, map_parser, not, opt, eof, peek};
+use nom::bytes::complete::is_not;
+use nom::character::complete::digit1;
+use nom::combinator::{opt, peek, recognize};
 use nom::error::Error as NomError;
-use nom::number::complete::be_u8;
-use nom::sequence::{delimited, pair, preceded, terminated};
+use nom::sequence::{pair, preceded};
 use nom::Err as NomErr;
-use nom::{branch::alt, bytes::complete::tag, error::ErrorKind, Finish, IResult};
+use nom::{branch::alt, bytes::complete::tag, Finish, IResult};
 use serde::{
     de::{
         self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess,
@@ -15,8 +14,7 @@ use serde::{
     },
     Deserialize,
 };
-use std::num::NonZeroI128;
-use std::ops::{AddAssign, Mul, MulAssign, Neg};
+use std::str::FromStr;
 
 pub struct Deserializer<'de> {
     input: &'de [u8],
@@ -30,7 +28,7 @@ impl<'de> Deserializer<'de> {
         self.input.iter().next().cloned().ok_or(Error::Eof)
     }
     fn next_byte(&mut self) -> Result<u8> {
-        self.input.into_iter().next().cloned().ok_or(Error::Eof)
+        self.input.iter().next().cloned().ok_or(Error::Eof)
     }
 }
 
@@ -61,61 +59,59 @@ fn parse_bool(i: &[u8]) -> IResult<&[u8], bool,Error> {
     Ok((i, parsed))
 }
 
-fn parse_unsigned<T>(i: &[u8]) -> IResult<&[u8], T>
-where
-    T: AddAssign<T> + MulAssign<T> + From<u8>,
-{
-    let (i, int) = nom::character::complete::u8(i)?;
-    Ok((i, T::from(int)))
+fn parse_number<T: FromStr>(digits: &[u8]) -> std::result::Result<T, NomErr<Error>> {
+    std::str::from_utf8(digits)
+        .ok()
+        .and_then(|d| d.parse().ok())
+        .ok_or(NomErr::Error(Error::Parse("Integer out of range")))
 }
 
-fn parse_signed<T>(i: &[u8]) -> IResult<&[u8], T>
-where
-    T: Neg<Output = T> + AddAssign<T> + MulAssign<T> + From<i8>,
-{
-    let (i, minus) = opt(tag("-"))(i)?;
-    let is_neg = minus.is_some();
-    let (i, int): (_, i16) = parse_unsigned(i)?;
-    let parsed = if is_neg {
-        T::from(-int as i8)
-    } else {
-        T::from(int as i8)
-    };
-    Ok((i, parsed))
+fn is_integer(token: &[u8]) -> bool {
+    let digits = token.strip_prefix(b"-").unwrap_or(token);
+    !digits.is_empty() && digits.iter().all(u8::is_ascii_digit)
 }
 
+fn parse_unsigned<T: FromStr>(i: &[u8]) -> IResult<&[u8], T, Error> {
+    let (i, digits) = digit1(i)?;
+    Ok((i, parse_number(digits)?))
+}
 
-fn dbytes(i: &[u8]) -> IResult<&[u8], &[u8],Error> {
-    is_a(&b"\x01\x02\x03\x04\x05\x06"[..])(i)
+fn parse_signed<T: FromStr>(i: &[u8]) -> IResult<&[u8], T, Error> {
+    let (i, digits) = recognize(pair(opt(tag("-")), digit1))(i)?;
+    Ok((i, parse_number(digits)?))
 }
+
 fn not_dbytes(i: &[u8]) -> IResult<&[u8], &[u8],Error> {
     is_not(&b"\x01\x02\x03\x04\x05\x06"[..])(i)
 }
 fn parse_string(i: &[u8]) -> IResult<&[u8], String,Error> {
     let (i, data) = not_dbytes(i)?;
-    Ok((i, String::from_utf8(data.to_owned()).unwrap()))
-}
-
-macro_rules! de_clos {
-    ($e:expr) => {
-        |_: Error| $e
-    };
+    let s = String::from_utf8(data.to_owned()).map_err(|_| NomErr::Error(Error::Parse("Expected UTF-8 string")))?;
+    Ok((i, s))
 }
 
-impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     type Error = Error;
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
         match self.peek_byte()? {
-            b'N' => self.deserialize_unit(visitor),
-            b'T' | b'F' => self.deserialize_bool(visitor),
-            b'0'..=b'9' => self.deserialize_u64(visitor),
-            b'-' => self.deserialize_i64(visitor),
             5 => self.deserialize_seq(visitor),
             3 => self.deserialize_map(visitor),
-            _ => self.deserialize_str(visitor),
+            _ => {
+                // Only a whole token can be a keyword or number, "Forest" is still a string
+                let token = not_dbytes(self.input).map(|(_, t)| t).unwrap_or_default();
+                match token {
+                    b"NULL" => self.deserialize_unit(visitor),
+                    b"TRUE" | b"FALSE" => self.deserialize_bool(visitor),
+                    [b'0'..=b'9', ..] if is_integer(token) && parse_number::<u64>(token).is_ok() => {
+                        self.deserialize_u64(visitor)
+                    }
+                    [b'-', ..] if is_integer(token) && parse_number::<i64>(token).is_ok() => self.deserialize_i64(visitor),
+                    _ => self.deserialize_str(visitor),
+                }
+            }
         }
     }
 
@@ -290,13 +286,12 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         fn start(i: &[u8]) -> IResult<&[u8],&[u8],Error> {
             tag(b"\x05")(i)
         }
-        let (i,_) = start(self.input).map_err(|_| Error::ExpectedArrayStart)?;
+        (self.input,_) = start(self.input).map_err(|_| Error::ExpectedArrayStart)?;
         let value = visitor.visit_seq(DByteSeparator::new(self))?;
         fn end(i: &[u8]) -> IResult<&[u8],&[u8], Error> {
             tag(b"\x06")(i)
         }
-        let (i,_) = end(i).map_err(|_| Error::ExpectedArrayEnd)?;
-        self.input = i;
+        (self.input,_) = end(self.input).map_err(|_| Error::ExpectedArrayEnd)?;
         Ok(value)
     }
 
@@ -1,5 +1,5 @@
 use serde::{de, ser};
-use std::fmt::{self, Display, format};
+use std::fmt::{self, Display};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -4,7 +4,9 @@ mod ser;
 mod de;
 #[cfg(test)]
 mod tests;
+pub mod variables;
 
 pub use ser::{Serializer,to_vec};
 pub use de::{Deserializer,from_slice};
-pub use error::{Error,Result};
\ No newline at end of file
+pub use error::{Error,Result};
+pub use variables::Variable;
\ No newline at end of file
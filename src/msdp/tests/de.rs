@@ -12,3 +12,19 @@ pub(crate) fn test_struct() {
    let expected = Amogus {sus: true, id: 69};
    assert_eq!(value,expected)
 }
+
+#[test]
+pub(crate) fn test_invalid_utf8() {
+    use super::from_slice;
+    let input = &b"caf\xe9"[..];
+    assert!(from_slice::<String>(input).is_err())
+}
+
+#[test]
+pub(crate) fn test_any() {
+    use super::from_slice;
+    use serde_json::{json, Value};
+    let input = &b"\x03\x01ROOM\x02Forest\x01HP\x02300\x01EXITS\x02\x05\x02n\x02s\x06\x04"[..];
+    let value: Value = from_slice(input).expect("Failed deserialization");
+    assert_eq!(value, json!({"room": "Forest", "hp": 300, "exits": ["n", "s"]}))
+}
@@ -0,0 +1,94 @@
+//! The MSDP variables a server offers, so that it can answer the client's `LIST` requests
+//! and keep privileged variables from players who may not see them
+
+/// A variable the server sends, or lets the client set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variable {
+    /// Upper case, e.g. `HEALTH`
+    pub name: String,
+    /// Clients may `SEND` for it
+    pub sendable: bool,
+    /// Clients may `REPORT` it, to be sent every change
+    pub reportable: bool,
+    /// Clients may set it, e.g. `CLIENT_NAME`
+    pub configurable: bool,
+    /// Only players with this permission see the variable at all
+    pub permission: Option<String>,
+}
+
+impl Variable {
+    /// A variable the server sends and reports
+    pub fn sendable(name: &str) -> Self {
+        let name = name.to_ascii_uppercase();
+        Variable { name, sendable: true, reportable: true, configurable: false, permission: None }
+    }
+
+    /// A variable the client sets
+    pub fn configurable(name: &str) -> Self {
+        let name = name.to_ascii_uppercase();
+        Variable { name, sendable: false, reportable: false, configurable: true, permission: None }
+    }
+
+    /// Hide the variable from players without `permission`
+    pub fn restrict(mut self, permission: &str) -> Self {
+        self.permission = Some(permission.to_string());
+        self
+    }
+
+    /// Whether the variable belongs in a `LIST` reply such as `SENDABLE_VARIABLES`
+    pub fn is_listed(&self, list: &str) -> bool {
+        match list {
+            "SENDABLE_VARIABLES" => self.sendable,
+            "REPORTABLE_VARIABLES" => self.reportable,
+            "CONFIGURABLE_VARIABLES" => self.configurable,
+            _ => false,
+        }
+    }
+}
+
+/// One top level `VAR name VAL value` pair of an MSDP message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Pair<'a> {
+    /// The whole pair, starting with its VAR byte
+    pub raw: &'a [u8],
+    /// Upper case
+    pub name: String,
+    /// Every plain value, whether given once, several times or in an array
+    pub values: Vec<&'a [u8]>,
+}
+
+/// Split an MSDP message into its top level pairs, keeping tables and arrays whole
+pub(crate) fn pairs(data: &[u8]) -> Vec<Pair<'_>> {
+    let mut starts = Vec::new();
+    let mut depth = 0usize;
+    for (i, &byte) in data.iter().enumerate() {
+        match byte {
+            1 if depth == 0 => starts.push(i),
+            3 | 5 => depth += 1,
+            4 | 6 => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    let ends = starts.iter().skip(1).copied().chain([data.len()]);
+    starts
+        .iter()
+        .zip(ends)
+        .map(|(&start, end)| {
+            let raw = &data[start..end];
+            let split = raw.iter().position(|&b| b == 2).unwrap_or(raw.len());
+            let name = String::from_utf8_lossy(&raw[1..split]).to_ascii_uppercase();
+            let values = raw[split..].split(|b| (2..=6).contains(b)).filter(|v| !v.is_empty()).collect();
+            Pair { raw, name, values }
+        })
+        .collect()
+}
+
+/// The lists asked for by `LIST` commands in an MSDP message, e.g. `SENDABLE_VARIABLES`
+pub fn list_requests(data: &[u8]) -> Vec<String> {
+    pairs(data)
+        .into_iter()
+        .filter(|p| p.name == "LIST")
+        .flat_map(|p| p.values)
+        .map(|v| String::from_utf8_lossy(v).to_ascii_uppercase())
+        .collect()
+}
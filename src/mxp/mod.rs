@@ -0,0 +1,216 @@
+//! This module provides an output builder for [MXP](https://www.zuggsoft.com/zmud/mxp.htm) markup
+#[cfg(test)]
+mod tests;
+
+use std::fmt::{self, Display, Write};
+
+/// An MXP line mode, selected with `ESC [ <n> z`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineMode {
+    /// Only open (formatting) tags are parsed
+    Open = 0,
+    /// Every tag is parsed, including `<send>` and `<!ELEMENT>`
+    Secure = 1,
+    /// No tags are parsed
+    Locked = 2,
+    /// Close all open tags and return to the default mode
+    Reset = 3,
+    /// Only the next tag is secure
+    TempSecure = 4,
+    LockOpen = 5,
+    LockSecure = 6,
+    LockLocked = 7,
+}
+
+impl Display for LineMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\x1b[{}z", *self as u8)
+    }
+}
+
+/// Escape `<`, `>`, `&` and `"` so `text` is shown literally
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// A custom element, sent as `<!ELEMENT ...>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Element {
+    name: String,
+    definition: String,
+    attributes: Vec<String>,
+    flag: Option<String>,
+    open: bool,
+    empty: bool,
+}
+
+impl Element {
+    /// `definition` is the markup the element expands to, e.g. `<send href="go &dir;">`
+    pub fn new(name: &str, definition: &str) -> Self {
+        Element {
+            name: name.to_string(),
+            definition: definition.to_string(),
+            attributes: Vec::new(),
+            flag: None,
+            open: false,
+            empty: false,
+        }
+    }
+
+    /// Declare an attribute, optionally with a default value
+    pub fn attribute(mut self, name: &str, default: Option<&str>) -> Self {
+        self.attributes.push(match default {
+            Some(d) => format!("{}=\"{}\"", name, escape(d).replace('\'', "&#39;")),
+            None => name.to_string(),
+        });
+        self
+    }
+
+    /// Set the element's `FLAG`, such as `RoomName`
+    pub fn flag(mut self, flag: &str) -> Self {
+        self.flag = Some(flag.to_string());
+        self
+    }
+
+    /// Allow the element in open line mode
+    pub fn open(mut self) -> Self {
+        self.open = true;
+        self
+    }
+
+    /// Mark the element as having no closing tag
+    pub fn empty(mut self) -> Self {
+        self.empty = true;
+        self
+    }
+}
+
+impl Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<!ELEMENT {} '{}'", self.name, self.definition.replace('\'', "&#39;"))?;
+        if !self.attributes.is_empty() {
+            write!(f, " ATT='{}'", self.attributes.join(" "))?;
+        }
+        if let Some(flag) = &self.flag {
+            write!(f, " FLAG=\"{}\"", escape(flag))?;
+        }
+        if self.open {
+            f.write_str(" OPEN")?;
+        }
+        if self.empty {
+            f.write_str(" EMPTY")?;
+        }
+        f.write_str(">")
+    }
+}
+
+/// Builds a piece of output that uses MXP when the session negotiated it, and plain text otherwise.<br/>
+/// Text is always escaped, and lines containing tags are switched to secure mode automatically
+#[derive(Debug, Clone)]
+pub struct Mxp {
+    enabled: bool,
+    secure: bool,
+    out: String,
+}
+
+impl Mxp {
+    pub fn new(enabled: bool) -> Self {
+        Mxp { enabled, secure: false, out: String::new() }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Append plain text
+    pub fn text(&mut self, text: &str) -> &mut Self {
+        if self.enabled {
+            self.out.push_str(&escape(text));
+            if text.contains('\n') {
+                self.secure = false;
+            }
+        } else {
+            self.out.push_str(text);
+        }
+        self
+    }
+
+    /// Switch line modes. Secure and open modes last until the end of the line
+    pub fn mode(&mut self, mode: LineMode) -> &mut Self {
+        if self.enabled {
+            let _ = write!(self.out, "{}", mode);
+            self.secure = matches!(mode, LineMode::Secure | LineMode::LockSecure);
+        }
+        self
+    }
+
+    fn ensure_secure(&mut self) {
+        if !self.secure {
+            self.mode(LineMode::Secure);
+        }
+    }
+
+    /// Define a custom element. Nothing is sent without MXP
+    pub fn define(&mut self, element: &Element) -> &mut Self {
+        if self.enabled {
+            self.ensure_secure();
+            let _ = write!(self.out, "{}", element);
+        }
+        self
+    }
+
+    /// Wrap `text` in a tag, e.g. a custom [`Element`] or `<b>`
+    pub fn tag(&mut self, name: &str, attributes: &[(&str, &str)], text: &str) -> &mut Self {
+        if !self.enabled {
+            return self.text(text);
+        }
+        self.ensure_secure();
+        self.out.push('<');
+        self.out.push_str(name);
+        for (key, value) in attributes {
+            let _ = write!(self.out, " {}=\"{}\"", key, escape(value));
+        }
+        self.out.push('>');
+        self.text(text);
+        let _ = write!(self.out, "</{}>", name);
+        self
+    }
+
+    /// A link that sends `command` to the game when clicked
+    pub fn send(&mut self, command: &str, text: &str) -> &mut Self {
+        self.tag("send", &[("href", command)], text)
+    }
+
+    /// A link with a right-click menu. The first command is also the default action
+    pub fn menu(&mut self, text: &str, commands: &[(&str, &str)]) -> &mut Self {
+        let href = commands.iter().map(|(cmd, _)| cmd.replace('|', "")).collect::<Vec<_>>().join("|");
+        let hint = std::iter::once(text.to_string())
+            .chain(commands.iter().map(|(_, hint)| hint.replace('|', "")))
+            .collect::<Vec<_>>()
+            .join("|");
+        self.tag("send", &[("href", &href), ("hint", &hint)], text)
+    }
+
+    /// A hyperlink to `url`
+    pub fn link(&mut self, url: &str, text: &str) -> &mut Self {
+        self.tag("a", &[("href", url)], text)
+    }
+
+    /// The output, with a line left in secure mode locked so nothing written after it on the same line is parsed
+    pub fn finish(mut self) -> String {
+        if self.secure {
+            self.mode(LineMode::Locked);
+        }
+        self.out
+    }
+}
@@ -0,0 +1,36 @@
+use super::{Element, Mxp};
+
+#[test]
+fn exits() {
+    let build = |enabled| {
+        let mut mxp = Mxp::new(enabled);
+        mxp.text("Exits: ").send("north", "north").text(" <up> & ").send("up", "up").text("\n");
+        mxp.finish()
+    };
+    assert_eq!(
+        build(true),
+        "Exits: \x1b[1z<send href=\"north\">north</send> &lt;up&gt; &amp; <send href=\"up\">up</send>\n"
+    );
+    assert_eq!(build(false), "Exits: north <up> & up\n");
+}
+
+#[test]
+fn menus_and_elements() {
+    let mut mxp = Mxp::new(true);
+    mxp.define(&Element::new("Ex", "<send>").flag("RoomExit"));
+    mxp.menu("sword", &[("get sword", "Get"), ("look sword", "Look")]);
+    assert_eq!(
+        mxp.finish(),
+        "\x1b[1z<!ELEMENT Ex '<send>' FLAG=\"RoomExit\"><send href=\"get sword|look sword\" hint=\"sword|Get|Look\">sword</send>\x1b[2z"
+    );
+}
+
+#[test]
+fn attribute_defaults_are_quoted() {
+    let element = Element::new("Mob", "<send href='kill &name;'>");
+    let element = element.attribute("name", Some("Bob's \"dog\"")).attribute("id", None);
+    assert_eq!(
+        element.to_string(),
+        "<!ELEMENT Mob '<send href=&#39;kill &name;&#39;>' ATT='name=\"Bob&#39;s &quot;dog&quot;\" id'>"
+    );
+}
@@ -0,0 +1,332 @@
+//! This module provides [`Permissions`]: named roles that grant and deny permissions and inherit from other roles,
+//! assigned to accounts along with grants and denials of their own.
+//! Permissions are dotted names such as `admin.snoop` or `build.midgaard`, and `build.*` or `*` match many at once.
+//!
+//! An account's own denials win, then its own grants, then its roles. A role's own grants and denials win over
+//! what it inherits, and between roles a denial wins. The `everyone` role, if defined, applies to every session,
+//! including those that haven't logged in
+#[cfg(test)]
+mod tests;
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// The role every session has
+pub const EVERYONE: &str = "everyone";
+
+/// Whether `pattern` covers `permission`: equal, `*`, or a prefix ending in `.*`
+pub fn matches(pattern: &str, permission: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some("") => true,
+        Some(prefix) => {
+            let prefix = prefix.strip_suffix('.').unwrap_or(prefix);
+            permission == prefix || permission.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('.'))
+        }
+        None => pattern.eq_ignore_ascii_case(permission),
+    }
+}
+
+/// The permission online building in an area needs, e.g. `build.midgaard`
+pub fn build(area: &str) -> String {
+    format!("build.{}", area.to_ascii_lowercase())
+}
+
+/// A named set of grants and denials, e.g. `builder` or `immortal`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Role {
+    pub name: String,
+    /// Roles whose permissions this one also has
+    #[serde(default)]
+    pub inherits: Vec<String>,
+    #[serde(default)]
+    pub grants: BTreeSet<String>,
+    #[serde(default)]
+    pub denies: BTreeSet<String>,
+}
+
+impl Role {
+    pub fn new(name: &str) -> Self {
+        Role { name: name.to_ascii_lowercase(), ..Default::default() }
+    }
+
+    pub fn inherit(mut self, role: &str) -> Self {
+        self.inherits.push(role.to_ascii_lowercase());
+        self
+    }
+
+    pub fn grant(mut self, permission: &str) -> Self {
+        self.grants.insert(permission.to_ascii_lowercase());
+        self
+    }
+
+    pub fn deny(mut self, permission: &str) -> Self {
+        self.denies.insert(permission.to_ascii_lowercase());
+        self
+    }
+}
+
+/// An account's roles, and the permissions granted or denied to it alone
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Grants {
+    #[serde(default)]
+    pub roles: BTreeSet<String>,
+    #[serde(default)]
+    pub grants: BTreeSet<String>,
+    #[serde(default)]
+    pub denies: BTreeSet<String>,
+}
+
+/// Every role and every account's grants, as saved and loaded
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PermissionSet {
+    #[serde(default)]
+    pub roles: BTreeMap<String, Role>,
+    /// Keyed by lowercase account name
+    #[serde(default)]
+    pub accounts: BTreeMap<String, Grants>,
+}
+
+/// One change to a [`PermissionSet`], applied with [`Permissions::apply`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// Add a role, or replace the one with the same name
+    DefineRole(Role),
+    RemoveRole(String),
+    Assign { account: String, role: String },
+    Unassign { account: String, role: String },
+    Grant { account: String, permission: String },
+    Deny { account: String, permission: String },
+    /// Forget an account's own grant or denial of a permission
+    Clear { account: String, permission: String },
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::DefineRole(role) => {
+                write!(f, "define role {}", role.name)?;
+                if !role.inherits.is_empty() {
+                    write!(f, " inheriting {}", role.inherits.join(", "))?;
+                }
+                let grants = role.grants.iter().map(|g| format!("+{}", g));
+                let denies = role.denies.iter().map(|d| format!("-{}", d));
+                write!(f, " [{}]", grants.chain(denies).collect::<Vec<_>>().join(" "))
+            }
+            Change::RemoveRole(role) => write!(f, "remove role {}", role),
+            Change::Assign { account, role } => write!(f, "assign role {} to {}", role, account),
+            Change::Unassign { account, role } => write!(f, "unassign role {} from {}", role, account),
+            Change::Grant { account, permission } => write!(f, "grant {} to {}", permission, account),
+            Change::Deny { account, permission } => write!(f, "deny {} to {}", permission, account),
+            Change::Clear { account, permission } => write!(f, "clear {} for {}", permission, account),
+        }
+    }
+}
+
+/// Why a [`Change`] was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PermissionError {
+    NoSuchRole(String),
+    /// The role would end up inheriting from itself
+    Cycle(String),
+    /// Another role inherits from it
+    InUse { role: String, by: String },
+}
+
+impl Display for PermissionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PermissionError::NoSuchRole(role) => write!(f, "there is no role called {}", role),
+            PermissionError::Cycle(role) => write!(f, "role {} would inherit from itself", role),
+            PermissionError::InUse { role, by } => write!(f, "role {} is inherited by {}", role, by),
+        }
+    }
+}
+
+impl std::error::Error for PermissionError {}
+
+/// Whether a set of grants and denials decides `permission`: `Some(false)` if denied, `Some(true)` if granted
+fn decide(grants: &BTreeSet<String>, denies: &BTreeSet<String>, permission: &str) -> Option<bool> {
+    if denies.iter().any(|d| matches(d, permission)) {
+        Some(false)
+    } else if grants.iter().any(|g| matches(g, permission)) {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+impl PermissionSet {
+    /// What the roles say about `permission`, a denial from any of them winning
+    fn decide_roles<'a>(&self, roles: impl IntoIterator<Item = &'a String>, permission: &str) -> Option<bool> {
+        let mut decided = None;
+        for name in roles {
+            match self.decide_role(name, permission, &mut BTreeSet::new()) {
+                Some(false) => return Some(false),
+                Some(true) => decided = Some(true),
+                None => {}
+            }
+        }
+        decided
+    }
+
+    fn decide_role(&self, name: &str, permission: &str, seen: &mut BTreeSet<String>) -> Option<bool> {
+        let role = self.roles.get(name)?;
+        if !seen.insert(role.name.clone()) {
+            return None;
+        }
+        if let Some(decided) = decide(&role.grants, &role.denies, permission) {
+            return Some(decided);
+        }
+        let mut decided = None;
+        for parent in &role.inherits {
+            match self.decide_role(parent, permission, seen) {
+                Some(false) => return Some(false),
+                Some(true) => decided = Some(true),
+                None => {}
+            }
+        }
+        decided
+    }
+
+    /// Whether `role` reaches `target` through what it inherits
+    fn reaches(&self, role: &Role, target: &str, seen: &mut BTreeSet<String>) -> bool {
+        role.inherits.iter().any(|parent| {
+            if parent == target {
+                return true;
+            }
+            seen.insert(parent.clone()) && self.roles.get(parent).is_some_and(|p| self.reaches(p, target, seen))
+        })
+    }
+
+    pub fn allows(&self, account: Option<&str>, permission: &str) -> bool {
+        let permission = permission.to_ascii_lowercase();
+        let everyone = EVERYONE.to_string();
+        let grants = account.and_then(|a| self.accounts.get(&a.to_ascii_lowercase()));
+        if let Some(decided) = grants.and_then(|g| decide(&g.grants, &g.denies, &permission)) {
+            return decided;
+        }
+        let roles = grants.into_iter().flat_map(|g| &g.roles).chain([&everyone]);
+        self.decide_roles(roles, &permission).unwrap_or(false)
+    }
+
+    fn grants_mut(&mut self, account: &str) -> &mut Grants {
+        self.accounts.entry(account.to_ascii_lowercase()).or_default()
+    }
+
+    pub fn apply(&mut self, change: Change) -> Result<(), PermissionError> {
+        match change {
+            Change::DefineRole(role) => {
+                if let Some(missing) = role.inherits.iter().find(|r| !self.roles.contains_key(*r)) {
+                    return Err(PermissionError::NoSuchRole(missing.clone()));
+                }
+                if self.reaches(&role, &role.name, &mut BTreeSet::new()) {
+                    return Err(PermissionError::Cycle(role.name));
+                }
+                self.roles.insert(role.name.clone(), role);
+            }
+            Change::RemoveRole(name) => {
+                let name = name.to_ascii_lowercase();
+                if !self.roles.contains_key(&name) {
+                    return Err(PermissionError::NoSuchRole(name));
+                }
+                if let Some(by) = self.roles.values().find(|r| r.inherits.contains(&name)) {
+                    return Err(PermissionError::InUse { role: name, by: by.name.clone() });
+                }
+                self.roles.remove(&name);
+                for grants in self.accounts.values_mut() {
+                    grants.roles.remove(&name);
+                }
+            }
+            Change::Assign { account, role } => {
+                let role = role.to_ascii_lowercase();
+                if !self.roles.contains_key(&role) {
+                    return Err(PermissionError::NoSuchRole(role));
+                }
+                self.grants_mut(&account).roles.insert(role);
+            }
+            Change::Unassign { account, role } => {
+                self.grants_mut(&account).roles.remove(&role.to_ascii_lowercase());
+            }
+            Change::Grant { account, permission } => {
+                let grants = self.grants_mut(&account);
+                let permission = permission.to_ascii_lowercase();
+                grants.denies.remove(&permission);
+                grants.grants.insert(permission);
+            }
+            Change::Deny { account, permission } => {
+                let grants = self.grants_mut(&account);
+                let permission = permission.to_ascii_lowercase();
+                grants.grants.remove(&permission);
+                grants.denies.insert(permission);
+            }
+            Change::Clear { account, permission } => {
+                let grants = self.grants_mut(&account);
+                grants.grants.remove(&permission.to_ascii_lowercase());
+                grants.denies.remove(&permission.to_ascii_lowercase());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The server's [`PermissionSet`], changeable while it runs
+#[derive(Debug, Default)]
+pub struct Permissions {
+    set: Mutex<PermissionSet>,
+    /// Bumped by every change, so sessions know to look again
+    generation: AtomicU64,
+}
+
+impl Permissions {
+    pub fn new() -> Self {
+        Permissions::default()
+    }
+
+    /// Permissions loaded from storage. Fails if a role inherits one that doesn't exist, or from itself
+    pub fn from_set(set: PermissionSet) -> Result<Self, PermissionError> {
+        let mut checked = PermissionSet { accounts: set.accounts, ..Default::default() };
+        let mut roles: Vec<_> = set.roles.into_values().collect();
+        // Define roles once what they inherit is defined
+        while !roles.is_empty() {
+            let ready = roles.iter().position(|r| r.inherits.iter().all(|i| checked.roles.contains_key(i)));
+            let Some(ready) = ready else {
+                // Nothing left can be defined: something inherits a role that doesn't exist, or they go round
+                let pending = |name: &String| roles.iter().any(|r| &r.name == name);
+                let unknown = |name: &&String| !checked.roles.contains_key(*name) && !pending(name);
+                let missing = roles.iter().flat_map(|r| &r.inherits).find(unknown);
+                return Err(match missing {
+                    Some(missing) => PermissionError::NoSuchRole(missing.clone()),
+                    None => PermissionError::Cycle(roles[0].name.clone()),
+                });
+            };
+            checked.apply(Change::DefineRole(roles.remove(ready)))?;
+        }
+        Ok(Permissions { set: Mutex::new(checked), generation: AtomicU64::new(0) })
+    }
+
+    /// A copy of every role and grant, to save
+    pub fn set(&self) -> PermissionSet {
+        self.set.lock().unwrap().clone()
+    }
+
+    /// Whether the account, or a session that hasn't logged in if `None`, has `permission`
+    pub fn allows(&self, account: Option<&str>, permission: &str) -> bool {
+        self.set.lock().unwrap().allows(account, permission)
+    }
+
+    /// Change a role or an account's grants. Takes effect for every session at once.
+    /// Use [`Server::change_permissions`](crate::server::Server::change_permissions) to have it audited
+    pub fn apply(&self, change: Change) -> Result<(), PermissionError> {
+        self.set.lock().unwrap().apply(change)?;
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// How many changes have been made, to notice when they have
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
+}
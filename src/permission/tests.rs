@@ -0,0 +1,69 @@
+use super::{matches, Change, PermissionError, PermissionSet, Permissions, Role, EVERYONE};
+
+fn grant(account: &str, permission: &str) -> Change {
+    Change::Grant { account: account.to_string(), permission: permission.to_string() }
+}
+
+fn assign(account: &str, role: &str) -> Change {
+    Change::Assign { account: account.to_string(), role: role.to_string() }
+}
+
+#[test]
+fn patterns() {
+    assert!(matches("*", "admin.snoop"));
+    assert!(matches("build.*", "build"));
+    assert!(matches("build.*", "build.midgaard.rooms"));
+    assert!(!matches("build.*", "builder"));
+    assert!(matches("admin.snoop", "admin.snoop"));
+    assert!(!matches("admin.snoop", "admin.snoopy"));
+}
+
+#[test]
+fn roles_inherit_and_deny() {
+    let permissions = Permissions::new();
+    permissions.apply(Change::DefineRole(Role::new(EVERYONE).grant("chat"))).unwrap();
+    permissions.apply(Change::DefineRole(Role::new("builder").grant("build.*").deny("build.limbo"))).unwrap();
+    let immortal = Role::new("immortal").inherit("builder").grant("admin.*").deny("admin.shutdown");
+    permissions.apply(Change::DefineRole(immortal)).unwrap();
+    permissions.apply(Change::DefineRole(Role::new("muted").deny("chat"))).unwrap();
+    permissions.apply(assign("Alice", "immortal")).unwrap();
+    permissions.apply(assign("bob", "builder")).unwrap();
+
+    assert!(permissions.allows(None, "chat"));
+    assert!(!permissions.allows(None, "build.midgaard"));
+    assert!(permissions.allows(Some("alice"), "build.midgaard"));
+    assert!(permissions.allows(Some("ALICE"), "admin.snoop"));
+    assert!(!permissions.allows(Some("alice"), "admin.shutdown"));
+    assert!(!permissions.allows(Some("alice"), "build.limbo"));
+    assert!(!permissions.allows(Some("bob"), "admin.snoop"));
+
+    // an account's own grants and denials beat its roles
+    let generation = permissions.generation();
+    permissions.apply(grant("alice", "admin.shutdown")).unwrap();
+    assert!(permissions.allows(Some("alice"), "admin.shutdown"));
+    assert!(permissions.generation() > generation);
+    permissions.apply(assign("bob", "muted")).unwrap();
+    assert!(!permissions.allows(Some("bob"), "chat"));
+    permissions.apply(Change::Deny { account: "bob".to_string(), permission: "build.*".to_string() }).unwrap();
+    assert!(!permissions.allows(Some("bob"), "build.midgaard"));
+    permissions.apply(Change::Clear { account: "bob".to_string(), permission: "build.*".to_string() }).unwrap();
+    assert!(permissions.allows(Some("bob"), "build.midgaard"));
+
+    assert_eq!(permissions.apply(assign("carol", "wizard")), Err(PermissionError::NoSuchRole("wizard".to_string())));
+    let cycle = Role::new("builder").inherit("immortal");
+    assert_eq!(permissions.apply(Change::DefineRole(cycle)), Err(PermissionError::Cycle("builder".to_string())));
+    let in_use = permissions.apply(Change::RemoveRole("builder".to_string()));
+    assert_eq!(in_use, Err(PermissionError::InUse { role: "builder".to_string(), by: "immortal".to_string() }));
+    permissions.apply(Change::RemoveRole("muted".to_string())).unwrap();
+    assert!(permissions.allows(Some("bob"), "chat"));
+
+    let saved: PermissionSet = serde_json::from_str(&serde_json::to_string(&permissions.set()).unwrap()).unwrap();
+    let loaded = Permissions::from_set(saved).unwrap();
+    assert!(loaded.allows(Some("alice"), "build.midgaard"));
+    assert_eq!(loaded.set(), permissions.set());
+
+    let mut broken = permissions.set();
+    broken.roles.insert("builder".to_string(), Role::new("builder").inherit("immortal"));
+    assert!(matches!(Permissions::from_set(broken), Err(PermissionError::Cycle(_))));
+    assert_eq!(grant("alice", "admin.*").to_string(), "grant admin.* to alice");
+}
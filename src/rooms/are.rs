@@ -0,0 +1,733 @@
+//! This module imports ROM 2.4 and Merc `.are` area files: the `#AREA`, `#ROOMS`, `#MOBILES`, `#OBJECTS`,
+//! `#RESETS`, `#SHOPS` and `#SPECIALS` sections. Anything that doesn't fit lumina's types, such as mobprogs or
+//! an unknown room flag, is left out and listed in [`Import::unsupported`] with its line number.
+//! Files from that era are usually Latin-1, read them with [`String::from_utf8_lossy`] or decode them first
+use super::{Area, Areas, Direction, Door, DoorState, Exit, ExtraDescription, Reset, Room, RoomFlag, Sector, Vnum};
+use super::World;
+use crate::entity::{Apply, Dice, MobPrototype, ObjPrototype, Shop};
+use nom::branch::alt;
+use nom::bytes::complete::{is_not, tag, take_till, take_until};
+use nom::character::complete::{char, digit1, multispace0, one_of, satisfy, space0};
+use nom::combinator::{map, map_res, opt, recognize};
+use nom::multi::separated_list1;
+use nom::sequence::{delimited, pair, preceded, terminated, tuple};
+use nom::IResult;
+use std::fmt::{self, Display};
+
+/// Why a file couldn't be imported at all
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+/// Something in the file that was left out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unsupported {
+    pub line: usize,
+    pub what: String,
+}
+
+impl Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.what)
+    }
+}
+
+/// Everything read from one area file
+#[derive(Debug, Clone, Default)]
+pub struct Import {
+    /// `None` if the file has no `#AREA` section, like a file of helps. `#RESETS` without one are left out
+    pub area: Option<Area>,
+    pub rooms: Vec<Room>,
+    pub mobiles: Vec<MobPrototype>,
+    pub objects: Vec<ObjPrototype>,
+    pub unsupported: Vec<Unsupported>,
+}
+
+impl Import {
+    /// Add the rooms to `world` and the area to `areas`. Mobiles and objects are the game's to keep
+    pub fn install(&self, world: &World, areas: &Areas) {
+        for room in &self.rooms {
+            world.insert(room.clone());
+        }
+        if let Some(area) = &self.area {
+            areas.insert(area.clone());
+        }
+    }
+}
+
+type Res<'a, O> = IResult<&'a str, O>;
+
+/// A number, possibly signed, or several joined by `|` and added up. `None` if it doesn't fit an `i64`
+fn number(i: &str) -> Res<'_, Option<i64>> {
+    let signed = map(recognize(pair(opt(one_of("+-")), digit1)), |n: &str| n.parse::<i64>().ok());
+    let sum = map(separated_list1(char('|'), signed), |parts| {
+        parts.into_iter().try_fold(0, |sum: i64, part| sum.checked_add(part?))
+    });
+    preceded(multispace0, sum)(i)
+}
+
+/// Text up to a `~`, which may span lines
+fn string(i: &str) -> Res<'_, String> {
+    map(preceded(multispace0, terminated(take_until("~"), char('~'))), str::to_string)(i)
+}
+
+/// A word, or a quoted phrase such as a spell name
+fn word(i: &str) -> Res<'_, &str> {
+    let single = delimited(char('\''), take_until("'"), char('\''));
+    let double = delimited(char('"'), take_until("\""), char('"'));
+    preceded(multispace0, alt((single, double, is_not(" \t\n"))))(i)
+}
+
+/// Flags as letters, `A` for bit 0 to `Z` then `a` onwards, as a number, or several joined by `|`
+fn flags(i: &str) -> Res<'_, u64> {
+    let letters = map(recognize(nom::multi::many1(satisfy(|c| c.is_ascii_alphabetic()))), |letters: &str| {
+        letters.chars().map(|c| if c.is_ascii_uppercase() { c as u64 - 'A' as u64 } else { c as u64 - 'a' as u64 + 26 })
+            .filter(|&bit| bit < 64)
+            .fold(0, |flags, bit| flags | 1 << bit)
+    });
+    let part = alt((letters, map_res(digit1, str::parse::<u64>)));
+    let all = map(separated_list1(char('|'), part), |parts| parts.into_iter().fold(0, |flags, part| flags | part));
+    preceded(multispace0, all)(i)
+}
+
+/// A roll such as `10d10+100`
+fn dice(i: &str) -> Res<'_, Dice> {
+    let count = map_res(digit1, str::parse::<u32>);
+    let sides = map_res(digit1, str::parse::<u32>);
+    let bonus = map_res(recognize(pair(one_of("+-"), digit1)), str::parse::<i32>);
+    let roll = tuple((count, char('d'), sides, opt(bonus)));
+    map(preceded(multispace0, roll), |(count, _, sides, bonus)| Dice { count, sides, bonus: bonus.unwrap_or(0) })(i)
+}
+
+/// Whatever is left of the line, e.g. a comment after a reset
+fn rest_of_line(i: &str) -> Res<'_, &str> {
+    terminated(take_till(|c| c == '\n'), opt(char('\n')))(i)
+}
+
+/// A number further along the same line, if there is one
+fn number_on_line(i: &str) -> Res<'_, Option<i64>> {
+    opt(preceded(space0, map_res(recognize(pair(opt(one_of("+-")), digit1)), str::parse::<i64>)))(i)
+}
+
+fn section(i: &str) -> Res<'_, &str> {
+    preceded(multispace0, preceded(char('#'), alt((tag("$"), is_not(" \t\n")))))(i)
+}
+
+fn direction(n: i64) -> Option<Direction> {
+    Direction::ALL.get(usize::try_from(n).ok().filter(|&n| n < 6)?).cloned()
+}
+
+fn sector(n: i64) -> Option<Sector> {
+    Some(match n {
+        0 => Sector::Inside,
+        1 => Sector::City,
+        2 => Sector::Field,
+        3 => Sector::Forest,
+        4 => Sector::Hills,
+        5 => Sector::Mountain,
+        6 => Sector::Swim,
+        7 => Sector::NoSwim,
+        9 => Sector::Air,
+        10 => Sector::Desert,
+        _ => return None,
+    })
+}
+
+/// Room flags by bit
+const ROOM_FLAGS: [(u32, RoomFlag); 8] = [
+    (0, RoomFlag::Dark),
+    (2, RoomFlag::NoMob),
+    (3, RoomFlag::Indoors),
+    (9, RoomFlag::Private),
+    (10, RoomFlag::Safe),
+    (11, RoomFlag::Solitary),
+    (13, RoomFlag::NoRecall),
+    (15, RoomFlag::GodsOnly),
+];
+
+/// Where `E` resets put things on, by number
+const WEAR_SLOTS: [&str; 19] = [
+    "light", "finger_l", "finger_r", "neck_1", "neck_2", "body", "head", "legs", "feet", "hands", "arms", "shield",
+    "about", "waist", "wrist_l", "wrist_r", "wield", "hold", "float",
+];
+
+fn flag_letter(bit: u32) -> char {
+    if bit < 26 {
+        (b'A' + bit as u8) as char
+    } else {
+        (b'a' + (bit - 26) as u8) as char
+    }
+}
+
+fn keywords(text: &str) -> Vec<String> {
+    text.split_whitespace().map(str::to_string).collect()
+}
+
+/// Where parsing is up to, and what's been left out so far
+struct Cursor<'a> {
+    rest: &'a str,
+    /// Line breaks before `rest`
+    lines: usize,
+    unsupported: Vec<Unsupported>,
+}
+
+impl<'a> Cursor<'a> {
+    /// The line the next token is on
+    fn line(&self) -> usize {
+        let skipped = &self.rest[..self.rest.len() - self.rest.trim_start().len()];
+        self.lines + skipped.matches('\n').count() + 1
+    }
+
+    /// Move on to `rest`, which must be a tail of what's left
+    fn advance(&mut self, rest: &'a str) {
+        self.lines += self.rest[..self.rest.len() - rest.len()].matches('\n').count();
+        self.rest = rest;
+    }
+
+    fn error(&self, message: String) -> ImportError {
+        ImportError { line: self.line(), message }
+    }
+
+    fn take<O>(&mut self, what: &str, mut parser: impl FnMut(&'a str) -> Res<'a, O>) -> Result<O, ImportError> {
+        let (rest, output) = parser(self.rest).map_err(|_| self.error(format!("expected {}", what)))?;
+        self.advance(rest);
+        Ok(output)
+    }
+
+    fn number(&mut self, what: &str) -> Result<i64, ImportError> {
+        let line = self.line();
+        let number = self.take(what, number)?;
+        number.ok_or_else(|| ImportError { line, message: format!("{} is too large", what) })
+    }
+
+    /// A number that has to fit `T`. One that doesn't is listed as unsupported and the default used instead
+    fn fit<T: TryFrom<i64> + Default>(&mut self, what: &str) -> Result<T, ImportError> {
+        let line = self.line();
+        let number = self.number(what)?;
+        Ok(T::try_from(number).unwrap_or_else(|_| {
+            self.unsupported(line, format!("{} {} is out of range", what, number));
+            T::default()
+        }))
+    }
+
+    fn string(&mut self, what: &str) -> Result<String, ImportError> {
+        self.take(what, string)
+    }
+
+    fn word(&mut self, what: &str) -> Result<String, ImportError> {
+        self.take(what, word).map(str::to_string)
+    }
+
+    fn flags(&mut self, what: &str) -> Result<u64, ImportError> {
+        self.take(what, flags)
+    }
+
+    fn dice(&mut self, what: &str) -> Result<Dice, ImportError> {
+        self.take(what, dice)
+    }
+
+    /// The next non-blank character, without taking it
+    fn peek(&self) -> Option<char> {
+        self.rest.trim_start().chars().next()
+    }
+
+    /// Take the next non-blank character
+    fn letter(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.advance(&self.rest.trim_start()[c.len_utf8()..]);
+        Some(c)
+    }
+
+    fn skip_line(&mut self) {
+        if let Ok((rest, _)) = rest_of_line(self.rest) {
+            self.advance(rest);
+        }
+    }
+
+    fn unsupported(&mut self, line: usize, what: impl Into<String>) {
+        self.unsupported.push(Unsupported { line, what: what.into() });
+    }
+
+    /// `#vnum` of the next record, or `None` at the `#0` that ends a section
+    fn vnum(&mut self, what: &str) -> Result<Option<u32>, ImportError> {
+        let vnum = self.take(what, preceded(multispace0, preceded(char('#'), number)))?;
+        match vnum {
+            Some(0) => Ok(None),
+            vnum => vnum
+                .and_then(|vnum| u32::try_from(vnum).ok())
+                .map(Some)
+                .ok_or_else(|| self.error(format!("{} is out of range", what))),
+        }
+    }
+
+    /// Skip to the next section header
+    fn skip_section(&mut self) {
+        loop {
+            let line = self.rest.trim_start_matches([' ', '\t', '\n']);
+            let mut chars = line.chars();
+            let header = chars.next() == Some('#') && chars.next().is_some_and(|c| c == '$' || c.is_ascii_uppercase());
+            if line.is_empty() || header {
+                self.advance(line);
+                return;
+            }
+            self.advance(line);
+            self.skip_line();
+        }
+    }
+}
+
+/// Parse an area file
+pub fn parse(source: &str) -> Result<Import, ImportError> {
+    let source = source.replace('\r', "");
+    let mut cursor = Cursor { rest: &source, lines: 0, unsupported: Vec::new() };
+    let mut import = Import::default();
+    let (mut vnums, mut shops, mut specials) = (None, Vec::new(), Vec::new());
+    while !cursor.rest.trim().is_empty() {
+        let line = cursor.line();
+        match cursor.take("a section such as #ROOMS", section)? {
+            "$" => break,
+            "AREA" => {
+                let (area, range) = read_area(&mut cursor)?;
+                import.area = Some(area);
+                vnums = range;
+            }
+            "ROOMS" => read_rooms(&mut cursor, &mut import.rooms)?,
+            "MOBILES" => read_mobiles(&mut cursor, &mut import.mobiles)?,
+            "OBJECTS" => read_objects(&mut cursor, &mut import.objects)?,
+            "RESETS" => {
+                let resets = read_resets(&mut cursor)?;
+                match &mut import.area {
+                    Some(area) => area.resets.extend(resets),
+                    None => cursor.unsupported(line, "#RESETS with no #AREA before it"),
+                }
+            }
+            "SHOPS" => read_shops(&mut cursor, &mut shops)?,
+            "SPECIALS" => read_specials(&mut cursor, &mut specials)?,
+            other => {
+                cursor.unsupported(line, format!("section #{}", other));
+                cursor.skip_section();
+            }
+        }
+    }
+    for (line, keeper, shop) in shops {
+        match import.mobiles.iter_mut().find(|m| m.vnum == keeper) {
+            Some(mobile) => mobile.shop = Some(shop),
+            None => cursor.unsupported(line, format!("shop kept by mobile #{}, which isn't in this file", keeper)),
+        }
+    }
+    for (line, vnum, special) in specials {
+        match import.mobiles.iter_mut().find(|m| m.vnum == vnum) {
+            Some(mobile) => mobile.special = Some(special),
+            None => cursor.unsupported(line, format!("special for mobile #{}, which isn't in this file", vnum)),
+        }
+    }
+    if let Some(area) = &mut import.area {
+        let rooms = import.rooms.iter().map(|r| r.vnum);
+        let (first, last) = (rooms.clone().min(), rooms.max());
+        area.vnums = vnums.or(first.zip(last)).unwrap_or(area.vnums);
+    }
+    import.unsupported = cursor.unsupported;
+    Ok(import)
+}
+
+/// `{ 5 35} Hatchet  Midgaard`: the level range, the author, and in Merc files the area's name
+fn credits(text: &str) -> (Option<(u32, u32)>, String, String) {
+    let text = text.trim();
+    let (levels, rest) = match text.strip_prefix('{').and_then(|t| t.split_once('}')) {
+        Some((levels, rest)) => {
+            let mut bounds = levels.split_whitespace().map(str::parse::<u32>);
+            let levels = match (bounds.next(), bounds.next()) {
+                (Some(Ok(min)), Some(Ok(max))) => Some((min, max)),
+                _ => None,
+            };
+            (levels, rest.trim())
+        }
+        None => (None, text),
+    };
+    let (author, name) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    (levels, author.to_string(), name.trim().to_string())
+}
+
+fn read_area(cursor: &mut Cursor) -> Result<(Area, Option<(Vnum, Vnum)>), ImportError> {
+    let rest_of_header = cursor.rest.split('\n').next().unwrap_or_default();
+    if !rest_of_header.trim().is_empty() {
+        // Merc: #AREA {5 35} Hatchet Midgaard~
+        let (levels, author, name) = credits(&cursor.string("the area's credits")?);
+        let mut area = Area::new(&name, Vnum(0), Vnum(0)).builder(&author);
+        area.levels = levels.unwrap_or(area.levels);
+        return Ok((area, None));
+    }
+    let _file = cursor.string("the area's file name")?;
+    let name = cursor.string("the area's name")?;
+    let (levels, author, _) = credits(&cursor.string("the area's credits")?);
+    let first = cursor.fit("the area's first vnum")?;
+    let last = cursor.fit("the area's last vnum")?;
+    let mut area = Area::new(&name, Vnum(first), Vnum(last)).builder(&author);
+    area.levels = levels.unwrap_or(area.levels);
+    let vnums = area.vnums;
+    Ok((area, Some(vnums)))
+}
+
+fn read_rooms(cursor: &mut Cursor, rooms: &mut Vec<Room>) -> Result<(), ImportError> {
+    while let Some(vnum) = cursor.vnum("a room vnum")? {
+        let mut room = Room::new(Vnum(vnum), &cursor.string("the room's name")?);
+        room.description = cursor.string("the room's description")?;
+        cursor.number("the room's area number")?;
+        let line = cursor.line();
+        let mut bits = cursor.flags("the room's flags")?;
+        for (bit, flag) in ROOM_FLAGS {
+            if bits & 1 << bit != 0 {
+                room.flags.insert(flag);
+                bits &= !(1 << bit);
+            }
+        }
+        for bit in (0..64).filter(|bit| bits & 1 << bit != 0) {
+            cursor.unsupported(line, format!("room {} flag {}", room.vnum, flag_letter(bit)));
+        }
+        let line = cursor.line();
+        let number = cursor.number("the room's sector")?;
+        room.sector = sector(number).unwrap_or_else(|| {
+            cursor.unsupported(line, format!("room {} sector {}", room.vnum, number));
+            Sector::Inside
+        });
+        loop {
+            let line = cursor.line();
+            match cursor.letter() {
+                Some('S') => break,
+                Some('D') => read_exit(cursor, &mut room, line)?,
+                Some('E') => {
+                    let words = cursor.string("the extra description's keywords")?;
+                    let description = cursor.string("the extra description")?;
+                    room.extra.push(ExtraDescription { keywords: keywords(&words), description });
+                }
+                Some('H') | Some('M') => {
+                    let rate = cursor.number("a heal or mana rate")?;
+                    if rate != 100 {
+                        cursor.unsupported(line, format!("room {} heal or mana rate {}", room.vnum, rate));
+                    }
+                }
+                Some(c @ ('C' | 'O')) => {
+                    let value = cursor.string("a clan or owner")?;
+                    let what = if c == 'C' { "clan" } else { "owner" };
+                    cursor.unsupported(line, format!("room {} {} {}", room.vnum, what, value.trim()));
+                }
+                Some(c) => return Err(ImportError { line, message: format!("unknown room field '{}'", c) }),
+                None => return Err(ImportError { line, message: "the file ends inside a room".to_string() }),
+            }
+        }
+        rooms.push(room);
+    }
+    Ok(())
+}
+
+fn read_exit(cursor: &mut Cursor, room: &mut Room, line: usize) -> Result<(), ImportError> {
+    let number = cursor.take("an exit direction", map_res(digit1, str::parse::<i64>))?;
+    let direction = direction(number).ok_or(ImportError { line, message: format!("exit direction {}", number) })?;
+    let description = cursor.string("the exit's description")?;
+    let words = cursor.string("the door's keywords")?;
+    let locks = cursor.number("the door's lock type")?;
+    let key = cursor.number("the door's key")?;
+    let to = cursor.number("the exit's destination")?;
+    let Ok(to) = u32::try_from(to) else {
+        cursor.unsupported(line, format!("room {} exit {} leads nowhere", room.vnum, direction));
+        return Ok(());
+    };
+    let mut exit = Exit::to(Vnum(to));
+    exit.description = description;
+    if locks != 0 {
+        let keywords: Vec<_> = words.split_whitespace().collect();
+        let mut door = Door::new(&keywords).state(DoorState::Open);
+        door.key = u32::try_from(key).ok().filter(|&key| key > 0);
+        door.pickproof = matches!(locks, 2 | 4);
+        door.bashable = true;
+        if matches!(locks, 3 | 4) {
+            cursor.unsupported(line, format!("room {} exit {} can't be passed through", room.vnum, direction));
+        } else if locks > 4 {
+            cursor.unsupported(line, format!("room {} exit {} lock type {}", room.vnum, direction, locks));
+        }
+        exit.door = Some(door);
+    }
+    room.exits.insert(direction, exit);
+    Ok(())
+}
+
+fn read_mobiles(cursor: &mut Cursor, mobiles: &mut Vec<MobPrototype>) -> Result<(), ImportError> {
+    while let Some(vnum) = cursor.vnum("a mobile vnum")? {
+        let mut mobile = MobPrototype {
+            vnum,
+            keywords: keywords(&cursor.string("the mobile's keywords")?),
+            short: cursor.string("the mobile's short description")?,
+            long: cursor.string("the mobile's long description")?,
+            description: cursor.string("the mobile's description")?,
+            ..Default::default()
+        };
+        // ROM gives the race next, Merc goes straight to the flags
+        let rom = cursor.rest.trim_start().split('\n').next().is_some_and(|line| line.contains('~'));
+        if rom {
+            mobile.race = cursor.string("the mobile's race")?;
+        }
+        mobile.act = cursor.flags("the mobile's act flags")?;
+        mobile.affected = cursor.flags("the mobile's affect flags")?;
+        mobile.alignment = cursor.fit("the mobile's alignment")?;
+        if !rom {
+            cursor.word("S")?;
+            mobile.level = cursor.fit("the mobile's level")?;
+            mobile.hitroll = cursor.fit("the mobile's hitroll")?;
+            mobile.armor = [cursor.fit("the mobile's armor")?; 4];
+            mobile.hit = cursor.dice("the mobile's hit dice")?;
+            mobile.damage = cursor.dice("the mobile's damage dice")?;
+            mobile.gold = cursor.fit("the mobile's gold")?;
+            cursor.number("the mobile's experience")?;
+            cursor.number("the mobile's position")?;
+            cursor.number("the mobile's default position")?;
+            mobile.sex = match cursor.number("the mobile's sex")? {
+                1 => "male",
+                2 => "female",
+                _ => "neutral",
+            }
+            .to_string();
+            mobiles.push(mobile);
+            continue;
+        }
+        cursor.number("the mobile's group")?;
+        mobile.level = cursor.fit("the mobile's level")?;
+        mobile.hitroll = cursor.fit("the mobile's hitroll")?;
+        mobile.hit = cursor.dice("the mobile's hit dice")?;
+        mobile.mana = cursor.dice("the mobile's mana dice")?;
+        mobile.damage = cursor.dice("the mobile's damage dice")?;
+        mobile.damage_type = cursor.word("the mobile's damage type")?;
+        for armor in &mut mobile.armor {
+            *armor = cursor.fit("the mobile's armor")?;
+        }
+        for what in ["offensive", "immunity", "resistance", "vulnerability"] {
+            let line = cursor.line();
+            if cursor.flags(&format!("the mobile's {} flags", what))? != 0 {
+                cursor.unsupported(line, format!("mobile #{} {} flags", vnum, what));
+            }
+        }
+        cursor.word("the mobile's position")?;
+        cursor.word("the mobile's default position")?;
+        mobile.sex = cursor.word("the mobile's sex")?;
+        mobile.gold = cursor.fit("the mobile's wealth")?;
+        cursor.flags("the mobile's form")?;
+        cursor.flags("the mobile's parts")?;
+        mobile.size = cursor.word("the mobile's size")?;
+        cursor.word("the mobile's material")?;
+        loop {
+            let line = cursor.line();
+            match cursor.peek() {
+                Some('F') => {
+                    cursor.letter();
+                    let kind = cursor.word("what the flags remove from")?;
+                    cursor.flags("the flags to remove")?;
+                    cursor.unsupported(line, format!("mobile #{} removes {} flags", vnum, kind));
+                }
+                Some('M') => {
+                    cursor.letter();
+                    let trigger = cursor.word("the mobprog's trigger")?;
+                    cursor.number("the mobprog's vnum")?;
+                    cursor.string("the mobprog's phrase")?;
+                    cursor.unsupported(line, format!("mobile #{} mobprog on {}", vnum, trigger.to_ascii_lowercase()));
+                }
+                _ => break,
+            }
+        }
+        mobiles.push(mobile);
+    }
+    Ok(())
+}
+
+fn read_objects(cursor: &mut Cursor, objects: &mut Vec<ObjPrototype>) -> Result<(), ImportError> {
+    while let Some(vnum) = cursor.vnum("an object vnum")? {
+        let mut object = ObjPrototype {
+            vnum,
+            keywords: keywords(&cursor.string("the object's keywords")?),
+            short: cursor.string("the object's short description")?,
+            long: cursor.string("the object's long description")?,
+            ..Default::default()
+        };
+        // ROM's material or Merc's action description
+        let material = cursor.string("the object's material")?;
+        object.item_type = cursor.word("the object's type")?;
+        let merc = object.item_type.parse::<i64>().is_ok();
+        if !merc {
+            object.material = material;
+        }
+        object.extra = cursor.flags("the object's extra flags")?;
+        object.wear = cursor.flags("the object's wear flags")?;
+        for _ in 0..if merc { 4 } else { 5 } {
+            object.values.push(cursor.word("an object value")?);
+        }
+        if !merc {
+            object.level = cursor.fit("the object's level")?;
+        }
+        object.weight = cursor.fit("the object's weight")?;
+        object.cost = cursor.fit("the object's cost")?;
+        if merc {
+            cursor.number("the object's rent")?;
+        } else {
+            cursor.word("the object's condition")?;
+        }
+        loop {
+            let line = cursor.line();
+            match cursor.peek() {
+                Some('A') => {
+                    cursor.letter();
+                    let location = cursor.fit("the apply's location")?;
+                    let modifier = cursor.fit("the apply's modifier")?;
+                    object.applies.push(Apply { location, modifier });
+                }
+                Some('E') => {
+                    cursor.letter();
+                    let words = cursor.string("the extra description's keywords")?;
+                    let description = cursor.string("the extra description")?;
+                    object.extra_descriptions.push(ExtraDescription { keywords: keywords(&words), description });
+                }
+                Some('F') => {
+                    cursor.letter();
+                    cursor.word("what the affect is to")?;
+                    cursor.number("the affect's location")?;
+                    cursor.number("the affect's modifier")?;
+                    cursor.flags("the affect's flags")?;
+                    cursor.unsupported(line, format!("object #{} affect flags", vnum));
+                }
+                _ => break,
+            }
+        }
+        objects.push(object);
+    }
+    Ok(())
+}
+
+fn read_resets(cursor: &mut Cursor) -> Result<Vec<Reset>, ImportError> {
+    let mut resets = Vec::new();
+    loop {
+        let line = cursor.line();
+        let letter = match cursor.letter() {
+            Some('S') => break,
+            Some('*') => {
+                cursor.skip_line();
+                continue;
+            }
+            Some(letter) => letter,
+            None => return Err(ImportError { line, message: "the file ends inside #RESETS".to_string() }),
+        };
+        cursor.number("the reset's if-flag")?;
+        let arg1 = cursor.number("the reset's first argument")?;
+        let arg2 = cursor.number("the reset's second argument")?;
+        let arg3 = if matches!(letter, 'G' | 'R') { 0 } else { cursor.number("the reset's third argument")? };
+        // ROM's room limit on M and count on P, which Merc doesn't have
+        cursor.take("a reset", number_on_line)?;
+        cursor.skip_line();
+        let vnums: &[i64] = match letter {
+            'M' | 'O' | 'P' => &[arg1, arg3],
+            'G' | 'E' | 'D' => &[arg1],
+            _ => &[],
+        };
+        if let Some(bad) = vnums.iter().find(|&&n| u32::try_from(n).is_err()) {
+            cursor.unsupported(line, format!("reset '{}' with vnum #{} out of range", letter, bad));
+            continue;
+        }
+        // Every vnum used below was checked above
+        let vnum = |n: i64| u32::try_from(n).unwrap_or_default();
+        let reset = match letter {
+            'M' => {
+                let limit = usize::try_from(arg2).ok().filter(|&l| l > 0).unwrap_or(usize::MAX);
+                Reset::Mob { mob: vnum(arg1), room: Vnum(vnum(arg3)), limit }
+            }
+            'G' => Reset::Give { object: vnum(arg1) },
+            'E' => match WEAR_SLOTS.get(usize::try_from(arg3).unwrap_or(usize::MAX)) {
+                Some(slot) => Reset::Equip { object: vnum(arg1), slot: slot.to_string() },
+                None => {
+                    cursor.unsupported(line, format!("reset equips object #{} on wear location {}", arg1, arg3));
+                    continue;
+                }
+            },
+            'O' => Reset::Object { object: vnum(arg1), room: Vnum(vnum(arg3)) },
+            'P' => Reset::Put { object: vnum(arg1), container: vnum(arg3) },
+            'D' => {
+                let state = match arg3 {
+                    0 => DoorState::Open,
+                    1 => DoorState::Closed,
+                    2 => DoorState::Locked,
+                    _ => {
+                        cursor.unsupported(line, format!("door reset to state {}", arg3));
+                        continue;
+                    }
+                };
+                let Some(direction) = direction(arg2) else {
+                    cursor.unsupported(line, format!("door reset in direction {}", arg2));
+                    continue;
+                };
+                Reset::Door { room: Vnum(vnum(arg1)), direction, state }
+            }
+            'R' => {
+                cursor.unsupported(line, format!("reset shuffles the exits of room #{}", arg1));
+                continue;
+            }
+            other => {
+                cursor.unsupported(line, format!("reset '{}'", other));
+                continue;
+            }
+        };
+        resets.push(reset);
+    }
+    Ok(resets)
+}
+
+fn read_shops(cursor: &mut Cursor, shops: &mut Vec<(usize, u32, Shop)>) -> Result<(), ImportError> {
+    loop {
+        let line = cursor.line();
+        let keeper = cursor.number("a shopkeeper's vnum")?;
+        if keeper == 0 {
+            return Ok(());
+        }
+        let mut buys = Vec::new();
+        for _ in 0..5 {
+            let item_type = cursor.number("an item type the shop buys")?;
+            buys.extend(u32::try_from(item_type).ok().filter(|&t| t > 0));
+        }
+        let profit_buy = cursor.fit("the shop's buying profit")?;
+        let profit_sell = cursor.fit("the shop's selling profit")?;
+        let hours = (cursor.fit("the shop's opening hour")?, cursor.fit("the shop's closing hour")?);
+        cursor.skip_line();
+        match u32::try_from(keeper) {
+            Ok(keeper) => shops.push((line, keeper, Shop { buys, profit_buy, profit_sell, hours })),
+            Err(_) => cursor.unsupported(line, format!("shop kept by mobile #{}", keeper)),
+        }
+    }
+}
+
+fn read_specials(cursor: &mut Cursor, specials: &mut Vec<(usize, u32, String)>) -> Result<(), ImportError> {
+    loop {
+        let line = cursor.line();
+        match cursor.letter() {
+            Some('S') => return Ok(()),
+            Some('*') => cursor.skip_line(),
+            Some('M') => {
+                let vnum = cursor.number("the mobile's vnum")?;
+                let special = cursor.word("the special's name")?;
+                cursor.skip_line();
+                match u32::try_from(vnum) {
+                    Ok(vnum) => specials.push((line, vnum, special.to_ascii_lowercase())),
+                    Err(_) => cursor.unsupported(line, format!("special for mobile #{}", vnum)),
+                }
+            }
+            Some(other) => {
+                cursor.unsupported(line, format!("special '{}'", other));
+                cursor.skip_line();
+            }
+            None => return Err(ImportError { line, message: "the file ends inside #SPECIALS".to_string() }),
+        }
+    }
+}
@@ -0,0 +1,297 @@
+//! Areas group rooms under a name with the resets that keep them populated.
+//! Resets run in order, like a Diku zone file: a [`Reset::Give`] or [`Reset::Equip`] applies to the mobile
+//! the last [`Reset::Mob`] loaded, and is skipped with it when that mobile was already at its limit
+use super::{Direction, DoorState, Vnum, World};
+use crate::entity::EntityId;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A mobile or object template, by its vnum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Prototype {
+    Mob(u32),
+    Object(u32),
+}
+
+impl Display for Prototype {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Prototype::Mob(vnum) => write!(f, "mobile #{}", vnum),
+            Prototype::Object(vnum) => write!(f, "object #{}", vnum),
+        }
+    }
+}
+
+/// One step of an area's reset
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Reset {
+    /// Load a mobile into a room, unless `limit` of it are already in the world
+    Mob { mob: u32, room: Vnum, limit: usize },
+    /// Give an object to the last mobile loaded
+    Give { object: u32 },
+    /// Have the last mobile loaded wear or wield an object
+    Equip { object: u32, slot: String },
+    /// Load an object into a room, unless one is already there
+    Object { object: u32, room: Vnum },
+    /// Put an object into the last container loaded from prototype `container`
+    Put { object: u32, container: u32 },
+    /// Set a door's state, and the other side's
+    Door { room: Vnum, direction: Direction, state: DoorState },
+}
+
+/// What the game does for resets, since mobiles and objects are its own. Register it with
+/// [`Builder::populate`](crate::server::Builder::populate)
+pub trait Populate: Send + Sync {
+    /// How many instances of `prototype` exist, in `room` if given, or anywhere
+    fn count(&self, prototype: Prototype, room: Option<Vnum>) -> usize;
+    /// Make a new instance, not yet anywhere. `None` if there's no such prototype
+    fn create(&self, prototype: Prototype) -> Option<EntityId>;
+    /// Give `object` to mobile or container `to`, worn on `slot` if given. `false` if it can't be done
+    fn give(&self, object: EntityId, to: EntityId, slot: Option<&str>) -> bool;
+}
+
+/// How a reset went
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResetReport {
+    pub area: String,
+    /// Mobiles and objects created
+    pub loaded: usize,
+    /// `reset N: reason` for each reset that should have run and couldn't
+    pub failed: Vec<String>,
+}
+
+impl Display for ResetReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} loaded", self.area, self.loaded)?;
+        if !self.failed.is_empty() {
+            write!(f, ", {} failed ({})", self.failed.len(), self.failed.join("; "))?;
+        }
+        Ok(())
+    }
+}
+
+fn fifteen() -> u32 {
+    15
+}
+
+/// A named group of rooms, e.g. Midgaard
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Area {
+    pub name: String,
+    /// Accounts allowed to edit it, besides those with its [`build`](crate::permission::build) permission
+    #[serde(default)]
+    pub builders: Vec<String>,
+    /// The levels it's meant for
+    pub levels: (u32, u32),
+    /// The rooms it owns, first to last
+    pub vnums: (Vnum, Vnum),
+    #[serde(default)]
+    pub resets: Vec<Reset>,
+    /// How old the area gets before it resets again
+    #[serde(default = "fifteen")]
+    pub reset_minutes: u32,
+    /// Wait for the players to leave before resetting
+    #[serde(default)]
+    pub only_when_empty: bool,
+}
+
+impl Area {
+    pub fn new(name: &str, first: Vnum, last: Vnum) -> Self {
+        Area {
+            name: name.to_string(),
+            builders: Vec::new(),
+            levels: (1, 100),
+            vnums: (first, last),
+            resets: Vec::new(),
+            reset_minutes: fifteen(),
+            only_when_empty: false,
+        }
+    }
+
+    pub fn builder(mut self, account: &str) -> Self {
+        self.builders.push(account.to_string());
+        self
+    }
+
+    pub fn levels(mut self, min: u32, max: u32) -> Self {
+        self.levels = (min, max);
+        self
+    }
+
+    pub fn reset(mut self, reset: Reset) -> Self {
+        self.resets.push(reset);
+        self
+    }
+
+    pub fn reset_minutes(mut self, minutes: u32) -> Self {
+        self.reset_minutes = minutes;
+        self
+    }
+
+    pub fn only_when_empty(mut self) -> Self {
+        self.only_when_empty = true;
+        self
+    }
+
+    pub fn reset_every(&self) -> Duration {
+        Duration::from_secs(self.reset_minutes as u64 * 60)
+    }
+
+    pub fn contains(&self, vnum: Vnum) -> bool {
+        (self.vnums.0..=self.vnums.1).contains(&vnum)
+    }
+
+    pub fn is_builder(&self, account: &str) -> bool {
+        self.builders.iter().any(|b| b.eq_ignore_ascii_case(account))
+    }
+
+    /// Run every reset, putting what's loaded into `world`
+    pub fn run_resets(&self, world: &World, populate: &dyn Populate) -> ResetReport {
+        let mut report = ResetReport { area: self.name.clone(), loaded: 0, failed: Vec::new() };
+        // The mobile the last Mob reset loaded, None if it was skipped
+        let mut mob = None;
+        // The last object loaded from each prototype, for Put
+        let mut containers = HashMap::new();
+        for (n, reset) in self.resets.iter().enumerate() {
+            let mut fail = |reason: String| report.failed.push(format!("reset {}: {}", n + 1, reason));
+            let loaded = match reset {
+                Reset::Mob { mob: vnum, room, limit } => {
+                    mob = None;
+                    if populate.count(Prototype::Mob(*vnum), None) >= *limit {
+                        continue;
+                    }
+                    create(world, populate, Prototype::Mob(*vnum), Some(*room)).map(|entity| mob = Some(entity))
+                }
+                Reset::Give { object } | Reset::Equip { object, .. } => {
+                    let Some(mob) = mob else { continue };
+                    let slot = match reset {
+                        Reset::Equip { slot, .. } => Some(slot.as_str()),
+                        _ => None,
+                    };
+                    create(world, populate, Prototype::Object(*object), None).and_then(|entity| {
+                        containers.insert(*object, entity);
+                        match populate.give(entity, mob, slot) {
+                            true => Ok(()),
+                            false => Err(format!("{} can't take object #{}", mob, object)),
+                        }
+                    })
+                }
+                Reset::Object { object, room } => {
+                    if populate.count(Prototype::Object(*object), Some(*room)) > 0 {
+                        continue;
+                    }
+                    create(world, populate, Prototype::Object(*object), Some(*room)).map(|entity| {
+                        containers.insert(*object, entity);
+                    })
+                }
+                Reset::Put { object, container } => {
+                    let Some(&into) = containers.get(container) else { continue };
+                    create(world, populate, Prototype::Object(*object), None).and_then(|entity| {
+                        containers.insert(*object, entity);
+                        match populate.give(entity, into, None) {
+                            true => Ok(()),
+                            false => Err(format!("object #{} can't hold object #{}", container, object)),
+                        }
+                    })
+                }
+                Reset::Door { room, direction, state } => {
+                    if let Err(e) = world.set_door(*room, direction, *state) {
+                        fail(format!("{} {}: {}", room, direction, e));
+                    }
+                    continue;
+                }
+            };
+            match loaded {
+                Ok(()) => report.loaded += 1,
+                Err(reason) => fail(reason),
+            }
+        }
+        report
+    }
+}
+
+/// Create an instance of `prototype`, in `room` if given
+fn create(world: &World, populate: &dyn Populate, prototype: Prototype, room: Option<Vnum>)
+    -> Result<EntityId, String> {
+    if let Some(room) = room.filter(|&room| !world.contains(room)) {
+        return Err(format!("there is no room {}", room));
+    }
+    let entity = populate.create(prototype).ok_or_else(|| format!("there is no {}", prototype))?;
+    if let Some(room) = room {
+        world.move_entity(entity, room).map_err(|e| e.to_string())?;
+    }
+    Ok(entity)
+}
+
+#[derive(Debug)]
+struct Entry {
+    area: Area,
+    reset_at: Instant,
+    report: Option<ResetReport>,
+}
+
+/// Every area, by lowercase name, and when each last reset
+#[derive(Debug, Default)]
+pub struct Areas {
+    areas: Mutex<BTreeMap<String, Entry>>,
+}
+
+impl Areas {
+    pub fn new() -> Self {
+        Areas::default()
+    }
+
+    /// Add an area, or replace the one with the same name. It counts as just reset
+    pub fn insert(&self, area: Area) -> Option<Area> {
+        let entry = Entry { area, reset_at: Instant::now(), report: None };
+        self.areas.lock().unwrap().insert(entry.area.name.to_ascii_lowercase(), entry).map(|e| e.area)
+    }
+
+    pub fn remove(&self, name: &str) -> Option<Area> {
+        self.areas.lock().unwrap().remove(&name.to_ascii_lowercase()).map(|e| e.area)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Area> {
+        self.areas.lock().unwrap().get(&name.to_ascii_lowercase()).map(|e| e.area.clone())
+    }
+
+    /// Every area, in name order
+    pub fn all(&self) -> Vec<Area> {
+        self.areas.lock().unwrap().values().map(|e| e.area.clone()).collect()
+    }
+
+    /// The area owning room `vnum`
+    pub fn area_of(&self, vnum: Vnum) -> Option<Area> {
+        self.areas.lock().unwrap().values().find(|e| e.area.contains(vnum)).map(|e| e.area.clone())
+    }
+
+    /// How long since the area last reset
+    pub fn age(&self, name: &str) -> Option<Duration> {
+        self.areas.lock().unwrap().get(&name.to_ascii_lowercase()).map(|e| e.reset_at.elapsed())
+    }
+
+    /// Areas old enough to reset. Those that only reset when empty are left out while `occupied` says otherwise
+    pub fn due(&self, occupied: impl Fn(&Area) -> bool) -> Vec<Area> {
+        let areas = self.areas.lock().unwrap();
+        let aged = areas.values().filter(|e| e.reset_at.elapsed() >= e.area.reset_every()).map(|e| &e.area);
+        aged.filter(|a| !(a.only_when_empty && occupied(a))).cloned().collect()
+    }
+
+    /// Start the area's age over, and keep how the reset went
+    pub fn mark_reset(&self, report: ResetReport) {
+        if let Some(entry) = self.areas.lock().unwrap().get_mut(&report.area.to_ascii_lowercase()) {
+            entry.reset_at = Instant::now();
+            entry.report = Some(report);
+        }
+    }
+
+    /// How the area's last reset went
+    pub fn last_report(&self, name: &str) -> Option<ResetReport> {
+        self.areas.lock().unwrap().get(&name.to_ascii_lowercase()).and_then(|e| e.report.clone())
+    }
+}
@@ -0,0 +1,200 @@
+//! Opening, closing, locking, picking and bashing doors. A door is one [`Door`] on each side of a passage,
+//! and every change to one is made to the other in the same step, so both sides always agree
+use super::{Direction, Door, DoorState, Room, Vnum, World};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+/// Something a character does to a door
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoorAction {
+    Open,
+    Close,
+    /// Needs the key
+    Lock,
+    /// Needs the key
+    Unlock,
+    /// Unlock without the key, if the skill roll succeeds
+    Pick,
+    /// Break it open, if the skill roll succeeds
+    Bash,
+}
+
+impl DoorAction {
+    /// What the door ends up as
+    fn result(&self) -> DoorState {
+        match self {
+            DoorAction::Open | DoorAction::Bash => DoorState::Open,
+            DoorAction::Close | DoorAction::Unlock | DoorAction::Pick => DoorState::Closed,
+            DoorAction::Lock => DoorState::Locked,
+        }
+    }
+}
+
+impl Display for DoorAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DoorAction::Open => "open",
+            DoorAction::Close => "close",
+            DoorAction::Lock => "lock",
+            DoorAction::Unlock => "unlock",
+            DoorAction::Pick => "pick",
+            DoorAction::Bash => "bash",
+        })
+    }
+}
+
+/// Why a [`DoorAction`] didn't happen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoorError {
+    NoSuchRoom(Vnum),
+    /// No door that way, or by that name
+    NoDoor,
+    AlreadyOpen,
+    AlreadyClosed,
+    AlreadyLocked,
+    /// Opening a locked door
+    Locked,
+    /// Locking an open door
+    Open,
+    /// Unlocking or picking a door that isn't locked
+    NotLocked,
+    /// The door has no lock
+    NoLock,
+    NoKey,
+    Pickproof,
+    /// Bashing a door that isn't bashable
+    TooSturdy,
+    /// The pick or bash roll failed
+    Failed,
+}
+
+impl Display for DoorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DoorError::NoSuchRoom(vnum) => write!(f, "there is no room {}", vnum),
+            DoorError::NoDoor => f.write_str("there's no door there"),
+            DoorError::AlreadyOpen => f.write_str("it's already open"),
+            DoorError::AlreadyClosed => f.write_str("it's already closed"),
+            DoorError::AlreadyLocked => f.write_str("it's already locked"),
+            DoorError::Locked => f.write_str("it's locked"),
+            DoorError::Open => f.write_str("it's open"),
+            DoorError::NotLocked => f.write_str("it isn't locked"),
+            DoorError::NoLock => f.write_str("it has no lock"),
+            DoorError::NoKey => f.write_str("you lack the key"),
+            DoorError::Pickproof => f.write_str("it can't be picked"),
+            DoorError::TooSturdy => f.write_str("it's too sturdy to break"),
+            DoorError::Failed => f.write_str("you failed"),
+        }
+    }
+}
+
+impl std::error::Error for DoorError {}
+
+/// A door that changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operated {
+    /// The exit it's in
+    pub direction: Direction,
+    pub name: String,
+    pub state: DoorState,
+    /// The room on the other side
+    pub to: Vnum,
+    /// The exit back, if it has a door too
+    pub back: Option<Direction>,
+}
+
+/// The exit in `room` that leads back to `from`, with a door. The reverse direction if it qualifies
+fn back(room: &Room, from: Vnum, direction: &Direction) -> Option<Direction> {
+    let leads_back = |d: &Direction| room.exits.get(d).is_some_and(|e| e.to == from && e.door.is_some());
+    if let Some(reverse) = direction.reverse().filter(leads_back) {
+        return Some(reverse);
+    }
+    room.exits.keys().find(|d| leads_back(d)).cloned()
+}
+
+/// Whether the door allows `action`, before any key check or roll
+fn check(door: &Door, action: DoorAction) -> Result<(), DoorError> {
+    match (action, door.state) {
+        (DoorAction::Open | DoorAction::Bash, DoorState::Open) => Err(DoorError::AlreadyOpen),
+        (DoorAction::Open, DoorState::Locked) => Err(DoorError::Locked),
+        (DoorAction::Close, DoorState::Closed | DoorState::Locked) => Err(DoorError::AlreadyClosed),
+        (DoorAction::Lock | DoorAction::Unlock | DoorAction::Pick, _) if door.key.is_none() => Err(DoorError::NoLock),
+        (DoorAction::Lock, DoorState::Open) => Err(DoorError::Open),
+        (DoorAction::Lock, DoorState::Locked) => Err(DoorError::AlreadyLocked),
+        (DoorAction::Unlock | DoorAction::Pick, DoorState::Open | DoorState::Closed) => Err(DoorError::NotLocked),
+        (DoorAction::Pick, _) if door.pickproof => Err(DoorError::Pickproof),
+        (DoorAction::Bash, _) if !door.bashable => Err(DoorError::TooSturdy),
+        _ => Ok(()),
+    }
+}
+
+impl World {
+    /// The exit with a door that `name` means: a direction, a door's keyword, or the start of a custom direction.
+    /// Hidden doors are only found by direction
+    pub fn find_door(&self, room: Vnum, name: &str) -> Option<Direction> {
+        self.with(room, |room| {
+            let doors = || room.exits.iter().filter_map(|(d, e)| Some((d, e.door.as_ref()?)));
+            let Ok(direction) = name.parse::<Direction>();
+            if let Some((direction, _)) = doors().find(|(d, _)| **d == direction) {
+                return Some(direction.clone());
+            }
+            if let Some((direction, _)) = doors().find(|(_, door)| !door.hidden && door.matches(name)) {
+                return Some(direction.clone());
+            }
+            let (direction, exit) = room.find_exit(&direction)?;
+            exit.door.as_ref().map(|_| direction.clone())
+        })?
+    }
+
+    /// Do `action` to the door in `room`'s exit `direction`, and the same to the other side.
+    /// `allowed` is asked last, whether the character has the key to lock or unlock, or whether the pick or bash
+    /// roll succeeded. The world is locked while it runs, so it must not use it
+    pub fn operate(
+        &self,
+        room: Vnum,
+        direction: &Direction,
+        action: DoorAction,
+        allowed: impl FnOnce(&Door) -> bool,
+    ) -> Result<Operated, DoorError> {
+        let mut rooms = self.rooms.write().unwrap();
+        let here = rooms.rooms.get(&room).ok_or(DoorError::NoSuchRoom(room))?;
+        let exit = here.exits.get(direction).ok_or(DoorError::NoDoor)?;
+        let door = exit.door.as_ref().ok_or(DoorError::NoDoor)?;
+        check(door, action)?;
+        if !allowed(door) {
+            return Err(match action {
+                DoorAction::Lock | DoorAction::Unlock => DoorError::NoKey,
+                _ => DoorError::Failed,
+            });
+        }
+        let (to, name) = (exit.to, door.name().to_string());
+        let state = action.result();
+        let back = set(&mut rooms.rooms, room, direction, state);
+        Ok(Operated { direction: direction.clone(), name, state, to, back })
+    }
+
+    /// Put the door in `room`'s exit `direction`, and the other side, in `state` whatever it was, e.g. for a reset
+    pub fn set_door(&self, room: Vnum, direction: &Direction, state: DoorState) -> Result<(), DoorError> {
+        let mut rooms = self.rooms.write().unwrap();
+        let here = rooms.rooms.get(&room).ok_or(DoorError::NoSuchRoom(room))?;
+        here.exits.get(direction).and_then(|e| e.door.as_ref()).ok_or(DoorError::NoDoor)?;
+        set(&mut rooms.rooms, room, direction, state);
+        Ok(())
+    }
+}
+
+/// The door in `room`'s exit `direction`, and the room it leads to
+fn door_mut<'a>(rooms: &'a mut BTreeMap<Vnum, Room>, room: Vnum, direction: &Direction)
+    -> Option<(Vnum, &'a mut Door)> {
+    let exit = rooms.get_mut(&room)?.exits.get_mut(direction)?;
+    exit.door.as_mut().map(|door| (exit.to, door))
+}
+
+/// Put the door in `room`'s exit `direction` and the one on the other side in `state`. Returns the way back
+fn set(rooms: &mut BTreeMap<Vnum, Room>, room: Vnum, direction: &Direction, state: DoorState) -> Option<Direction> {
+    let (to, here) = door_mut(rooms, room, direction)?;
+    here.state = state;
+    let back = rooms.get(&to).and_then(|there| back(there, room, direction))?;
+    door_mut(rooms, to, &back)?.1.state = state;
+    Some(back)
+}
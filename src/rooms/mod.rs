@@ -1 +1,163 @@
-mod room;
+//! This module provides [`Room`]s and their [`Door`]s, the [`World`] that holds them,
+//! and the [`Area`]s that group and repopulate them.
+//! The world is shared between connections: look rooms up, change them in place,
+//! and move entities between them through it so room contents stay consistent.
+//! [`are`] imports rooms and areas from ROM and Merc area files
+pub mod are;
+pub mod area;
+pub mod door;
+#[cfg(test)]
+mod tests;
+pub mod room;
+
+pub use area::{Area, Areas, Populate, Reset};
+pub use door::{DoorAction, DoorError};
+pub use room::{Direction, Door, DoorState, Exit, ExtraDescription, Room, RoomFlag, Sector, Vnum};
+
+use crate::entity::EntityId;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display};
+use std::sync::RwLock;
+
+/// Why the world refused a change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorldError {
+    NoSuchRoom(Vnum),
+    /// Rooms with entities in them can't be removed
+    Occupied(Vnum),
+}
+
+impl Display for WorldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorldError::NoSuchRoom(vnum) => write!(f, "there is no room {}", vnum),
+            WorldError::Occupied(vnum) => write!(f, "room {} isn't empty", vnum),
+        }
+    }
+}
+
+impl std::error::Error for WorldError {}
+
+#[derive(Debug, Default)]
+struct Rooms {
+    rooms: BTreeMap<Vnum, Room>,
+    /// Where each entity is, the reverse of every room's contents
+    locations: HashMap<EntityId, Vnum>,
+}
+
+/// Every room, by vnum
+#[derive(Debug, Default)]
+pub struct World {
+    rooms: RwLock<Rooms>,
+}
+
+impl World {
+    pub fn new() -> Self {
+        World::default()
+    }
+
+    /// Add a room, or replace the one with the same vnum. A replaced room's contents carry over
+    pub fn insert(&self, mut room: Room) -> Option<Room> {
+        let mut rooms = self.rooms.write().unwrap();
+        room.contents.clear();
+        let old = rooms.rooms.remove(&room.vnum);
+        if let Some(old) = &old {
+            room.contents.clone_from(&old.contents);
+        }
+        rooms.rooms.insert(room.vnum, room);
+        old
+    }
+
+    /// Remove an empty room
+    pub fn remove(&self, vnum: Vnum) -> Result<Room, WorldError> {
+        let mut rooms = self.rooms.write().unwrap();
+        match rooms.rooms.get(&vnum) {
+            None => Err(WorldError::NoSuchRoom(vnum)),
+            Some(room) if !room.contents.is_empty() => Err(WorldError::Occupied(vnum)),
+            Some(_) => Ok(rooms.rooms.remove(&vnum).unwrap()),
+        }
+    }
+
+    pub fn contains(&self, vnum: Vnum) -> bool {
+        self.rooms.read().unwrap().rooms.contains_key(&vnum)
+    }
+
+    /// A copy of the room
+    pub fn get(&self, vnum: Vnum) -> Option<Room> {
+        self.rooms.read().unwrap().rooms.get(&vnum).cloned()
+    }
+
+    /// Look at a room without copying it. Don't touch the world from `f`, the lock is held
+    pub fn with<R>(&self, vnum: Vnum, f: impl FnOnce(&Room) -> R) -> Option<R> {
+        self.rooms.read().unwrap().rooms.get(&vnum).map(f)
+    }
+
+    /// Change a room in place. Its vnum and contents are the world's to manage, changes to them are undone
+    pub fn update<R>(&self, vnum: Vnum, f: impl FnOnce(&mut Room) -> R) -> Option<R> {
+        let mut rooms = self.rooms.write().unwrap();
+        let room = rooms.rooms.get_mut(&vnum)?;
+        let contents = std::mem::take(&mut room.contents);
+        let result = f(room);
+        room.vnum = vnum;
+        room.contents = contents;
+        Some(result)
+    }
+
+    /// Every vnum, in order
+    pub fn vnums(&self) -> Vec<Vnum> {
+        self.rooms.read().unwrap().rooms.keys().copied().collect()
+    }
+
+    /// Copies of every room, in vnum order
+    pub fn rooms(&self) -> Vec<Room> {
+        self.rooms.read().unwrap().rooms.values().cloned().collect()
+    }
+
+    /// Call `f` with every room in vnum order, holding the lock throughout
+    pub fn for_each(&self, mut f: impl FnMut(&Room)) {
+        self.rooms.read().unwrap().rooms.values().for_each(&mut f);
+    }
+
+    pub fn len(&self) -> usize {
+        self.rooms.read().unwrap().rooms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rooms.read().unwrap().rooms.is_empty()
+    }
+
+    /// Where `entity` is
+    pub fn location(&self, entity: EntityId) -> Option<Vnum> {
+        self.rooms.read().unwrap().locations.get(&entity).copied()
+    }
+
+    /// Put `entity` in room `to`, taking it out of wherever it was. Returns the room it left
+    pub fn move_entity(&self, entity: EntityId, to: Vnum) -> Result<Option<Vnum>, WorldError> {
+        let mut guard = self.rooms.write().unwrap();
+        let Rooms { rooms, locations } = &mut *guard;
+        rooms.get_mut(&to).ok_or(WorldError::NoSuchRoom(to))?.contents.insert(entity);
+        let from = locations.insert(entity, to).filter(|&from| from != to);
+        if let Some(room) = from.and_then(|from| rooms.get_mut(&from)) {
+            room.contents.remove(&entity);
+        }
+        Ok(from)
+    }
+
+    /// Take `entity` out of the world, e.g. when it's extracted or its player quits. Returns where it was
+    pub fn remove_entity(&self, entity: EntityId) -> Option<Vnum> {
+        let mut guard = self.rooms.write().unwrap();
+        let Rooms { rooms, locations } = &mut *guard;
+        let from = locations.remove(&entity)?;
+        if let Some(room) = rooms.get_mut(&from) {
+            room.contents.remove(&entity);
+        }
+        Some(from)
+    }
+
+    /// Exits leading to rooms that don't exist, as `(room, direction, target)`
+    pub fn dangling_exits(&self) -> Vec<(Vnum, Direction, Vnum)> {
+        let rooms = self.rooms.read().unwrap();
+        let exits = rooms.rooms.values().flat_map(|room| room.exits.iter().map(move |(d, e)| (room.vnum, d, e.to)));
+        exits.filter(|(_, _, to)| !rooms.rooms.contains_key(to)).map(|(v, d, to)| (v, d.clone(), to)).collect()
+    }
+}
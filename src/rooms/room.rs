@@ -0,0 +1,426 @@
+//! A single room: what it looks like, where its exits lead, and what's in it
+use crate::entity::EntityId;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+/// A room's virtual number, stable across reboots and what area files and exits refer to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Vnum(pub u32);
+
+impl Display for Vnum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Which way an exit leads. Anything that isn't a compass direction, up or down is [`Direction::Custom`],
+/// e.g. `portal` or `hole`. Saved by name, so exits can be keyed by direction in JSON
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum Direction {
+    North,
+    East,
+    South,
+    West,
+    Up,
+    Down,
+    Northeast,
+    Northwest,
+    Southeast,
+    Southwest,
+    Custom(String),
+}
+
+impl Direction {
+    /// The standard directions, in the order exits are usually listed
+    pub const ALL: [Direction; 10] = [
+        Direction::North,
+        Direction::East,
+        Direction::South,
+        Direction::West,
+        Direction::Up,
+        Direction::Down,
+        Direction::Northeast,
+        Direction::Northwest,
+        Direction::Southeast,
+        Direction::Southwest,
+    ];
+
+    pub fn name(&self) -> &str {
+        match self {
+            Direction::North => "north",
+            Direction::East => "east",
+            Direction::South => "south",
+            Direction::West => "west",
+            Direction::Up => "up",
+            Direction::Down => "down",
+            Direction::Northeast => "northeast",
+            Direction::Northwest => "northwest",
+            Direction::Southeast => "southeast",
+            Direction::Southwest => "southwest",
+            Direction::Custom(name) => name,
+        }
+    }
+
+    /// The abbreviation client mappers use, e.g. `ne`. Custom directions aren't abbreviated
+    pub fn short(&self) -> &str {
+        match self {
+            Direction::North => "n",
+            Direction::East => "e",
+            Direction::South => "s",
+            Direction::West => "w",
+            Direction::Up => "u",
+            Direction::Down => "d",
+            Direction::Northeast => "ne",
+            Direction::Northwest => "nw",
+            Direction::Southeast => "se",
+            Direction::Southwest => "sw",
+            Direction::Custom(name) => name,
+        }
+    }
+
+    /// The direction back, if there is an obvious one
+    pub fn reverse(&self) -> Option<Direction> {
+        Some(match self {
+            Direction::North => Direction::South,
+            Direction::East => Direction::West,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Northeast => Direction::Southwest,
+            Direction::Northwest => Direction::Southeast,
+            Direction::Southeast => Direction::Northwest,
+            Direction::Southwest => Direction::Northeast,
+            Direction::Custom(_) => return None,
+        })
+    }
+}
+
+impl Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl From<String> for Direction {
+    fn from(s: String) -> Self {
+        let Ok(direction) = s.parse();
+        direction
+    }
+}
+
+impl From<Direction> for String {
+    fn from(direction: Direction) -> Self {
+        direction.name().to_string()
+    }
+}
+
+impl FromStr for Direction {
+    type Err = std::convert::Infallible;
+
+    /// Accepts full names and the usual abbreviations (`n`, `ne`, `u`...). Anything else is a custom direction
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        let abbreviated = match s.as_str() {
+            "n" => Some(Direction::North),
+            "e" => Some(Direction::East),
+            "s" => Some(Direction::South),
+            "w" => Some(Direction::West),
+            "u" => Some(Direction::Up),
+            "d" => Some(Direction::Down),
+            "ne" => Some(Direction::Northeast),
+            "nw" => Some(Direction::Northwest),
+            "se" => Some(Direction::Southeast),
+            "sw" => Some(Direction::Southwest),
+            _ => None,
+        };
+        let standard = || Direction::ALL.into_iter().find(|d| d.name() == s);
+        Ok(abbreviated.or_else(standard).unwrap_or(Direction::Custom(s)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DoorState {
+    Open,
+    Closed,
+    Locked,
+}
+
+/// A door in an exit. Open, close, lock and pick it with [`World::operate`](super::World::operate),
+/// which keeps the door on the other side in the same state
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Door {
+    /// What players call it, e.g. `gate`. The first one is its name
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    pub state: DoorState,
+    /// The vnum of the object that locks and unlocks it. Without one it can't be locked
+    #[serde(default)]
+    pub key: Option<u32>,
+    /// Taken off the picker's skill, in percent
+    #[serde(default)]
+    pub pick_difficulty: u8,
+    #[serde(default)]
+    pub pickproof: bool,
+    /// Whether it can be broken open
+    #[serde(default)]
+    pub bashable: bool,
+    /// Left out of exit lists while closed, and only found by direction, not by name
+    #[serde(default)]
+    pub hidden: bool,
+}
+
+impl Door {
+    /// A closed door
+    pub fn new(keywords: &[&str]) -> Self {
+        Door {
+            keywords: keywords.iter().map(|k| k.to_string()).collect(),
+            state: DoorState::Closed,
+            key: None,
+            pick_difficulty: 0,
+            pickproof: false,
+            bashable: false,
+            hidden: false,
+        }
+    }
+
+    pub fn state(mut self, state: DoorState) -> Self {
+        self.state = state;
+        self
+    }
+
+    pub fn key(mut self, key: u32) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    pub fn pick_difficulty(mut self, difficulty: u8) -> Self {
+        self.pick_difficulty = difficulty;
+        self
+    }
+
+    pub fn pickproof(mut self) -> Self {
+        self.pickproof = true;
+        self
+    }
+
+    pub fn bashable(mut self) -> Self {
+        self.bashable = true;
+        self
+    }
+
+    pub fn hidden(mut self) -> Self {
+        self.hidden = true;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        self.keywords.first().map_or("door", String::as_str)
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state == DoorState::Open
+    }
+
+    /// Whether `keyword` is one of the keywords or the start of one
+    pub fn matches(&self, keyword: &str) -> bool {
+        let keyword = keyword.to_ascii_lowercase();
+        !keyword.is_empty() && self.keywords.iter().any(|k| k.to_ascii_lowercase().starts_with(&keyword))
+    }
+}
+
+/// A way out of a room
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Exit {
+    pub to: Vnum,
+    /// What `look <direction>` shows
+    #[serde(default)]
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub door: Option<Door>,
+}
+
+impl Exit {
+    pub fn to(to: Vnum) -> Self {
+        Exit { to, description: String::new(), door: None }
+    }
+
+    pub fn door(mut self, door: Door) -> Self {
+        self.door = Some(door);
+        self
+    }
+
+    /// Whether there's a closed door in the way
+    pub fn is_closed(&self) -> bool {
+        self.door.as_ref().is_some_and(|d| !d.is_open())
+    }
+
+    /// Whether players can see it: anything but a closed hidden door
+    pub fn is_visible(&self) -> bool {
+        self.door.as_ref().is_none_or(|d| d.is_open() || !d.hidden)
+    }
+}
+
+/// Something in a room worth a closer look, e.g. `look fountain`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtraDescription {
+    pub keywords: Vec<String>,
+    pub description: String,
+}
+
+impl ExtraDescription {
+    /// Whether `keyword` is one of the keywords or the start of one
+    pub fn matches(&self, keyword: &str) -> bool {
+        let keyword = keyword.to_ascii_lowercase();
+        !keyword.is_empty() && self.keywords.iter().any(|k| k.to_ascii_lowercase().starts_with(&keyword))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomFlag {
+    /// Can't be seen in without a light
+    Dark,
+    /// Mobiles won't wander in
+    NoMob,
+    Indoors,
+    /// No fighting
+    Safe,
+    /// At most two characters at a time
+    Private,
+    /// One character at a time
+    Solitary,
+    NoRecall,
+    /// Only immortals may enter
+    GodsOnly,
+}
+
+/// The terrain, which decides movement cost and what's needed to get through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sector {
+    #[default]
+    Inside,
+    City,
+    Field,
+    Forest,
+    Hills,
+    Mountain,
+    /// Water that can be swum
+    Swim,
+    /// Water that needs a boat
+    NoSwim,
+    Underwater,
+    Air,
+    Desert,
+}
+
+/// A place in the world
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Room {
+    pub vnum: Vnum,
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub extra: Vec<ExtraDescription>,
+    #[serde(default)]
+    pub exits: BTreeMap<Direction, Exit>,
+    #[serde(default)]
+    pub flags: BTreeSet<RoomFlag>,
+    #[serde(default)]
+    pub sector: Sector,
+    /// The entities here, kept up to date by [`World::move_entity`](super::World::move_entity).
+    /// Not saved with the room, since entities are saved on their own
+    #[serde(skip)]
+    pub contents: BTreeSet<EntityId>,
+}
+
+impl Room {
+    pub fn new(vnum: Vnum, name: &str) -> Self {
+        Room {
+            vnum,
+            name: name.to_string(),
+            description: String::new(),
+            extra: Vec::new(),
+            exits: BTreeMap::new(),
+            flags: BTreeSet::new(),
+            sector: Sector::default(),
+            contents: BTreeSet::new(),
+        }
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+
+    pub fn extra(mut self, keywords: &[&str], description: &str) -> Self {
+        let keywords = keywords.iter().map(|k| k.to_string()).collect();
+        self.extra.push(ExtraDescription { keywords, description: description.to_string() });
+        self
+    }
+
+    pub fn exit(mut self, direction: Direction, exit: Exit) -> Self {
+        self.exits.insert(direction, exit);
+        self
+    }
+
+    pub fn flag(mut self, flag: RoomFlag) -> Self {
+        self.flags.insert(flag);
+        self
+    }
+
+    pub fn sector(mut self, sector: Sector) -> Self {
+        self.sector = sector;
+        self
+    }
+
+    pub fn has_flag(&self, flag: RoomFlag) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    /// The first extra description `keyword` matches
+    pub fn extra_description(&self, keyword: &str) -> Option<&str> {
+        self.extra.iter().find(|e| e.matches(keyword)).map(|e| e.description.as_str())
+    }
+
+    /// The exits players can see, see [`Exit::is_visible`]
+    pub fn visible_exits(&self) -> impl Iterator<Item = (&Direction, &Exit)> {
+        self.exits.iter().filter(|(_, e)| e.is_visible())
+    }
+
+    /// The MSDP `ROOM_EXITS` table client mappers read: each visible exit's abbreviation to `O` if it's open
+    /// or `C` if a door is closed
+    pub fn msdp_exits(&self) -> BTreeMap<String, &'static str> {
+        let exits = self.visible_exits().map(|(d, e)| (d.short().to_string(), if e.is_closed() { "C" } else { "O" }));
+        exits.collect()
+    }
+
+    /// The MSDP `ROOM` table: its vnum, name, area, terrain,
+    /// and each visible exit's abbreviation to the vnum it leads to
+    pub fn msdp(&self, area: &str) -> serde_json::Value {
+        let exits: BTreeMap<_, _> = self.visible_exits().map(|(d, e)| (d.short(), e.to.0.to_string())).collect();
+        let terrain = serde_json::to_value(self.sector).unwrap_or_default();
+        serde_json::json!({
+            "VNUM": self.vnum.0.to_string(),
+            "NAME": self.name,
+            "AREA": area,
+            "TERRAIN": terrain,
+            "EXITS": exits,
+        })
+    }
+
+    /// The exit `direction` leads through. Custom directions also match by prefix, so `port` finds `portal`
+    pub fn find_exit(&self, direction: &Direction) -> Option<(&Direction, &Exit)> {
+        self.exits.get_key_value(direction).or_else(|| match direction {
+            Direction::Custom(prefix) if !prefix.is_empty() => self.exits.iter().find(|(d, _)| {
+                matches!(d, Direction::Custom(name) if name.starts_with(prefix.as_str()))
+            }),
+            _ => None,
+        })
+    }
+}
@@ -0,0 +1,428 @@
+use super::area::Prototype;
+use super::{are, Area, Areas, Direction, DoorState, Exit, Populate, Reset, Room, RoomFlag, Sector, Vnum, World};
+use super::WorldError;
+use crate::entity::EntityId;
+use std::sync::{Arc, Mutex};
+
+fn temple() -> Room {
+    Room::new(Vnum(3001), "The Temple of Midgaard")
+        .description("You are in the southern end of the temple hall.")
+        .extra(&["altar", "stone"], "A huge altar of white marble.")
+        .exit(Direction::North, Exit::to(Vnum(3054)))
+        .exit(Direction::Custom("portal".to_string()), Exit::to(Vnum(1200)))
+        .flag(RoomFlag::Safe)
+        .flag(RoomFlag::NoMob)
+        .sector(Sector::City)
+}
+
+#[test]
+fn rooms() {
+    assert_eq!("NE".parse::<Direction>().unwrap(), Direction::Northeast);
+    assert_eq!("down".parse::<Direction>().unwrap(), Direction::Down);
+    assert_eq!("Hole".parse::<Direction>().unwrap(), Direction::Custom("hole".to_string()));
+    assert_eq!(Direction::Southwest.reverse(), Some(Direction::Northeast));
+    assert_eq!(Direction::Custom("hole".to_string()).reverse(), None);
+
+    let room = temple();
+    assert_eq!(room.extra_description("ALT"), Some("A huge altar of white marble."));
+    assert_eq!(room.extra_description("floor"), None);
+    assert_eq!(room.find_exit(&Direction::North).map(|(_, e)| e.to), Some(Vnum(3054)));
+    assert_eq!(room.find_exit(&"por".parse().unwrap()).map(|(d, _)| d.name()), Some("portal"));
+    assert!(room.find_exit(&Direction::South).is_none());
+    assert!(room.has_flag(RoomFlag::Safe) && !room.has_flag(RoomFlag::Dark));
+
+    let json = serde_json::to_value(&room).unwrap();
+    assert_eq!(json["exits"]["portal"]["to"], 1200);
+    assert_eq!(json["flags"], serde_json::json!(["no_mob", "safe"]));
+    assert_eq!(serde_json::from_value::<Room>(json).unwrap(), room);
+}
+
+#[test]
+fn world() {
+    let world = Arc::new(World::new());
+    world.insert(temple());
+    world.insert(Room::new(Vnum(3054), "By the Temple Altar").exit(Direction::South, Exit::to(Vnum(3001))));
+    assert_eq!(world.vnums(), [Vnum(3001), Vnum(3054)]);
+    assert_eq!(world.dangling_exits(), [(Vnum(3001), Direction::Custom("portal".to_string()), Vnum(1200))]);
+
+    let (alice, bob) = (EntityId(1), EntityId(2));
+    assert_eq!(world.move_entity(alice, Vnum(3001)), Ok(None));
+    assert_eq!(world.move_entity(bob, Vnum(3001)), Ok(None));
+    assert_eq!(world.move_entity(bob, Vnum(3054)), Ok(Some(Vnum(3001))));
+    assert_eq!(world.move_entity(bob, Vnum(9999)), Err(WorldError::NoSuchRoom(Vnum(9999))));
+    assert_eq!(world.location(bob), Some(Vnum(3054)));
+    assert_eq!(world.with(Vnum(3001), |r| r.contents.iter().copied().collect::<Vec<_>>()), Some(vec![alice]));
+
+    // Contents survive edits and replacement, and can't be changed behind the world's back
+    world.update(Vnum(3001), |r| {
+        r.name = "The Temple".to_string();
+        r.contents.clear();
+    });
+    world.insert(world.get(Vnum(3001)).unwrap().description("Rebuilt."));
+    assert_eq!(world.get(Vnum(3001)).unwrap().contents.len(), 1);
+    assert_eq!(world.remove(Vnum(3001)), Err(WorldError::Occupied(Vnum(3001))));
+    assert_eq!(world.remove_entity(alice), Some(Vnum(3001)));
+    assert_eq!(world.remove(Vnum(3001)).unwrap().name, "The Temple");
+    assert_eq!(world.len(), 1);
+
+    let threads: Vec<_> = (10..20)
+        .map(|id| {
+            let world = world.clone();
+            std::thread::spawn(move || world.move_entity(EntityId(id), Vnum(3054)).unwrap())
+        })
+        .collect();
+    for thread in threads {
+        assert_eq!(thread.join().unwrap(), None);
+    }
+    let mut count = 0;
+    world.for_each(|r| count += r.contents.len());
+    assert_eq!(count, 11);
+}
+
+/// Mobiles and objects as a game might keep them: what each entity is, and what it was given
+#[derive(Default)]
+struct Things {
+    world: Arc<World>,
+    made: Mutex<Vec<Prototype>>,
+    given: Mutex<Vec<(EntityId, EntityId, Option<String>)>>,
+}
+
+impl Populate for Things {
+    fn count(&self, prototype: Prototype, room: Option<Vnum>) -> usize {
+        let made = self.made.lock().unwrap();
+        let instances = made.iter().enumerate().filter(|(_, p)| **p == prototype);
+        instances.filter(|(id, _)| room.is_none() || self.world.location(EntityId(*id as u64)) == room).count()
+    }
+
+    fn create(&self, prototype: Prototype) -> Option<EntityId> {
+        if prototype == Prototype::Object(666) {
+            return None;
+        }
+        let mut made = self.made.lock().unwrap();
+        made.push(prototype);
+        Some(EntityId(made.len() as u64 - 1))
+    }
+
+    fn give(&self, object: EntityId, to: EntityId, slot: Option<&str>) -> bool {
+        self.given.lock().unwrap().push((object, to, slot.map(str::to_string)));
+        true
+    }
+}
+
+#[test]
+fn resets() {
+    let things = Things::default();
+    things.world.insert(temple());
+    let area = Area::new("Midgaard", Vnum(3000), Vnum(3099))
+        .builder("Hatchet")
+        .levels(1, 10)
+        .reset(Reset::Mob { mob: 3005, room: Vnum(3001), limit: 1 })
+        .reset(Reset::Equip { object: 3020, slot: "wield".to_string() })
+        .reset(Reset::Give { object: 3010 })
+        .reset(Reset::Put { object: 3011, container: 3010 })
+        .reset(Reset::Object { object: 3099, room: Vnum(3001) })
+        .reset(Reset::Object { object: 666, room: Vnum(3001) })
+        .reset(Reset::Mob { mob: 3006, room: Vnum(3050), limit: 2 })
+        .reset(Reset::Door { room: Vnum(3001), direction: Direction::North, state: DoorState::Locked });
+    assert!(area.contains(Vnum(3099)) && !area.contains(Vnum(3100)));
+    assert!(area.is_builder("hatchet"));
+
+    let report = area.run_resets(&things.world, &things);
+    assert_eq!(report.loaded, 5);
+    assert_eq!(report.failed, [
+        "reset 6: there is no object #666",
+        "reset 7: there is no room #3050",
+        "reset 8: #3001 north: there's no door there",
+    ]);
+    let (guard, wielded, given) = (EntityId(0), EntityId(1), EntityId(2));
+    assert_eq!(things.world.location(guard), Some(Vnum(3001)));
+    assert_eq!(things.given.lock().unwrap()[..], [
+        (wielded, guard, Some("wield".to_string())),
+        (given, guard, None),
+        (EntityId(3), given, None),
+    ]);
+
+    // The guard is at its limit and the object is still there, so nothing new is loaded or given
+    let report = area.run_resets(&things.world, &things);
+    assert_eq!((report.loaded, report.failed.len()), (0, 3));
+    assert_eq!(report.to_string().lines().count(), 1);
+    assert!(report.to_string().starts_with("Midgaard: 0 loaded, 3 failed (reset 6: "));
+
+    let json = serde_json::to_value(&area).unwrap();
+    assert_eq!(json["resets"][1], serde_json::json!({"type": "equip", "object": 3020, "slot": "wield"}));
+    assert_eq!(serde_json::from_value::<Area>(json).unwrap(), area);
+
+    let areas = Areas::new();
+    areas.insert(area.reset_minutes(0).only_when_empty());
+    areas.insert(Area::new("Limbo", Vnum(1), Vnum(99)));
+    assert_eq!(areas.area_of(Vnum(3001)).map(|a| a.name), Some("Midgaard".to_string()));
+    assert_eq!(areas.due(|_| false).iter().map(|a| a.name.as_str()).collect::<Vec<_>>(), ["Midgaard"]);
+    assert!(areas.due(|a| a.name == "Midgaard").is_empty());
+    areas.mark_reset(report);
+    assert_eq!(areas.last_report("midgaard").map(|r| r.loaded), Some(0));
+}
+
+#[test]
+fn doors() {
+    use super::{Door, DoorAction, DoorError};
+
+    let world = World::new();
+    let gate = || Door::new(&["gate", "iron"]).state(DoorState::Locked).key(3123).pick_difficulty(30).bashable();
+    world.insert(temple().exit(Direction::South, Exit::to(Vnum(3002)).door(gate())));
+    world.insert(
+        Room::new(Vnum(3002), "Outside the Temple")
+            .exit(Direction::North, Exit::to(Vnum(3001)).door(gate()))
+            .exit(Direction::Down, Exit::to(Vnum(3003)).door(Door::new(&["trapdoor"]).hidden().pickproof())),
+    );
+    let state = |room, direction: Direction| {
+        world.with(Vnum(room), |r| r.exits[&direction].door.as_ref().unwrap().state).unwrap()
+    };
+
+    assert_eq!(world.find_door(Vnum(3001), "s"), Some(Direction::South));
+    assert_eq!(world.find_door(Vnum(3001), "IRON"), Some(Direction::South));
+    assert_eq!(world.find_door(Vnum(3001), "north"), None);
+    assert_eq!(world.find_door(Vnum(3002), "trap"), None);
+    assert_eq!(world.find_door(Vnum(3002), "down"), Some(Direction::Down));
+    let exits = world.with(Vnum(3002), |r| r.msdp_exits()).unwrap();
+    assert_eq!(exits.into_iter().collect::<Vec<_>>(), [("n".to_string(), "C")]);
+
+    let south = Direction::South;
+    assert_eq!(world.operate(Vnum(3001), &south, DoorAction::Open, |_| true), Err(DoorError::Locked));
+    assert_eq!(world.operate(Vnum(3001), &south, DoorAction::Unlock, |_| false), Err(DoorError::NoKey));
+    assert_eq!(world.operate(Vnum(3001), &south, DoorAction::Pick, |_| false), Err(DoorError::Failed));
+    let picked = world.operate(Vnum(3001), &south, DoorAction::Pick, |door| door.pick_difficulty == 30).unwrap();
+    assert_eq!((picked.to, picked.back, picked.name.as_str()), (Vnum(3002), Some(Direction::North), "gate"));
+    assert_eq!(state(3002, Direction::North), DoorState::Closed);
+    assert_eq!(world.operate(Vnum(3002), &Direction::North, DoorAction::Pick, |_| true), Err(DoorError::NotLocked));
+    world.operate(Vnum(3002), &Direction::North, DoorAction::Open, |_| true).unwrap();
+    assert_eq!(state(3001, south.clone()), DoorState::Open);
+    assert_eq!(world.operate(Vnum(3001), &south, DoorAction::Lock, |_| true), Err(DoorError::Open));
+    assert_eq!(world.operate(Vnum(3001), &south, DoorAction::Bash, |_| true), Err(DoorError::AlreadyOpen));
+    world.operate(Vnum(3001), &south, DoorAction::Close, |_| true).unwrap();
+    world.operate(Vnum(3001), &south, DoorAction::Lock, |_| true).unwrap();
+    assert_eq!(world.operate(Vnum(3001), &south, DoorAction::Bash, |_| true).map(|o| o.state), Ok(DoorState::Open));
+    assert_eq!(state(3002, Direction::North), DoorState::Open);
+
+    let down = Direction::Down;
+    assert_eq!(world.operate(Vnum(3002), &down, DoorAction::Lock, |_| true), Err(DoorError::NoLock));
+    assert_eq!(world.operate(Vnum(3002), &down, DoorAction::Bash, |_| true), Err(DoorError::TooSturdy));
+    assert_eq!(world.operate(Vnum(3002), &Direction::East, DoorAction::Open, |_| true), Err(DoorError::NoDoor));
+    // The trapdoor's other side doesn't exist, so only this side opens
+    assert_eq!(world.operate(Vnum(3002), &down, DoorAction::Open, |_| true).map(|o| o.back), Ok(None));
+    let exits = world.with(Vnum(3002), |r| r.msdp_exits()).unwrap();
+    assert_eq!(exits.into_iter().collect::<Vec<_>>(), [("d".to_string(), "O"), ("n".to_string(), "O")]);
+    let room = world.get(Vnum(3002)).unwrap().msdp("Midgaard");
+    assert_eq!(room["EXITS"], serde_json::json!({"d": "3003", "n": "3001"}));
+    assert_eq!((room["VNUM"].as_str(), room["TERRAIN"].as_str()), (Some("3002"), Some("inside")));
+
+    world.set_door(Vnum(3002), &Direction::North, DoorState::Locked).unwrap();
+    assert_eq!(state(3001, south), DoorState::Locked);
+    let saved = world.get(Vnum(3002)).unwrap();
+    assert_eq!(serde_json::from_value::<Room>(serde_json::to_value(&saved).unwrap()).unwrap(), saved);
+}
+
+const ROM_AREA: &str = "#AREA
+midgaard.are~
+Midgaard~
+{ 5 35} Diku    Midgaard~
+3000 3002
+
+#MOBILES
+#3000
+wizard~
+the wizard~
+A wizard walks around behind the counter.
+~
+The wizard looks old and senile.
+~
+human~
+ABT 0 900 0
+23 0 10d10+100 100d10+0 2d8+6 magic
+-1 -1 -1 -1
+0 0 0 0
+stand stand male 1000
+0 0 medium unknown
+F par CD
+M GREET 3000 100~
+#0
+
+#OBJECTS
+#3001
+gate key~
+a small key~
+A small key lies here.~
+iron~
+key 0 A
+0 0 0 0 0
+1 1 10 P
+A
+18 2
+E
+key~
+It is plain.
+~
+#0
+
+#ROOMS
+#3001
+The Temple Of Midgaard~
+You are in the southern end of the temple hall.
+~
+0 CDK 1
+D2
+The gate.
+~
+gate~
+1 3001 3002
+E
+altar~
+A huge altar.
+~
+H 120
+S
+#3002
+The Temple Square~
+You are standing on the temple square.
+~
+0 Z 1
+D0
+~
+gate~
+2 -1 3001
+S
+#0
+
+#RESETS
+* The wizard
+M 0 3000 1 3001 1    wizard
+E 1 3001 -1 16
+D 0 3001 2 2
+R 0 3002 4
+O 0 -5 0 3001
+S
+
+#SHOPS
+3000 2 3 4 10 0  105 15  0 23
+0
+
+#SPECIALS
+M 3000 spec_cast_mage
+M 9999 spec_thief
+S
+
+#HELPS
+0 IMOTD~
+Welcome!
+~
+0 $~
+
+#$
+";
+
+const MERC_AREA: &str = "#AREA   {10 20} Hatchet  Sewers~
+
+#MOBILES
+#7000
+rat~
+a rat~
+A rat scurries by.
+~
+It is a rat.
+~
+1|64 0 -100 S
+2 0 8 2d8+10 1d4+0
+10 0
+8 8 1
+#0
+
+#OBJECTS
+#7001
+torch~
+a torch~
+A torch is here.~
+~
+1 0 1|16384
+0 0 24 0
+1 5 0
+#0
+
+#ROOMS
+#7002
+The Sewer~
+It smells.
+~
+0 1 9
+S
+#0
+
+#$
+";
+
+#[test]
+fn import() {
+    let import = are::parse(ROM_AREA).unwrap();
+    let area = import.area.as_ref().unwrap();
+    assert_eq!((area.name.as_str(), area.levels, area.vnums), ("Midgaard", (5, 35), (Vnum(3000), Vnum(3002))));
+    assert!(area.is_builder("diku"));
+    assert_eq!(area.resets, [
+        Reset::Mob { mob: 3000, room: Vnum(3001), limit: 1 },
+        Reset::Equip { object: 3001, slot: "wield".to_string() },
+        Reset::Door { room: Vnum(3001), direction: Direction::South, state: DoorState::Locked },
+    ]);
+
+    let temple = &import.rooms[0];
+    assert_eq!(temple.flags.iter().copied().collect::<Vec<_>>(), [RoomFlag::NoMob, RoomFlag::Indoors, RoomFlag::Safe]);
+    assert_eq!((temple.sector, temple.extra[0].keywords.as_slice()), (Sector::City, ["altar".to_string()].as_slice()));
+    let gate = temple.exits[&Direction::South].door.as_ref().unwrap();
+    assert_eq!((gate.name(), gate.key, gate.pickproof, gate.state), ("gate", Some(3001), false, DoorState::Open));
+    let back = import.rooms[1].exits[&Direction::North].door.as_ref().unwrap();
+    assert_eq!((back.key, back.pickproof), (None, true));
+
+    let wizard = &import.mobiles[0];
+    assert_eq!((wizard.level, wizard.damage.to_string(), wizard.race.as_str()), (23, "2d8+6".to_string(), "human"));
+    assert_eq!((wizard.act, wizard.gold, wizard.sex.as_str()), (1 | 2 | 1 << 19, 1000, "male"));
+    assert_eq!(wizard.shop.as_ref().map(|s| (s.buys.clone(), s.hours)), Some((vec![2, 3, 4, 10], (0, 23))));
+    assert_eq!(wizard.special.as_deref(), Some("spec_cast_mage"));
+    let key = &import.objects[0];
+    assert_eq!((key.item_type.as_str(), key.wear, key.cost, key.material.as_str()), ("key", 1, 10, "iron"));
+    assert_eq!((key.applies[0].location, key.applies[0].modifier, key.values.len()), (18, 2, 5));
+
+    let unsupported: Vec<_> = import.unsupported.iter().map(|u| u.to_string()).collect();
+    assert_eq!(unsupported, [
+        "line 22: mobile #3000 removes par flags",
+        "line 23: mobile #3000 mobprog on greet",
+        "line 58: room #3001 heal or mana rate 120",
+        "line 64: room #3002 flag Z",
+        "line 77: reset shuffles the exits of room #3002",
+        "line 78: reset 'O' with vnum #-5 out of range",
+        "line 90: section #HELPS",
+        "line 87: special for mobile #9999, which isn't in this file",
+    ]);
+    let (world, areas) = (World::new(), Areas::new());
+    import.install(&world, &areas);
+    assert!(world.contains(Vnum(3002)) && areas.get("midgaard").is_some());
+
+    let merc = are::parse(MERC_AREA).unwrap();
+    let area = merc.area.as_ref().unwrap();
+    assert_eq!((area.name.as_str(), area.levels, area.vnums), ("Sewers", (10, 20), (Vnum(7002), Vnum(7002))));
+    let rat = &merc.mobiles[0];
+    assert_eq!((rat.act, rat.alignment, rat.level, rat.armor, rat.sex.as_str()), (65, -100, 2, [8; 4], "male"));
+    let torch = &merc.objects[0];
+    assert_eq!((torch.item_type.as_str(), torch.wear, torch.values.len(), torch.weight), ("1", 16385, 4, 1));
+    assert_eq!((merc.rooms[0].sector, merc.rooms[0].has_flag(RoomFlag::Dark)), (Sector::Air, true));
+    assert!(merc.unsupported.is_empty());
+
+    let error = are::parse("#ROOMS\n#3001\nNo end~\nText~\n0 0 0\nX\n").unwrap_err();
+    assert_eq!(error.to_string(), "line 6: unknown room field 'X'");
+    let error = are::parse("#SHOPS\n\n4611686018427387904|4611686018427387904 0 0 0 0 0\n").unwrap_err();
+    assert_eq!(error.to_string(), "line 3: a shopkeeper's vnum is too large");
+    let resets = are::parse("#RESETS\nO 0 3001 0 3001\nS\n").unwrap();
+    assert!(resets.area.is_none());
+    assert_eq!(resets.unsupported[0].to_string(), "line 1: #RESETS with no #AREA before it");
+    let shop = are::parse("#SHOPS\n3000 0 0 0 0 0 100 100 0 5000000000\n0\n").unwrap();
+    let unsupported: Vec<_> = shop.unsupported.iter().map(|u| u.to_string()).collect();
+    assert_eq!(unsupported, [
+        "line 2: the shop's closing hour 5000000000 is out of range",
+        "line 2: shop kept by mobile #3000, which isn't in this file",
+    ]);
+}
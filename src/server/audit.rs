@@ -0,0 +1,71 @@
+//! This module provides [`AuditLog`], a record of privileged actions such as snooping and possession.
+//! The most recent entries are kept in memory;
+//! register [`Builder::on_audit`](super::Builder::on_audit) to write them somewhere durable
+use super::SessionId;
+use std::collections::VecDeque;
+use std::fmt::{self, Display};
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// One privileged action
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub at: SystemTime,
+    /// The session that did it
+    pub actor: SessionId,
+    /// Where the actor connected from, behind any proxy. `None` if it had already disconnected
+    pub addr: Option<SocketAddr>,
+    /// What was done, e.g. `snoop #4`
+    pub action: String,
+}
+
+impl Display for AuditEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.at.duration_since(SystemTime::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        write!(f, "[{}] {}", secs, self.actor)?;
+        if let Some(addr) = self.addr {
+            write!(f, " ({})", addr)?;
+        }
+        write!(f, " {}", self.action)
+    }
+}
+
+/// The most recent privileged actions, oldest first
+#[derive(Debug)]
+pub struct AuditLog {
+    entries: Mutex<VecDeque<AuditEntry>>,
+    capacity: usize,
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        AuditLog::new(AuditLog::DEFAULT_CAPACITY)
+    }
+}
+
+impl AuditLog {
+    /// How many entries are kept by default
+    pub const DEFAULT_CAPACITY: usize = 1000;
+
+    pub fn new(capacity: usize) -> Self {
+        AuditLog { entries: Mutex::new(VecDeque::new()), capacity }
+    }
+
+    /// Add an entry, forgetting the oldest one if the log is full
+    pub fn record(&self, entry: AuditEntry) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        if self.capacity > 0 {
+            entries.push_back(entry);
+        }
+    }
+
+    /// Up to `count` of the latest entries, oldest first
+    pub fn recent(&self, count: usize) -> Vec<AuditEntry> {
+        let entries = self.entries.lock().unwrap();
+        entries.iter().skip(entries.len().saturating_sub(count)).cloned().collect()
+    }
+}
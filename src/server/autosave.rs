@@ -0,0 +1,197 @@
+//! This module provides [`Autosave`]: characters marked dirty are saved to the server's
+//! [`Store`](crate::store::Store) every [`Config::autosave_interval`](super::Config::autosave_interval),
+//! and the whole world is snapshotted every [`Config::snapshot_interval`](super::Config::snapshot_interval).
+//! Each save is timed and kept in a short report history.
+//! Snapshots go through the store as a single record, so a crash leaves either the previous one or the new one
+use super::{Server, SessionId};
+use crate::entity::EntityId;
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt::{self, Display};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::Notify;
+use tokio::time::{Interval, MissedTickBehavior};
+
+/// The world table key snapshots are saved under, load them with
+/// [`Store::load_world`](crate::store::Store::load_world)
+pub const SNAPSHOT: &str = "snapshot";
+
+/// What a save covered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveKind {
+    /// Characters marked dirty since the last save
+    Autosave,
+    /// Every character in the world, dirty or not
+    All,
+    /// The whole world, from [`Builder::on_snapshot`](super::Builder::on_snapshot)
+    Snapshot,
+}
+
+impl Display for SaveKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveKind::Autosave => f.write_str("autosave"),
+            SaveKind::All => f.write_str("save all"),
+            SaveKind::Snapshot => f.write_str("snapshot"),
+        }
+    }
+}
+
+/// How a save went
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveReport {
+    pub kind: SaveKind,
+    /// When it started
+    pub at: SystemTime,
+    /// Records written
+    pub saved: usize,
+    /// What couldn't be saved, and why
+    pub failed: Vec<String>,
+    pub took: Duration,
+}
+
+impl Display for SaveReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} saved in {:.1}ms", self.kind, self.saved, self.took.as_secs_f64() * 1000.0)?;
+        if !self.failed.is_empty() {
+            write!(f, ", {} failed ({})", self.failed.len(), self.failed.join("; "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Dirty characters, saves asked for by commands, and recent reports
+#[derive(Debug, Default)]
+pub struct Autosave {
+    dirty: Mutex<BTreeSet<EntityId>>,
+    /// Saves asked for, and who to tell when they're done
+    requests: Mutex<Vec<(SaveKind, Option<SessionId>)>>,
+    requested: Notify,
+    reports: Mutex<VecDeque<SaveReport>>,
+}
+
+impl Autosave {
+    /// How many reports are kept
+    pub const HISTORY: usize = 20;
+
+    pub fn new() -> Self {
+        Autosave::default()
+    }
+
+    /// Save `entity` with the next autosave. Call this whenever a character changes
+    pub fn mark_dirty(&self, entity: EntityId) {
+        self.dirty.lock().unwrap().insert(entity);
+    }
+
+    pub fn is_dirty(&self, entity: EntityId) -> bool {
+        self.dirty.lock().unwrap().contains(&entity)
+    }
+
+    /// Ask for a save as soon as possible. The report is sent to session `by`, if given
+    pub fn request(&self, kind: SaveKind, by: Option<SessionId>) {
+        self.requests.lock().unwrap().push((kind, by));
+        self.requested.notify_one();
+    }
+
+    /// Recent saves, oldest first
+    pub fn reports(&self) -> Vec<SaveReport> {
+        self.reports.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// The latest save of this kind
+    pub fn last(&self, kind: SaveKind) -> Option<SaveReport> {
+        self.reports.lock().unwrap().iter().rev().find(|r| r.kind == kind).cloned()
+    }
+
+    fn record(&self, report: SaveReport) {
+        let mut reports = self.reports.lock().unwrap();
+        if reports.len() >= Autosave::HISTORY {
+            reports.pop_front();
+        }
+        reports.push_back(report);
+    }
+}
+
+fn every(period: Option<Duration>) -> Option<Interval> {
+    let period = period?;
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    // A save that overruns delays the next one instead of causing a burst
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    Some(interval)
+}
+
+async fn tick(interval: &mut Option<Interval>) {
+    match interval {
+        Some(interval) => drop(interval.tick().await),
+        None => std::future::pending().await,
+    }
+}
+
+/// Run scheduled and requested saves until the task is dropped at shutdown
+pub(crate) async fn schedule(server: Arc<Server>) {
+    let mut autosave = every(server.config.autosave_interval);
+    let mut snapshot = every(server.config.snapshot_interval);
+    loop {
+        tokio::select! {
+            _ = tick(&mut autosave) => drop(server.save(SaveKind::Autosave).await),
+            _ = tick(&mut snapshot) => drop(server.save(SaveKind::Snapshot).await),
+            _ = server.autosave.requested.notified() => {
+                let requests = std::mem::take(&mut *server.autosave.requests.lock().unwrap());
+                for (kind, by) in requests {
+                    let report = server.save(kind).await;
+                    if let Some(id) = by {
+                        server.registry.send(id, &format!("{}\n", report));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Server {
+    /// Save now and record a [`SaveReport`]. Does nothing without a store and the matching hook
+    pub async fn save(&self, kind: SaveKind) -> SaveReport {
+        let (at, started) = (SystemTime::now(), Instant::now());
+        let (saved, failed) = match kind {
+            SaveKind::Snapshot => self.save_snapshot().await,
+            _ => self.save_characters(kind == SaveKind::All).await,
+        };
+        let report = SaveReport { kind, at, saved, failed, took: started.elapsed() };
+        self.autosave.record(report.clone());
+        report
+    }
+
+    /// Save the dirty characters, or every character in the world. Failed ones stay dirty for the next try
+    async fn save_characters(&self, all: bool) -> (usize, Vec<String>) {
+        let (Some(store), Some(hook)) = (&self.store, &self.on_save_character) else {
+            return (0, Vec::new());
+        };
+        let mut entities = std::mem::take(&mut *self.autosave.dirty.lock().unwrap());
+        if all {
+            entities.extend(self.links.entities());
+        }
+        // Take every state before the first await, so the characters are saved as of one moment
+        let states: Vec<_> = entities.into_iter().filter_map(|e| Some((e, hook(self, e)?))).collect();
+        let (mut saved, mut failed) = (0, Vec::new());
+        for (entity, (name, state)) in states {
+            match store.save_character(&name, &state).await {
+                Ok(()) => saved += 1,
+                Err(e) => {
+                    self.autosave.mark_dirty(entity);
+                    failed.push(format!("{}: {}", name, e));
+                }
+            }
+        }
+        (saved, failed)
+    }
+
+    async fn save_snapshot(&self) -> (usize, Vec<String>) {
+        let (Some(store), Some(hook)) = (&self.store, &self.on_snapshot) else {
+            return (0, Vec::new());
+        };
+        match store.save_world(SNAPSHOT, &hook(self)).await {
+            Ok(()) => (1, Vec::new()),
+            Err(e) => (0, vec![e.to_string()]),
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use super::queue::Queue;
+use super::snoop;
+use super::transport::Transport;
+use super::{Handle, Message, Server, SessionId};
+use crate::account::{Nanny, Step};
+use crate::commands::Context;
+use crate::session::{Input, Session};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// Drive a new connection until the client leaves or the server closes it
+pub(crate) async fn run<T: Transport>(server: Arc<Server>, transport: T, addr: SocketAddr) -> std::io::Result<()> {
+    let id = server.next_id();
+    drive(server, transport, addr, id, None).await
+}
+
+/// Drive a connection inherited from a previous process, with its session already negotiated
+#[cfg(unix)]
+pub(crate) async fn resume<T: Transport>(
+    server: Arc<Server>,
+    transport: T,
+    addr: SocketAddr,
+    id: SessionId,
+    session: Session,
+) -> std::io::Result<()> {
+    drive(server, transport, addr, id, Some(session)).await
+}
+
+async fn drive<T: Transport>(
+    server: Arc<Server>,
+    mut transport: T,
+    addr: SocketAddr,
+    id: SessionId,
+    restored: Option<Session>,
+) -> std::io::Result<()> {
+    let (sender, mut mailbox) = mpsc::unbounded_channel();
+    let queue = Arc::new(Queue::new(server.config.output));
+    server.registry.insert(id, Handle::new(addr, transport.secure(), sender, queue.clone()));
+    let is_restored = restored.is_some();
+    let mut session = match restored {
+        Some(session) => {
+            for entity in session.entity().into_iter().chain(session.original()) {
+                server.links.bind(entity, id);
+            }
+            session
+        }
+        None => {
+            let mut session = Session::with_charset(transport.charset().unwrap_or(server.config.charset));
+            session.set_line_limit(server.config.line_limit);
+            session.set_secure(transport.secure());
+            session.negotiate();
+            session.receive(&transport.preamble());
+            session
+        }
+    };
+    session.set_peer(addr);
+    let mailbox = Mailbox { receiver: &mut mailbox, queue: &queue };
+    let result = serve(&server, id, addr, &mut transport, &mut session, is_restored, mailbox).await;
+    server.registry.remove(id);
+    server.logins.lock().unwrap().remove(&id);
+    server.snoops_lost(id);
+    // A possessing administrator's own character is the one left link-dead
+    server.unpossess(id, &mut session);
+    server.link_lost(id, &session);
+    if let Some(hook) = &server.on_disconnect {
+        hook(&server, id);
+    }
+    result
+}
+
+async fn serve<T: Transport>(
+    server: &Server,
+    id: SessionId,
+    #[cfg_attr(not(unix), allow(unused_variables))] addr: SocketAddr,
+    transport: &mut T,
+    session: &mut Session,
+    restored: bool,
+    mut mailbox: Mailbox<'_>,
+) -> std::io::Result<()> {
+    let config = &server.config;
+    if restored {
+        session.write(&config.copyover_done);
+        if let Some(hook) = &server.on_restore {
+            hook(&mut Context { server, id, session });
+        }
+    } else {
+        session.write(&config.greeting);
+        if let Some(hook) = &server.on_connect {
+            hook(&mut Context { server, id, session });
+        }
+    }
+    session.set_limits(config.limits);
+    // A session restored in the middle of logging in starts over
+    if server.accounts.is_some() && (session.account().is_none() || session.entity().is_none()) {
+        if config.require_secure_login && !session.is_secure() {
+            session.write(&config.insecure_login);
+            transport.write(&session.take_output()).await?;
+            return transport.close().await;
+        }
+        server.start_login(id, session, Nanny::new());
+    } else {
+        session.prompt(&config.prompt);
+    }
+
+    let limits = *mailbox.queue.limits();
+    let mut next_flush = Instant::now();
+    // The permissions generation and account the hidden MSDP variables were worked out for
+    let mut permissions: Option<(u64, Option<String>)> = None;
+    loop {
+        refresh_msdp(server, session, &mut permissions);
+        if let Some(text) = session.take_snooped() {
+            if let Some(snooper) = server.snoops.snooper(id) {
+                server.registry.send(snooper, &snoop::mirror(&text));
+            }
+        }
+        if mailbox.queue.is_overflowed() {
+            server.registry.record_slow_disconnect();
+            return Ok(());
+        }
+        // Coalesce everything already waiting into one write, but don't let it grow past the low watermark
+        let waiting = !mailbox.receiver.is_empty() && session.pending_text() < limits.low;
+        if session.has_output() && !waiting && Instant::now() >= next_flush {
+            if mailbox.queue.is_dropping() {
+                mailbox.queue.record_dropped(session.discard_text());
+            } else if mailbox.queue.take_unreported() > 0 {
+                session.write("[Some output was dropped because your connection couldn't keep up.]\n");
+            }
+            let output = session.take_output();
+            mailbox.queue.set_in_flight(output.len());
+            let Ok(written) = tokio::time::timeout(limits.write_timeout, transport.write(&output)).await else {
+                server.registry.record_slow_disconnect();
+                return Ok(());
+            };
+            written?;
+            mailbox.queue.set_in_flight(0);
+            next_flush = Instant::now() + limits.flush_interval;
+        }
+        let pending = session.has_output();
+        tokio::select! {
+            _ = tokio::time::sleep_until(next_flush), if pending => {}
+            read = transport.read() => {
+                let Some(data) = read? else {
+                    return Ok(());
+                };
+                // Permissions may have changed while waiting
+                refresh_msdp(server, session, &mut permissions);
+                let mut prompt = false;
+                for input in session.receive(&data) {
+                    let line = match input {
+                        Input::Line(line) => line,
+                        other => {
+                            if let Input::Msdp(data) = &other {
+                                server.answer_msdp(session, data);
+                            }
+                            if let Some(hook) = &server.on_input {
+                                hook(&mut Context { server, id, session: &mut *session }, other);
+                            }
+                            continue;
+                        }
+                    };
+                    let nanny = server.logins.lock().unwrap().remove(&id);
+                    let Some(mut nanny) = nanny else {
+                        server.commands.dispatch(&mut Context { server, id, session: &mut *session }, &line);
+                        // The command may have started a password change, which writes its own prompts
+                        prompt = !server.is_logging_in(id);
+                        continue;
+                    };
+                    match nanny.input(server, session, &line).await {
+                        Step::Continue => {
+                            server.logins.lock().unwrap().insert(id, nanny);
+                            prompt = false;
+                        }
+                        Step::Play(login) => {
+                            let mut ctx = Context { server, id, session: &mut *session };
+                            ctx.session.set_account(Some(login.account.clone()));
+                            let bound = ctx.bind(login.character.entity);
+                            if let Some(hook) = &server.on_login {
+                                hook(&mut ctx, &login, bound);
+                            }
+                            prompt = true;
+                        }
+                        Step::Resume => prompt = true,
+                        Step::Quit => {
+                            transport.write(&session.take_output()).await?;
+                            return transport.close().await;
+                        }
+                    }
+                }
+                if session.is_flooding() {
+                    session.write("You have been disconnected for flooding.\n");
+                    transport.write(&session.take_output()).await?;
+                    return transport.close().await;
+                }
+                if prompt {
+                    session.prompt(&config.prompt);
+                }
+            }
+            message = mailbox.recv() => match message {
+                Some(Message::Text(text)) => session.write(&text),
+                Some(Message::Close(text)) => {
+                    session.write(&text);
+                    transport.write(&session.take_output()).await?;
+                    return transport.close().await;
+                }
+                #[cfg(unix)]
+                Some(Message::Copyover(reply)) => {
+                    transport.write(&session.take_output()).await?;
+                    let Some(fd) = transport.duplicate_fd() else {
+                        session.write(&config.copyover_lost);
+                        transport.write(&session.take_output()).await?;
+                        return transport.close().await;
+                    };
+                    if reply.send(super::copyover::Detached { id, addr, fd, session: session.state() }).is_err() {
+                        // Too late, the copyover went ahead without this connection
+                        session.write(&config.copyover_lost);
+                        transport.write(&session.take_output()).await?;
+                        return transport.close().await;
+                    }
+                    // Stop reading until the copyover fails; if it succeeds this process is gone
+                    loop {
+                        match mailbox.recv().await {
+                            Some(Message::Resume) => break,
+                            Some(Message::Text(text)) => session.write(&text),
+                            Some(Message::Close(text)) => {
+                                session.write(&text);
+                                transport.write(&session.take_output()).await?;
+                                return transport.close().await;
+                            }
+                            Some(Message::Snoop(snooped)) => session.set_snooped(snooped),
+                            Some(Message::Msdp(name, value)) => {
+                                refresh_msdp(server, session, &mut permissions);
+                                drop(session.send_msdp(&name, &value))
+                            }
+                            Some(Message::Copyover(_)) => {}
+                            None => return Ok(()),
+                        }
+                    }
+                }
+                Some(Message::Snoop(snooped)) => session.set_snooped(snooped),
+                Some(Message::Msdp(name, value)) => {
+                    refresh_msdp(server, session, &mut permissions);
+                    drop(session.send_msdp(&name, &value))
+                }
+                #[cfg(unix)]
+                Some(Message::Resume) => {}
+                None => return Ok(()),
+            }
+        }
+    }
+}
+
+/// Work out the session's hidden MSDP variables again if its account or the permissions changed since `seen`,
+/// the generation and account they were last worked out for
+fn refresh_msdp(server: &Server, session: &mut Session, seen: &mut Option<(u64, Option<String>)>) {
+    let generation = server.permissions.generation();
+    if seen.as_ref().is_none_or(|(g, a)| *g != generation || a.as_deref() != session.account()) {
+        session.hide_msdp(server.msdp_hidden(session));
+        *seen = Some((generation, session.account().map(str::to_string)));
+    }
+}
+
+/// A connection's incoming messages, keeping its [`Queue`] depth up to date as text is taken
+struct Mailbox<'a> {
+    receiver: &'a mut mpsc::UnboundedReceiver<Message>,
+    queue: &'a Queue,
+}
+
+impl Mailbox<'_> {
+    async fn recv(&mut self) -> Option<Message> {
+        let message = self.receiver.recv().await;
+        if let Some(len) = message.as_ref().and_then(Message::queued_len) {
+            self.queue.pop(len);
+        }
+        message
+    }
+}
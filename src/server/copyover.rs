@@ -0,0 +1,256 @@
+//! Copyover (hot reboot): the server execs a new binary in place, handing it every plain telnet connection,
+//! the listening sockets and each session's negotiated state, so players stay connected across the reboot.
+//!
+//! Connections that keep state outside the socket (TLS, WebSocket, SSH) can't be handed over; they are told to
+//! reconnect and closed before the exec. The new process restores sessions in [`Server::run`] and calls the
+//! [`on_restore`](super::Builder::on_restore) hook so the game can reattach characters by [`Session::entity`](crate::session::Session::entity)
+use super::autosave::SaveKind;
+use super::connection;
+use super::transport::Stream;
+use super::{Listener, Message, Server, SessionId};
+use crate::session::{Session, SessionState};
+use serde::{Deserialize, Serialize};
+use std::ffi::{CString, OsString};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::net::SocketAddr;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+
+/// Environment variable holding the descriptor the new process reads the saved state from
+pub const ENV: &str = "LUMINA_COPYOVER";
+
+/// The protocol a listening socket speaks, so the new process can serve it the same way
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum ListenerKind {
+    Telnet,
+    WebSocket,
+    Tls,
+    Ssh,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedSession {
+    id: SessionId,
+    addr: SocketAddr,
+    fd: RawFd,
+    session: SessionState,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct State {
+    next_id: u64,
+    listeners: Vec<(ListenerKind, RawFd)>,
+    sessions: Vec<SavedSession>,
+}
+
+/// A connection handed over by its task
+#[derive(Debug)]
+pub struct Detached {
+    pub(crate) id: SessionId,
+    pub(crate) addr: SocketAddr,
+    pub(crate) fd: OwnedFd,
+    pub(crate) session: SessionState,
+}
+
+fn set_cloexec(fd: RawFd, cloexec: bool) -> io::Result<()> {
+    // SAFETY: fcntl on a descriptor we own has no memory safety requirements
+    let ok = unsafe { libc::fcntl(fd, libc::F_SETFD, if cloexec { libc::FD_CLOEXEC } else { 0 }) } != -1;
+    if ok {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Take ownership of a descriptor named in the state file, if it is actually open
+fn adopt(fd: RawFd) -> Option<OwnedFd> {
+    // SAFETY: F_GETFD only checks that the descriptor is open
+    if fd < 0 || unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        return None;
+    }
+    // SAFETY: the descriptor is open and was inherited from the previous process for us alone
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    set_cloexec(fd.as_raw_fd(), true).ok()?;
+    Some(fd)
+}
+
+/// Save every character and session and exec the current binary again. Only returns if saving or the exec
+/// failed, in which case every session carries on as before
+pub(crate) async fn perform(server: &Arc<Server>) -> io::Result<()> {
+    let exe = std::env::current_exe()?;
+    server.broadcast(&server.config.copyover_message);
+    if let Some(hook) = &server.on_shutdown {
+        hook(server);
+    }
+    // The new process reattaches players from their saved records, which have to be current
+    for kind in [SaveKind::All, SaveKind::Snapshot] {
+        let report = server.save(kind).await;
+        if !report.failed.is_empty() {
+            return Err(io::Error::other(format!("couldn't save {}", report.failed.join(", "))));
+        }
+    }
+
+    let (mut asked, mut pending) = (Vec::new(), Vec::new());
+    for id in server.registry.ids() {
+        let (reply, receive) = oneshot::channel();
+        if server.registry.get(id).is_some_and(|h| h.send(Message::Copyover(reply))) {
+            asked.push(id);
+            pending.push(receive);
+        }
+    }
+    let mut detached = Vec::new();
+    for receive in pending {
+        // A task that doesn't answer in time finds its reply refused, tells its player and closes.
+        // One that can't hand over its socket does the same
+        if let Ok(Ok(d)) = tokio::time::timeout(Duration::from_secs(5), receive).await {
+            detached.push(d);
+        }
+    }
+
+    let result = exec(server, &exe, &detached);
+    // Every task asked may be waiting, even one whose reply came too late to be counted
+    for id in asked {
+        if let Some(handle) = server.registry.get(id) {
+            handle.send(Message::Resume);
+        }
+    }
+    result
+}
+
+fn exec(server: &Server, exe: &std::path::Path, detached: &[Detached]) -> io::Result<()> {
+    let listeners = server
+        .listener_fds
+        .lock()
+        .unwrap()
+        .iter()
+        // SAFETY: the listeners are owned by acceptor tasks that outlive this call
+        .map(|&(kind, fd)| Ok((kind, unsafe { std::os::fd::BorrowedFd::borrow_raw(fd) }.try_clone_to_owned()?)))
+        .collect::<io::Result<Vec<_>>>()?;
+    let state = State {
+        next_id: server.next_id.load(Ordering::Relaxed),
+        listeners: listeners.iter().map(|(kind, fd)| (*kind, fd.as_raw_fd())).collect(),
+        sessions: detached
+            .iter()
+            .map(|d| SavedSession { id: d.id, addr: d.addr, fd: d.fd.as_raw_fd(), session: d.session.clone() })
+            .collect(),
+    };
+    for fd in state.listeners.iter().map(|l| l.1).chain(state.sessions.iter().map(|s| s.fd)) {
+        set_cloexec(fd, false)?;
+    }
+    let file = state_file(&state)?;
+    set_cloexec(file.as_raw_fd(), false)?;
+    Err(Command::new(exe).args(std::env::args_os().skip(1)).env(ENV, file.as_raw_fd().to_string()).exec())
+}
+
+/// Write the state to a file in a fresh private directory, removing both before returning the open file,
+/// so no other process can read or replace it
+fn state_file(state: &State) -> io::Result<File> {
+    let template = std::env::temp_dir().join("lumina-copyover-XXXXXX");
+    let mut template = CString::new(template.as_os_str().as_bytes())?.into_bytes_with_nul();
+    // SAFETY: the template is a writable buffer ending in NUL, as mkdtemp requires
+    if unsafe { libc::mkdtemp(template.as_mut_ptr().cast()) }.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    template.pop();
+    let dir = PathBuf::from(OsString::from_vec(template));
+    let path = dir.join("state.json");
+    let file = OpenOptions::new().read(true).write(true).create_new(true).mode(0o600).open(&path);
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_dir(&dir);
+    let mut file = file?;
+    file.write_all(&serde_json::to_vec(state)?)?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(file)
+}
+
+/// Read the state left by the previous process, if this process was started by a copyover.
+/// Only the first call reads it. The variable is left set, since changing the environment
+/// isn't safe once other threads are running
+pub(crate) fn take_state() -> io::Result<Option<State>> {
+    static TAKEN: AtomicBool = AtomicBool::new(false);
+    let Some(fd) = std::env::var_os(ENV) else {
+        return Ok(None);
+    };
+    if TAKEN.swap(true, Ordering::Relaxed) {
+        return Ok(None);
+    }
+    let fd = fd.to_str().and_then(|fd| fd.parse().ok()).and_then(adopt);
+    let fd = fd.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "the copyover state isn't open"))?;
+    let mut data = Vec::new();
+    File::from(fd).read_to_end(&mut data)?;
+    Ok(Some(serde_json::from_slice(&data)?))
+}
+
+fn listener(server: &Server, kind: ListenerKind, fd: OwnedFd) -> io::Result<Option<Listener>> {
+    let socket = std::net::TcpListener::from(fd);
+    socket.local_addr()?;
+    socket.set_nonblocking(true)?;
+    let socket = TcpListener::from_std(socket)?;
+    Ok(match kind {
+        ListenerKind::Telnet => Some(Listener::Telnet(socket)),
+        #[cfg(feature = "websocket")]
+        ListenerKind::WebSocket => Some(Listener::WebSocket(socket)),
+        #[cfg(feature = "tls")]
+        ListenerKind::Tls => match &server.config.tls {
+            Some(tls) => Some(Listener::Tls(socket, Arc::new(super::tls::Certificates::load(&tls.cert, &tls.key)?))),
+            None => None,
+        },
+        #[cfg(feature = "ssh")]
+        ListenerKind::Ssh => match &server.config.ssh {
+            Some(ssh) => Some(Listener::Ssh(socket, super::ssh::server_config(ssh)?)),
+            None => None,
+        },
+        #[allow(unreachable_patterns)]
+        _ => {
+            let _ = server;
+            None
+        }
+    })
+}
+
+fn stream(fd: OwnedFd) -> io::Result<TcpStream> {
+    let stream = std::net::TcpStream::from(fd);
+    stream.peer_addr()?;
+    stream.set_nonblocking(true)?;
+    TcpStream::from_std(stream)
+}
+
+/// Rebuild listeners and sessions from the previous process's state. Anything that can't be
+/// restored is closed, and the game is told about lost sessions through `on_disconnect`
+pub(crate) fn restore(server: &Arc<Server>, state: State) -> Vec<Listener> {
+    server.next_id.fetch_max(state.next_id, Ordering::Relaxed);
+    let listeners = state
+        .listeners
+        .into_iter()
+        .filter_map(|(kind, fd)| listener(server, kind, adopt(fd)?).ok().flatten())
+        .collect();
+    for saved in state.sessions {
+        match adopt(saved.fd).map(stream) {
+            Some(Ok(stream)) => {
+                let session = Session::from_state(saved.session);
+                tokio::spawn(connection::resume(server.clone(), Stream::new(stream), saved.addr, saved.id, session));
+            }
+            _ => {
+                if let Some(hook) = &server.on_disconnect {
+                    hook(server, saved.id);
+                }
+            }
+        }
+    }
+    listeners
+}
+
+#[cfg(test)]
+pub(crate) fn state_for_test(next_id: u64, id: SessionId, addr: SocketAddr, fd: RawFd, session: SessionState) -> State {
+    State { next_id, listeners: Vec::new(), sessions: vec![SavedSession { id, addr, fd, session }] }
+}
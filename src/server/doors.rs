@@ -0,0 +1,131 @@
+//! This module provides the door commands, `open`, `close`, `lock`, `unlock`, `pick` and `bash`, added by
+//! [`Builder::world`](super::Builder::world). Both sides of the door hear about a change, and clients in either room
+//! get fresh `ROOM` and `ROOM_EXITS` MSDP variables for their mappers
+use super::{Server, SessionId};
+use crate::commands::{Commands, Context};
+use crate::entity::EntityId;
+use crate::rooms::{DoorAction, Vnum};
+use crate::session::Session;
+
+const ACTIONS: [(&str, DoorAction); 6] = [
+    ("open", DoorAction::Open),
+    ("close", DoorAction::Close),
+    ("lock", DoorAction::Lock),
+    ("unlock", DoorAction::Unlock),
+    ("pick", DoorAction::Pick),
+    ("bash", DoorAction::Bash),
+];
+
+/// Add the door commands that aren't registered yet
+pub(crate) fn register(commands: &mut Commands) {
+    for (name, action) in ACTIONS {
+        if !commands.contains(name) {
+            commands.register(name, move |ctx, args| operate(ctx, args, action));
+        }
+    }
+}
+
+fn capitalized(text: impl ToString) -> String {
+    let text = text.to_string();
+    let mut chars = text.chars();
+    chars.next().map(|c| c.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+}
+
+fn operate(ctx: &mut Context, args: &str, action: DoorAction) {
+    let server = ctx.server;
+    let Some((entity, room)) = ctx.session.entity().and_then(|e| Some((e, server.world.location(e)?))) else {
+        ctx.session.write("You aren't anywhere.\n");
+        return;
+    };
+    if args.is_empty() {
+        ctx.session.write(&format!("{} what?\n", capitalized(action)));
+        return;
+    }
+    let Some(direction) = server.world.find_door(room, args) else {
+        ctx.session.write(&format!("You see no {} here.\n", args));
+        return;
+    };
+    // Ask the hooks before the world is locked, they may look at it
+    let door = server.world.with(room, |r| r.exits.get(&direction).and_then(|e| e.door.clone())).flatten();
+    let allowed = door.is_some_and(|door| match action {
+        DoorAction::Lock | DoorAction::Unlock => door.key.is_some_and(|key| server.has_key(entity, key)),
+        DoorAction::Pick => roll(server.door_skill(entity, action).saturating_sub(door.pick_difficulty)),
+        DoorAction::Bash => roll(server.door_skill(entity, action)),
+        DoorAction::Open | DoorAction::Close => true,
+    });
+    let operated = match server.world.operate(room, &direction, action, |_| allowed) {
+        Ok(operated) => operated,
+        Err(e) => {
+            ctx.session.write(&format!("{}.\n", capitalized(e)));
+            return;
+        }
+    };
+    let name = &operated.name;
+    let click = format!("You hear a click from the {}.\n", name);
+    let (you, others) = match action {
+        DoorAction::Open => (format!("You open the {}.\n", name), format!("The {} opens.\n", name)),
+        DoorAction::Close => (format!("You close the {}.\n", name), format!("The {} closes.\n", name)),
+        DoorAction::Lock => (format!("You lock the {}.\n", name), click),
+        DoorAction::Unlock => (format!("You unlock the {}.\n", name), click),
+        DoorAction::Pick => (format!("You pick the lock on the {}.\n", name), click),
+        DoorAction::Bash => (format!("You bash the {} open!\n", name), format!("The {} crashes open!\n", name)),
+    };
+    ctx.session.write(&you);
+    server.send_room(ctx.session, room);
+    for vnum in [room, operated.to] {
+        for id in server.sessions_in(vnum).into_iter().filter(|&id| id != ctx.id) {
+            server.registry.send(id, &others);
+        }
+        server.update_room(vnum, Some(ctx.id));
+    }
+}
+
+/// Whether a roll of 1 to 100 comes in at or under `chance`
+fn roll(chance: u8) -> bool {
+    rand::random_range(1..=100) <= chance
+}
+
+impl Server {
+    /// Whether the character carries the key with object vnum `key`, see [`Builder::has_key`](super::Builder::has_key)
+    pub fn has_key(&self, entity: EntityId, key: u32) -> bool {
+        self.has_key.as_ref().is_some_and(|hook| hook(self, entity, key))
+    }
+
+    /// The character's chance in percent to pick or bash a door, see
+    /// [`Builder::door_skill`](super::Builder::door_skill)
+    pub fn door_skill(&self, entity: EntityId, action: DoorAction) -> u8 {
+        self.door_skill.as_ref().map_or(0, |hook| hook(self, entity, action).min(100))
+    }
+
+    /// The sessions playing a character in room `vnum`
+    pub fn sessions_in(&self, vnum: Vnum) -> Vec<SessionId> {
+        let contents = self.world.with(vnum, |r| r.contents.iter().copied().collect::<Vec<_>>()).unwrap_or_default();
+        contents.into_iter().filter_map(|e| self.links.session(e)).collect()
+    }
+
+    /// The `ROOM` and `ROOM_EXITS` MSDP variables for room `vnum`
+    fn room_msdp(&self, vnum: Vnum) -> Option<[(&'static str, serde_json::Value); 2]> {
+        let area = self.areas.area_of(vnum).map(|a| a.name).unwrap_or_default();
+        self.world.with(vnum, |room| {
+            let exits = serde_json::to_value(room.msdp_exits()).unwrap_or_default();
+            [("ROOM", room.msdp(&area)), ("ROOM_EXITS", exits)]
+        })
+    }
+
+    /// Send the session the `ROOM` and `ROOM_EXITS` MSDP variables for room `vnum`. Call this as a character moves
+    pub fn send_room(&self, session: &mut Session, vnum: Vnum) {
+        for (name, value) in self.room_msdp(vnum).into_iter().flatten() {
+            let _ = session.send_msdp(name, &value);
+        }
+    }
+
+    /// Send everyone in room `vnum` but `except` fresh `ROOM` and `ROOM_EXITS` MSDP variables, e.g. when a door changes
+    pub fn update_room(&self, vnum: Vnum, except: Option<SessionId>) {
+        let Some(variables) = self.room_msdp(vnum) else { return };
+        for id in self.sessions_in(vnum).into_iter().filter(|&id| Some(id) != except) {
+            for (name, value) in &variables {
+                self.registry.send_msdp(id, name, value.clone());
+            }
+        }
+    }
+}
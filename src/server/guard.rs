@@ -0,0 +1,187 @@
+//! This module provides [`Guard`], which decides who may connect: CIDR ban and allow lists, editable at runtime,
+//! and a per-address connection rate limit
+use crate::session::{Bucket, Rate};
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// An address range such as `192.0.2.0/24` or `2001:db8::/32`. A bare address is a range of one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+/// IPv4 clients on a dual stack socket show up as `::ffff:a.b.c.d`
+fn canonical(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(addr, IpAddr::V4),
+        v4 => v4,
+    }
+}
+
+fn bits(addr: IpAddr) -> (u128, u8) {
+    match addr {
+        IpAddr::V4(v4) => (u128::from(u32::from(v4)), 32),
+        IpAddr::V6(v6) => (u128::from(v6), 128),
+    }
+}
+
+impl Cidr {
+    /// `None` if `prefix` is longer than the address. Host bits are cleared, so `192.0.2.7/24` is `192.0.2.0/24`
+    pub fn new(addr: IpAddr, prefix: u8) -> Option<Self> {
+        let addr = canonical(addr);
+        let (net, width) = bits(addr);
+        let shift = u32::from(width.checked_sub(prefix)?);
+        let net = if shift >= 128 { 0 } else { net >> shift << shift };
+        let addr = match addr {
+            IpAddr::V4(_) => IpAddr::from((net as u32).to_be_bytes()),
+            IpAddr::V6(_) => IpAddr::from(net.to_be_bytes()),
+        };
+        Some(Cidr { addr, prefix })
+    }
+
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        let ((net, width), (addr, addr_width)) = (bits(self.addr), bits(canonical(addr)));
+        if width != addr_width {
+            return false;
+        }
+        let shift = u32::from(width - self.prefix);
+        shift >= 128 || (net ^ addr) >> shift == 0
+    }
+}
+
+impl From<IpAddr> for Cidr {
+    fn from(addr: IpAddr) -> Self {
+        let addr = canonical(addr);
+        Cidr { addr, prefix: bits(addr).1 }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid address range '{}'", s);
+        match s.trim().split_once('/') {
+            Some((addr, prefix)) => {
+                let addr = addr.parse().map_err(|_| invalid())?;
+                Cidr::new(addr, prefix.parse().map_err(|_| invalid())?).ok_or_else(invalid)
+            }
+            None => s.trim().parse::<IpAddr>().map(Cidr::from).map_err(|_| invalid()),
+        }
+    }
+}
+
+impl Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// Why a connection was turned away
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refusal {
+    Banned,
+    /// The address connected too often recently
+    Throttled,
+}
+
+#[derive(Debug, Default)]
+struct Lists {
+    bans: Vec<Cidr>,
+    allows: Vec<Cidr>,
+    recent: HashMap<IpAddr, Bucket>,
+}
+
+/// Ban and allow lists plus per-address connection throttling.
+/// Addresses on the allow list are never banned or throttled, e.g. for a web client gateway
+#[derive(Debug, Default)]
+pub struct Guard {
+    connections: Option<Rate>,
+    lists: Mutex<Lists>,
+}
+
+/// How many addresses are tracked before idle ones are forgotten
+const TRACKED: usize = 1024;
+
+impl Guard {
+    /// Allow `connections` per address, or any number if `None`
+    pub fn new(connections: Option<Rate>) -> Self {
+        Guard { connections, lists: Mutex::default() }
+    }
+
+    /// Decide whether `addr` may connect, counting the attempt against its rate
+    pub fn admit(&self, addr: IpAddr) -> Result<(), Refusal> {
+        let addr = canonical(addr);
+        let mut lists = self.lists.lock().unwrap();
+        if lists.allows.iter().any(|c| c.contains(addr)) {
+            return Ok(());
+        }
+        if lists.bans.iter().any(|c| c.contains(addr)) {
+            return Err(Refusal::Banned);
+        }
+        let Some(rate) = self.connections else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        if lists.recent.len() >= TRACKED {
+            lists.recent.retain(|_, bucket| !bucket.is_full_at(now));
+        }
+        match lists.recent.entry(addr).or_insert_with(|| Bucket::new(rate)).take_at(now) {
+            true => Ok(()),
+            false => Err(Refusal::Throttled),
+        }
+    }
+
+    /// Whether `addr` is banned and not allowed
+    pub fn is_banned(&self, addr: IpAddr) -> bool {
+        let lists = self.lists.lock().unwrap();
+        !lists.allows.iter().any(|c| c.contains(addr)) && lists.bans.iter().any(|c| c.contains(addr))
+    }
+
+    /// Returns `false` if the range was already banned
+    pub fn ban(&self, range: Cidr) -> bool {
+        add(&mut self.lists.lock().unwrap().bans, range)
+    }
+
+    /// Returns `false` if the range wasn't banned
+    pub fn unban(&self, range: Cidr) -> bool {
+        remove(&mut self.lists.lock().unwrap().bans, range)
+    }
+
+    pub fn bans(&self) -> Vec<Cidr> {
+        self.lists.lock().unwrap().bans.clone()
+    }
+
+    /// Exempt a range from bans and throttling. Returns `false` if it already was
+    pub fn allow(&self, range: Cidr) -> bool {
+        add(&mut self.lists.lock().unwrap().allows, range)
+    }
+
+    /// Returns `false` if the range wasn't allowed
+    pub fn disallow(&self, range: Cidr) -> bool {
+        remove(&mut self.lists.lock().unwrap().allows, range)
+    }
+
+    pub fn allows(&self) -> Vec<Cidr> {
+        self.lists.lock().unwrap().allows.clone()
+    }
+}
+
+fn add(list: &mut Vec<Cidr>, range: Cidr) -> bool {
+    let added = !list.contains(&range);
+    if added {
+        list.push(range);
+    }
+    added
+}
+
+fn remove(list: &mut Vec<Cidr>, range: Cidr) -> bool {
+    let len = list.len();
+    list.retain(|&c| c != range);
+    list.len() != len
+}
@@ -0,0 +1,143 @@
+//! This module provides [`Links`], which binds characters to the sessions playing them.
+//! A character whose connection drops stays in the world as link-dead for [`Config::link_dead_grace`](super::Config::link_dead_grace),
+//! and logging in again takes it over in place
+use super::SessionId;
+use crate::entity::EntityId;
+use crate::session::Reports;
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// What happened when a session took control of a character
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    /// Nobody was playing the character
+    New,
+    /// The character was link-dead and this session picked it up
+    Reconnected,
+    /// Another session was playing the character and has been disconnected
+    Usurped(SessionId),
+}
+
+/// Why a session couldn't possess an entity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PossessError {
+    /// The session isn't playing a character to return to
+    NoCharacter,
+    /// Another session is playing the entity
+    Occupied(SessionId),
+    /// The entity is a link-dead character
+    LinkDead,
+}
+
+impl Display for PossessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PossessError::NoCharacter => f.write_str("you need a character of your own first"),
+            PossessError::Occupied(id) => write!(f, "already played by {}", id),
+            PossessError::LinkDead => f.write_str("that character is link-dead"),
+        }
+    }
+}
+
+impl std::error::Error for PossessError {}
+
+#[derive(Debug)]
+enum Link {
+    Playing(SessionId),
+    LinkDead { reports: Reports, generation: u64 },
+}
+
+/// Which session, if any, plays each character in the world
+#[derive(Debug, Default)]
+pub struct Links {
+    links: Mutex<HashMap<EntityId, Link>>,
+    generation: AtomicU64,
+}
+
+impl Links {
+    pub fn new() -> Self {
+        Links::default()
+    }
+
+    /// The session playing `entity`, if it has one
+    pub fn session(&self, entity: EntityId) -> Option<SessionId> {
+        match self.links.lock().unwrap().get(&entity) {
+            Some(Link::Playing(id)) => Some(*id),
+            _ => None,
+        }
+    }
+
+    pub fn is_link_dead(&self, entity: EntityId) -> bool {
+        matches!(self.links.lock().unwrap().get(&entity), Some(Link::LinkDead { .. }))
+    }
+
+    /// Every character in the world, played or link-dead
+    pub fn entities(&self) -> Vec<EntityId> {
+        let mut entities: Vec<_> = self.links.lock().unwrap().keys().copied().collect();
+        entities.sort();
+        entities
+    }
+
+    /// Characters currently link-dead
+    pub fn link_dead(&self) -> Vec<EntityId> {
+        let links = self.links.lock().unwrap();
+        let mut dead: Vec<_> = links.iter().filter(|(_, l)| matches!(l, Link::LinkDead { .. })).map(|(e, _)| *e).collect();
+        dead.sort();
+        dead
+    }
+
+    /// Record that `id` plays `entity`. Returns the previous link
+    pub(crate) fn bind(&self, entity: EntityId, id: SessionId) -> (Bound, Option<Reports>) {
+        match self.links.lock().unwrap().insert(entity, Link::Playing(id)) {
+            Some(Link::Playing(old)) if old != id => (Bound::Usurped(old), None),
+            Some(Link::LinkDead { reports, .. }) => (Bound::Reconnected, Some(reports)),
+            _ => (Bound::New, None),
+        }
+    }
+
+    /// Record that `id` plays `entity`, unless someone else is playing it or it is link-dead
+    pub(crate) fn claim(&self, entity: EntityId, id: SessionId) -> Result<(), PossessError> {
+        let mut links = self.links.lock().unwrap();
+        match links.get(&entity) {
+            Some(Link::Playing(other)) if *other != id => Err(PossessError::Occupied(*other)),
+            Some(Link::LinkDead { .. }) => Err(PossessError::LinkDead),
+            _ => {
+                links.insert(entity, Link::Playing(id));
+                Ok(())
+            }
+        }
+    }
+
+    /// Forget `entity` if `id` is playing it. Returns `false` if it wasn't
+    pub(crate) fn release(&self, entity: EntityId, id: SessionId) -> bool {
+        let mut links = self.links.lock().unwrap();
+        let playing = matches!(links.get(&entity), Some(Link::Playing(p)) if *p == id);
+        if playing {
+            links.remove(&entity);
+        }
+        playing
+    }
+
+    /// Mark `entity` link-dead if `id` was playing it, returning a generation to pass to [`Links::expire`]
+    pub(crate) fn drop_link(&self, entity: EntityId, id: SessionId, reports: Reports) -> Option<u64> {
+        let mut links = self.links.lock().unwrap();
+        if !matches!(links.get(&entity), Some(Link::Playing(p)) if *p == id) {
+            return None;
+        }
+        let generation = self.generation.fetch_add(1, Ordering::Relaxed);
+        links.insert(entity, Link::LinkDead { reports, generation });
+        Some(generation)
+    }
+
+    /// Forget `entity` if it is still link-dead since the same [`Links::drop_link`]
+    pub(crate) fn expire(&self, entity: EntityId, generation: u64) -> bool {
+        let mut links = self.links.lock().unwrap();
+        let expired = matches!(links.get(&entity), Some(Link::LinkDead { generation: g, .. }) if *g == generation);
+        if expired {
+            links.remove(&entity);
+        }
+        expired
+    }
+}
@@ -0,0 +1,1018 @@
+//! This module provides the async TCP game server: a listener, one task per connection, and a registry of sessions
+//!
+//! ```no_run
+//! # async fn run() -> std::io::Result<()> {
+//! let server = lumina::server::Server::builder()
+//!     .bind("0.0.0.0:4000")
+//!     .command("say", |ctx, args| ctx.server.broadcast(&format!("{} says '{}'\n", ctx.id, args)))
+//!     .build();
+//! server.run().await
+//! # }
+//! ```
+pub mod audit;
+pub mod autosave;
+mod connection;
+#[cfg(unix)]
+pub mod copyover;
+pub mod doors;
+pub mod guard;
+pub mod links;
+pub mod proxy;
+pub mod queue;
+mod registry;
+pub mod repop;
+pub mod snoop;
+#[cfg(feature = "ssh")]
+pub mod ssh;
+#[cfg(test)]
+mod tests;
+#[cfg(feature = "tls")]
+pub mod tls;
+mod transport;
+#[cfg(feature = "websocket")]
+pub mod websocket;
+
+pub use registry::{Handle, Message, Registry, SessionId};
+
+use crate::account::{Accounts, Lockout, Login, Nanny, Wizard};
+use crate::charset::Charset;
+use crate::commands::{Commands, Context};
+use crate::session::{Input, Limits, Rate, Session};
+use crate::store::Store;
+use crate::entity::{Allocator, EntityId};
+use crate::msdp::{self, Variable};
+use crate::permission::{self, Change, PermissionError, Permissions};
+use crate::rooms::{Area, Areas, DoorAction, Populate, World};
+use audit::{AuditEntry, AuditLog};
+use autosave::{Autosave, SaveKind};
+use guard::{Cidr, Guard};
+use links::{Bound, Links, PossessError};
+use snoop::{SnoopError, Snoops};
+use queue::OutputLimits;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, RawFd};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{watch, Notify};
+use tokio::task::JoinSet;
+use transport::Stream;
+
+/// Listener and session settings
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Address to listen on for telnet, e.g. `0.0.0.0:4000`
+    pub addr: String,
+    /// Address to listen on for WebSocket clients, if any
+    #[cfg(feature = "websocket")]
+    pub websocket_addr: Option<String>,
+    /// Where to listen for TLS connections, if anywhere
+    #[cfg(feature = "tls")]
+    pub tls: Option<tls::TlsConfig>,
+    /// Where to listen for SSH connections, if anywhere
+    #[cfg(feature = "ssh")]
+    pub ssh: Option<ssh::SshConfig>,
+    /// How long a client gets to complete the TLS, WebSocket or SSH handshake
+    pub handshake_timeout: Duration,
+    /// Sent to every session when a copyover starts
+    pub copyover_message: String,
+    /// Sent to every session that survived a copyover
+    pub copyover_done: String,
+    /// Sent to sessions whose connection can't survive a copyover, before they are closed
+    pub copyover_lost: String,
+    /// Sent to every new connection
+    pub greeting: String,
+    /// Sent to every connection when the server shuts down
+    pub goodbye: String,
+    /// Sent after each command
+    pub prompt: String,
+    /// The charset used until a client negotiates one
+    pub charset: Charset,
+    /// The longest line accepted from a player, in characters
+    pub line_limit: usize,
+    /// How long shutdown waits for connections to flush their output
+    pub shutdown_timeout: Duration,
+    /// Connections accepted from one address, see [`Guard`]
+    pub connection_rate: Option<Rate>,
+    /// Load balancers that send a [`proxy`] header with the real client address. Anyone else is taken at face value
+    pub trusted_proxies: Vec<Cidr>,
+    /// Input flood protection for each session
+    pub limits: Limits,
+    /// Output buffering and slow client handling for each session
+    pub output: OutputLimits,
+    /// Sent to sessions disconnected by [`Server::ban`]
+    pub banned: String,
+    /// How long a character stays in the world after its connection drops, see [`links`]
+    pub link_dead_grace: Duration,
+    /// Sent to a session whose character was taken over by a new login
+    pub usurped: String,
+    /// Sent to a session when an administrator starts snooping it. Snoops are silent if this is `None`
+    pub snoop_notice: Option<String>,
+    /// Shown after logging in to an account, before the character menu. Skipped if empty
+    pub motd: String,
+    /// Failed logins that lock an account
+    pub lockout: Lockout,
+    /// The steps of character creation, after the player names their character.
+    /// Its starting rooms must exist in [`Server::world`] by the time the server is served
+    pub creation: Wizard,
+    /// Only let encrypted connections (TLS or SSH) log in, so passwords are never sent in the clear
+    pub require_secure_login: bool,
+    /// Sent to plaintext connections before they are closed, when [`Config::require_secure_login`] is set
+    pub insecure_login: String,
+    /// How often dirty characters are saved, see [`autosave`]. Never if `None`
+    pub autosave_interval: Option<Duration>,
+    /// How often the whole world is snapshotted. Never if `None`
+    pub snapshot_interval: Option<Duration>,
+    /// The MSDP variables the game offers. If there are any, the server answers `LIST` requests for them,
+    /// and hides restricted ones from players without their permission
+    pub msdp_variables: Vec<Variable>,
+    /// How often areas are checked for a reset, see [`repop`]. Only when the server starts if `None`
+    pub area_tick: Option<Duration>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            addr: "0.0.0.0:4000".to_string(),
+            #[cfg(feature = "websocket")]
+            websocket_addr: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+            #[cfg(feature = "ssh")]
+            ssh: None,
+            handshake_timeout: Duration::from_secs(10),
+            copyover_message: "Copyover in progress, please hold on...\n".to_string(),
+            copyover_done: "Copyover complete.\n".to_string(),
+            copyover_lost: "Your connection can't survive the reboot, please reconnect in a moment.\n".to_string(),
+            greeting: "Welcome!\n".to_string(),
+            goodbye: "The server is shutting down. Goodbye!\n".to_string(),
+            prompt: "> ".to_string(),
+            charset: Charset::default(),
+            line_limit: Session::DEFAULT_LINE_LIMIT,
+            shutdown_timeout: Duration::from_secs(5),
+            connection_rate: Some(Rate::new(10, Duration::from_secs(30))),
+            trusted_proxies: Vec::new(),
+            limits: Limits::default(),
+            output: OutputLimits::default(),
+            banned: "You have been banned.\n".to_string(),
+            link_dead_grace: Duration::from_secs(180),
+            usurped: "Someone else has logged in as your character.\n".to_string(),
+            snoop_notice: None,
+            motd: String::new(),
+            lockout: Lockout::default(),
+            creation: Wizard::default(),
+            require_secure_login: false,
+            insecure_login: "Please connect with TLS or SSH to log in.\n".to_string(),
+            autosave_interval: Some(Duration::from_secs(60)),
+            snapshot_interval: Some(Duration::from_secs(15 * 60)),
+            msdp_variables: Vec::new(),
+            area_tick: Some(Duration::from_secs(60)),
+        }
+    }
+}
+
+/// A bound socket and the protocol spoken on it
+#[derive(Debug)]
+pub enum Listener {
+    Telnet(TcpListener),
+    /// WebSocket clients, see [`websocket`]
+    #[cfg(feature = "websocket")]
+    WebSocket(TcpListener),
+    /// Telnet over TLS
+    #[cfg(feature = "tls")]
+    Tls(TcpListener, Arc<tls::Certificates>),
+    /// SSH clients, see [`ssh`]
+    #[cfg(feature = "ssh")]
+    Ssh(TcpListener, Arc<russh::server::Config>),
+}
+
+impl Listener {
+    #[cfg(unix)]
+    fn kind(&self) -> copyover::ListenerKind {
+        match self {
+            Listener::Telnet(_) => copyover::ListenerKind::Telnet,
+            #[cfg(feature = "websocket")]
+            Listener::WebSocket(_) => copyover::ListenerKind::WebSocket,
+            #[cfg(feature = "tls")]
+            Listener::Tls(..) => copyover::ListenerKind::Tls,
+            #[cfg(feature = "ssh")]
+            Listener::Ssh(..) => copyover::ListenerKind::Ssh,
+        }
+    }
+
+    fn socket(&self) -> &TcpListener {
+        match self {
+            Listener::Telnet(l) => l,
+            #[cfg(feature = "websocket")]
+            Listener::WebSocket(l) => l,
+            #[cfg(feature = "tls")]
+            Listener::Tls(l, _) => l,
+            #[cfg(feature = "ssh")]
+            Listener::Ssh(l, _) => l,
+        }
+    }
+}
+
+type ContextHook = Box<dyn Fn(&mut Context) + Send + Sync>;
+type InputHook = Box<dyn Fn(&mut Context, Input) + Send + Sync>;
+type DisconnectHook = Box<dyn Fn(&Server, SessionId) + Send + Sync>;
+type ServerHook = Box<dyn Fn(&Server) + Send + Sync>;
+type EntityHook = Box<dyn Fn(&Server, EntityId) + Send + Sync>;
+type AuditHook = Box<dyn Fn(&Server, &AuditEntry) + Send + Sync>;
+type ErrorHook = Box<dyn Fn(&Server, &str) + Send + Sync>;
+type LoginHook = Box<dyn Fn(&mut Context, &Login, Bound) + Send + Sync>;
+type SaveHook = Box<dyn Fn(&Server, EntityId) -> Option<(String, serde_json::Value)> + Send + Sync>;
+type SnapshotHook = Box<dyn Fn(&Server) -> serde_json::Value + Send + Sync>;
+type KeyHook = Box<dyn Fn(&Server, EntityId, u32) -> bool + Send + Sync>;
+type SkillHook = Box<dyn Fn(&Server, EntityId, DoorAction) -> u8 + Send + Sync>;
+
+/// A game server. Build one with [`Server::builder`]
+pub struct Server {
+    pub config: Config,
+    pub commands: Commands,
+    pub registry: Registry,
+    /// Ban and allow lists, checked for every new connection
+    pub guard: Guard,
+    /// Which session plays each character
+    pub links: Links,
+    /// Which sessions administrators are watching
+    pub snoops: Snoops,
+    /// Recent privileged actions
+    pub audit: AuditLog,
+    /// Player accounts. If set, new sessions log in through the [`Nanny`] before reaching the game
+    pub accounts: Option<Accounts>,
+    /// Ids for new characters and other entities
+    pub entities: Allocator,
+    /// Where accounts are saved when they change, and characters and snapshots by [`autosave`]
+    pub store: Option<Store>,
+    /// Which characters need saving, and how recent saves went
+    pub autosave: Autosave,
+    /// Roles and grants, checked by restricted commands and MSDP variables
+    pub permissions: Permissions,
+    /// Every room
+    pub world: World,
+    /// The areas rooms are grouped into, and when each last reset
+    pub areas: Areas,
+    populate: Option<Box<dyn Populate>>,
+    /// Sessions going through the login, or changing their password
+    logins: Mutex<HashMap<SessionId, Nanny>>,
+    next_id: AtomicU64,
+    shutdown: watch::Sender<bool>,
+    copyover: Notify,
+    #[cfg(unix)]
+    listener_fds: Mutex<Vec<(copyover::ListenerKind, RawFd)>>,
+    on_connect: Option<ContextHook>,
+    on_restore: Option<ContextHook>,
+    on_input: Option<InputHook>,
+    on_disconnect: Option<DisconnectHook>,
+    on_shutdown: Option<ServerHook>,
+    on_link_dead: Option<EntityHook>,
+    on_link_expired: Option<EntityHook>,
+    on_audit: Option<AuditHook>,
+    on_error: Option<ErrorHook>,
+    on_login: Option<LoginHook>,
+    on_save_character: Option<SaveHook>,
+    on_snapshot: Option<SnapshotHook>,
+    has_key: Option<KeyHook>,
+    door_skill: Option<SkillHook>,
+}
+
+impl Server {
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    fn next_id(&self) -> SessionId {
+        SessionId(self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Send text to every connected session
+    pub fn broadcast(&self, text: &str) {
+        self.registry.broadcast(text);
+    }
+
+    /// Ban an address range and disconnect every session connected from it.
+    /// Returns `false` if the range was already banned
+    pub fn ban(&self, range: Cidr) -> bool {
+        let added = self.guard.ban(range);
+        for id in self.registry.ids() {
+            if self.registry.get(id).is_some_and(|h| self.guard.is_banned(h.addr.ip())) {
+                self.registry.kick(id, &self.config.banned);
+            }
+        }
+        added
+    }
+
+    /// Make session `id` play `entity`. If the character was link-dead the session takes it over,
+    /// along with its MSDP subscriptions; if another session was playing it, that one is disconnected
+    pub fn bind(&self, id: SessionId, session: &mut Session, entity: EntityId) -> Bound {
+        // Logging in as someone else ends any possession, and the possessing character is let go too
+        if let Some(original) = session.set_original(None).filter(|&o| o != entity) {
+            self.links.release(original, id);
+        }
+        let (bound, reports) = self.links.bind(entity, id);
+        if let Some(previous) = session.attach(entity).filter(|&p| p != entity) {
+            self.links.release(previous, id);
+        }
+        if let Bound::Usurped(old) = bound {
+            self.registry.kick(old, &self.config.usurped);
+        }
+        if let Some(reports) = reports {
+            session.resume_reports(reports);
+        }
+        bound
+    }
+
+    /// Stop playing the session's character, e.g. when the player quits, so it doesn't go link-dead
+    pub fn unbind(&self, id: SessionId, session: &mut Session) -> Option<EntityId> {
+        self.unpossess(id, session);
+        let entity = session.detach()?;
+        self.links.release(entity, id);
+        Some(entity)
+    }
+
+    /// Control `entity`, such as a mobile, from session `id` in place of the session's own character.
+    /// The character stays bound to the session until [`Server::unpossess`] returns to it
+    pub fn possess(&self, id: SessionId, session: &mut Session, entity: EntityId) -> Result<(), PossessError> {
+        let current = session.entity().ok_or(PossessError::NoCharacter)?;
+        let original = session.original().unwrap_or(current);
+        if entity == original {
+            self.unpossess(id, session);
+            return Ok(());
+        }
+        if entity == current {
+            return Ok(());
+        }
+        self.links.claim(entity, id)?;
+        if current != original {
+            self.links.release(current, id);
+        }
+        session.attach(entity);
+        session.set_original(Some(original));
+        self.audit(id, &format!("possess {} as {}", entity, original));
+        Ok(())
+    }
+
+    /// Return session `id` to its own character after [`Server::possess`]. Returns the entity it let go of
+    pub fn unpossess(&self, id: SessionId, session: &mut Session) -> Option<EntityId> {
+        let original = session.set_original(None)?;
+        let possessed = session.attach(original).filter(|&p| p != original)?;
+        self.links.release(possessed, id);
+        self.audit(id, &format!("return from {} to {}", possessed, original));
+        Some(possessed)
+    }
+
+    /// Copy everything written to session `target` to session `id`, which stops snooping anyone else
+    pub fn snoop(&self, id: SessionId, target: SessionId) -> Result<(), SnoopError> {
+        let handle = self.registry.get(target).ok_or(SnoopError::NotConnected)?;
+        if let Some(previous) = self.snoops.start(id, target)? {
+            self.unsnooped(id, previous);
+        }
+        if !handle.send(Message::Snoop(true)) {
+            self.snoops.stop(id);
+            return Err(SnoopError::NotConnected);
+        }
+        if let Some(notice) = &self.config.snoop_notice {
+            handle.send(Message::Text(notice.clone()));
+        }
+        self.audit(id, &format!("snoop {}", target));
+        Ok(())
+    }
+
+    /// Stop session `id` snooping. Returns the session it was watching
+    pub fn unsnoop(&self, id: SessionId) -> Option<SessionId> {
+        let target = self.snoops.stop(id)?;
+        self.unsnooped(id, target);
+        Some(target)
+    }
+
+    fn unsnooped(&self, id: SessionId, target: SessionId) {
+        if let Some(handle) = self.registry.get(target) {
+            handle.send(Message::Snoop(false));
+        }
+        self.audit(id, &format!("stop snooping {}", target));
+    }
+
+    /// Session `id` disconnected: end any snoop it was part of
+    fn snoops_lost(&self, id: SessionId) {
+        let (snooper, target) = self.snoops.remove(id);
+        if let Some(snooper) = snooper {
+            self.audit(snooper, &format!("stop snooping {}", id));
+            self.registry.send(snooper, &format!("% {} has disconnected.\n", id));
+        }
+        if let Some(target) = target {
+            self.unsnooped(id, target);
+        }
+    }
+
+    /// Whether session `id` is logging in or changing its password, so its input goes to the [`Nanny`]
+    pub fn is_logging_in(&self, id: SessionId) -> bool {
+        self.logins.lock().unwrap().contains_key(&id)
+    }
+
+    /// Send session `id`'s input to `nanny` until it finishes
+    pub fn start_login(&self, id: SessionId, session: &mut Session, nanny: Nanny) {
+        nanny.prompt(self, session);
+        self.logins.lock().unwrap().insert(id, nanny);
+    }
+
+    /// Record a privileged action by session `actor` in the [`AuditLog`], with the address it connected from
+    pub fn audit(&self, actor: SessionId, action: &str) {
+        let addr = self.registry.get(actor).map(|h| h.addr);
+        let entry = AuditEntry { at: SystemTime::now(), actor, addr, action: action.to_string() };
+        if let Some(hook) = &self.on_audit {
+            hook(self, &entry);
+        }
+        self.audit.record(entry);
+    }
+
+    /// Pass a failure to the [`on_error`](Builder::on_error) hook
+    pub fn report_error(&self, message: &str) {
+        if let Some(hook) = &self.on_error {
+            hook(self, message);
+        }
+    }
+
+    /// Whether the session's account has `permission`, see [`Permissions::allows`]
+    pub fn allows(&self, session: &Session, permission: &str) -> bool {
+        self.permissions.allows(session.account(), permission)
+    }
+
+    /// Change a role or an account's grants on behalf of session `actor`, recording it in the [`AuditLog`].
+    /// Every session sees the change at once
+    pub fn change_permissions(&self, actor: SessionId, change: Change) -> Result<(), PermissionError> {
+        let action = change.to_string();
+        self.permissions.apply(change)?;
+        self.audit(actor, &action);
+        Ok(())
+    }
+
+    /// The restricted MSDP variables the session may not see
+    pub(crate) fn msdp_hidden(&self, session: &Session) -> Vec<String> {
+        let variables = self.config.msdp_variables.iter();
+        let hidden = variables.filter(|v| v.permission.as_ref().is_some_and(|p| !self.allows(session, p)));
+        hidden.map(|v| v.name.clone()).collect()
+    }
+
+    /// Answer `LIST SENDABLE_VARIABLES` and the like from [`Config::msdp_variables`], leaving out hidden ones
+    pub(crate) fn answer_msdp(&self, session: &mut Session, data: &[u8]) {
+        if self.config.msdp_variables.is_empty() {
+            return;
+        }
+        let lists = msdp::variables::list_requests(data);
+        for list in lists.iter().filter(|l| l.ends_with("ABLE_VARIABLES")) {
+            let listed = self.config.msdp_variables.iter().filter(|v| v.is_listed(list));
+            let names: Vec<_> = listed.filter(|v| !session.is_msdp_hidden(&v.name)).map(|v| v.name.as_str()).collect();
+            let _ = session.send_msdp(list, &names);
+        }
+    }
+
+    /// The connection of session `id` is gone: leave its character link-dead for the grace period
+    fn link_lost(self: &Arc<Self>, id: SessionId, session: &Session) {
+        let Some(entity) = session.entity() else {
+            return;
+        };
+        if *self.shutdown.borrow() {
+            self.links.release(entity, id);
+            return;
+        }
+        let Some(generation) = self.links.drop_link(entity, id, session.reports()) else {
+            return;
+        };
+        if let Some(hook) = &self.on_link_dead {
+            hook(self, entity);
+        }
+        let server = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(server.config.link_dead_grace).await;
+            if server.links.expire(entity, generation) {
+                if let Some(hook) = &server.on_link_expired {
+                    hook(&server, entity);
+                }
+            }
+        });
+    }
+
+    /// Ask the server to shut down gracefully
+    pub fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    /// Reboot into the current binary without disconnecting players, see [`copyover`]
+    #[cfg(unix)]
+    pub fn copyover(&self) {
+        self.copyover.notify_one();
+    }
+
+    /// Bind every configured address and serve until shutdown.
+    /// If this process was started by a copyover, the inherited listeners and sessions are used instead
+    pub async fn run(self: &Arc<Self>) -> std::io::Result<()> {
+        #[cfg(unix)]
+        if let Some(state) = copyover::take_state()? {
+            let listeners = copyover::restore(self, state);
+            return self.serve(listeners).await;
+        }
+        #[allow(unused_mut)]
+        let mut listeners = vec![Listener::Telnet(TcpListener::bind(&self.config.addr).await?)];
+        #[cfg(feature = "websocket")]
+        if let Some(addr) = &self.config.websocket_addr {
+            listeners.push(Listener::WebSocket(TcpListener::bind(addr).await?));
+        }
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &self.config.tls {
+            let certificates = Arc::new(tls::Certificates::load(&tls.cert, &tls.key)?);
+            listeners.push(Listener::Tls(TcpListener::bind(&tls.addr).await?, certificates));
+        }
+        #[cfg(feature = "ssh")]
+        if let Some(config) = &self.config.ssh {
+            listeners.push(Listener::Ssh(TcpListener::bind(&config.addr).await?, ssh::server_config(config)?));
+        }
+        self.serve(listeners).await
+    }
+
+    /// Serve connections from already bound listeners until [`Server::shutdown`] is called or the process gets Ctrl-C.
+    /// Every listener shares the same registry. Fails if the creation wizard offers a room that doesn't exist
+    pub async fn serve(self: &Arc<Self>, listeners: Vec<Listener>) -> std::io::Result<()> {
+        let rooms = self.config.creation.check_rooms(&self.world);
+        rooms.map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let mut shutdown = self.shutdown.subscribe();
+        let mut acceptors = JoinSet::new();
+        #[cfg(all(feature = "tls", unix))]
+        {
+            let certificates: Vec<_> = listeners
+                .iter()
+                .filter_map(|l| match l {
+                    Listener::Tls(_, c) => Some(c.clone()),
+                    _ => None,
+                })
+                .collect();
+            if !certificates.is_empty() {
+                acceptors.spawn(tls::reload_on_hangup(self.clone(), certificates));
+            }
+        }
+        for listener in listeners {
+            #[cfg(unix)]
+            self.listener_fds.lock().unwrap().push((listener.kind(), listener.socket().as_raw_fd()));
+            acceptors.spawn(self.clone().accept(listener));
+        }
+        if self.store.is_some() {
+            acceptors.spawn(autosave::schedule(self.clone()));
+        }
+        if self.populate.is_some() {
+            acceptors.spawn(repop::schedule(self.clone()));
+        }
+        loop {
+            tokio::select! {
+                _ = async { shutdown.wait_for(|&stop| stop).await.is_ok() } => break,
+                _ = tokio::signal::ctrl_c() => break,
+                _ = self.copyover.notified() => {
+                    #[cfg(unix)]
+                    if let Err(e) = copyover::perform(self).await {
+                        self.broadcast(&format!("Copyover failed: {}\n", e));
+                    }
+                }
+            }
+        }
+        acceptors.shutdown().await;
+        if let Some(hook) = &self.on_shutdown {
+            hook(self);
+        }
+        // The world is final now, save it before the players go
+        self.save(SaveKind::All).await;
+        self.save(SaveKind::Snapshot).await;
+        self.registry.close_all(&self.config.goodbye);
+        let _ = tokio::time::timeout(self.config.shutdown_timeout, self.registry.wait_empty()).await;
+        Ok(())
+    }
+
+    /// Read the PROXY header from a trusted load balancer, then check the real address against the [`Guard`].
+    /// A header without an address (`LOCAL` or `UNKNOWN`) is the proxy's own connection, such as a health check:
+    /// it keeps the socket's address, as the PROXY protocol asks, and doesn't count against the proxy
+    async fn client_address(
+        &self,
+        mut stream: TcpStream,
+        addr: SocketAddr,
+        proxied: bool,
+    ) -> std::io::Result<(TcpStream, SocketAddr)> {
+        if !proxied {
+            return Ok((stream, addr));
+        }
+        let header = tokio::time::timeout(self.config.handshake_timeout, proxy::read_header(&mut stream)).await??;
+        let Some(addr) = header else {
+            return Ok((stream, addr));
+        };
+        if let Err(refusal) = self.guard.admit(addr.ip()) {
+            return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, format!("{:?}", refusal)));
+        }
+        Ok((stream, addr))
+    }
+
+    async fn accept(self: Arc<Self>, listener: Listener) {
+        loop {
+            let (stream, addr) = match listener.socket().accept().await {
+                Ok(accepted) => accepted,
+                Err(_) => {
+                    // Usually out of file descriptors; back off instead of spinning
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            // Proxied connections are checked once the real address is known
+            let proxied = self.config.trusted_proxies.iter().any(|c| c.contains(addr.ip()));
+            if !proxied && self.guard.admit(addr.ip()).is_err() {
+                continue;
+            }
+            let _ = stream.set_nodelay(true);
+            let server = self.clone();
+            match listener {
+                Listener::Telnet(_) => {
+                    tokio::spawn(async move {
+                        let (stream, addr) = server.client_address(stream, addr, proxied).await?;
+                        connection::run(server, Stream::new(stream), addr).await
+                    });
+                }
+                #[cfg(feature = "websocket")]
+                Listener::WebSocket(_) => {
+                    let timeout = self.config.handshake_timeout;
+                    tokio::spawn(async move {
+                        let (stream, addr) = server.client_address(stream, addr, proxied).await?;
+                        let transport = tokio::time::timeout(timeout, websocket::accept(stream)).await??;
+                        connection::run(server, transport, addr).await
+                    });
+                }
+                #[cfg(feature = "tls")]
+                Listener::Tls(_, ref certificates) => {
+                    let (acceptor, timeout) = (certificates.acceptor(), self.config.handshake_timeout);
+                    tokio::spawn(async move {
+                        let (stream, addr) = server.client_address(stream, addr, proxied).await?;
+                        let stream = tokio::time::timeout(timeout, acceptor.accept(stream)).await??;
+                        connection::run(server, Stream::secure(stream), addr).await
+                    });
+                }
+                #[cfg(feature = "ssh")]
+                Listener::Ssh(_, ref config) => {
+                    let (config, timeout) = (config.clone(), self.config.handshake_timeout);
+                    let auth = self.config.ssh.as_ref().map(|c| c.auth.clone()).unwrap_or_default();
+                    tokio::spawn(async move {
+                        let (stream, addr) = server.client_address(stream, addr, proxied).await?;
+                        let transport = tokio::time::timeout(timeout, ssh::accept(stream, config, auth)).await??;
+                        connection::run(server, transport, addr).await
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Configures and creates a [`Server`]
+#[derive(Default)]
+pub struct Builder {
+    config: Config,
+    commands: Commands,
+    on_connect: Option<ContextHook>,
+    on_restore: Option<ContextHook>,
+    on_input: Option<InputHook>,
+    on_disconnect: Option<DisconnectHook>,
+    on_shutdown: Option<ServerHook>,
+    on_link_dead: Option<EntityHook>,
+    on_link_expired: Option<EntityHook>,
+    on_audit: Option<AuditHook>,
+    on_error: Option<ErrorHook>,
+    accounts: Option<Accounts>,
+    store: Option<Store>,
+    permissions: Permissions,
+    world: World,
+    areas: Areas,
+    populate: Option<Box<dyn Populate>>,
+    on_login: Option<LoginHook>,
+    on_save_character: Option<SaveHook>,
+    on_snapshot: Option<SnapshotHook>,
+    has_key: Option<KeyHook>,
+    door_skill: Option<SkillHook>,
+}
+
+impl Builder {
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn bind(mut self, addr: &str) -> Self {
+        self.config.addr = addr.to_string();
+        self
+    }
+
+    /// Also accept WebSocket clients on `addr`
+    #[cfg(feature = "websocket")]
+    pub fn websocket(mut self, addr: &str) -> Self {
+        self.config.websocket_addr = Some(addr.to_string());
+        self
+    }
+
+    /// Also accept telnet over TLS, see [`tls`]
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, config: tls::TlsConfig) -> Self {
+        self.config.tls = Some(config);
+        self
+    }
+
+    pub fn greeting(mut self, greeting: &str) -> Self {
+        self.config.greeting = greeting.to_string();
+        self
+    }
+
+    pub fn goodbye(mut self, goodbye: &str) -> Self {
+        self.config.goodbye = goodbye.to_string();
+        self
+    }
+
+    pub fn prompt(mut self, prompt: &str) -> Self {
+        self.config.prompt = prompt.to_string();
+        self
+    }
+
+    /// Register a command, see [`Commands::register`]
+    pub fn command<F>(mut self, name: &str, handler: F) -> Self
+    where
+        F: Fn(&mut Context, &str) + Send + Sync + 'static,
+    {
+        self.commands.register(name, handler);
+        self
+    }
+
+    pub fn commands(mut self, commands: Commands) -> Self {
+        self.commands = commands;
+        self
+    }
+
+    /// Called when a connection opens, after the greeting
+    pub fn on_connect<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&mut Context) + Send + Sync + 'static,
+    {
+        self.on_connect = Some(Box::new(hook));
+        self
+    }
+
+    /// Called for each session that survived a copyover, so the game can reattach its character
+    /// (see [`Session::entity`])
+    pub fn on_restore<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&mut Context) + Send + Sync + 'static,
+    {
+        self.on_restore = Some(Box::new(hook));
+        self
+    }
+
+    /// Called with out-of-band input (MSDP and GMCP)
+    pub fn on_input<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&mut Context, Input) + Send + Sync + 'static,
+    {
+        self.on_input = Some(Box::new(hook));
+        self
+    }
+
+    /// Called after a connection closes
+    pub fn on_disconnect<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Server, SessionId) + Send + Sync + 'static,
+    {
+        self.on_disconnect = Some(Box::new(hook));
+        self
+    }
+
+    /// Called when the server shuts down, before players are disconnected, and before a copyover. Save game state here
+    pub fn on_shutdown<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Server) + Send + Sync + 'static,
+    {
+        self.on_shutdown = Some(Box::new(hook));
+        self
+    }
+
+    /// Called when a character's connection drops and it goes link-dead
+    pub fn on_link_dead<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Server, EntityId) + Send + Sync + 'static,
+    {
+        self.on_link_dead = Some(Box::new(hook));
+        self
+    }
+
+    /// Called when a link-dead character's grace period ends without a reconnect. Remove it from the world here
+    pub fn on_link_expired<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Server, EntityId) + Send + Sync + 'static,
+    {
+        self.on_link_expired = Some(Box::new(hook));
+        self
+    }
+
+    /// Called for every privileged action, such as a snoop or possession, as it is added to the [`AuditLog`].
+    /// Write it to a file or database here
+    pub fn on_audit<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Server, &AuditEntry) + Send + Sync + 'static,
+    {
+        self.on_audit = Some(Box::new(hook));
+        self
+    }
+
+    /// Called when something fails that no player can be told about in full, such as an account that couldn't be
+    /// saved or a TLS certificate that couldn't be reloaded. Log it here; without a hook these are dropped
+    pub fn on_error<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Server, &str) + Send + Sync + 'static,
+    {
+        self.on_error = Some(Box::new(hook));
+        self
+    }
+
+    /// Make players log in to an account before they reach the game, see [`account`](crate::account).
+    /// Adds a `password` command, unless one was registered
+    pub fn accounts(mut self, accounts: Accounts) -> Self {
+        self.accounts = Some(accounts);
+        self
+    }
+
+    /// Save accounts to `store` whenever one is created or changed, and characters and snapshots by [`autosave`].
+    /// Adds `save` and `snapshot` commands, unless they were registered
+    pub fn store(mut self, store: Store) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// The roles and grants to start with, see [`permission`](crate::permission)
+    pub fn permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    /// Only let players with `permission` use the command called `name`, see [`Commands::restrict`]
+    pub fn restrict(mut self, name: &str, permission: &str) -> Self {
+        self.commands.restrict(name, permission);
+        self
+    }
+
+    /// The rooms to start with. If there are any, adds the [`doors`] commands, unless they were registered
+    pub fn world(mut self, world: World) -> Self {
+        self.world = world;
+        self
+    }
+
+    pub fn area(self, area: Area) -> Self {
+        self.areas.insert(area);
+        self
+    }
+
+    /// Load mobiles and objects for area resets, see [`repop`]. Adds a `reset` command, unless one was registered
+    pub fn populate(mut self, populate: impl Populate + 'static) -> Self {
+        self.populate = Some(Box::new(populate));
+        self
+    }
+
+    /// Asked whether a character carries the key with object vnum `key`, to lock or unlock a door.
+    /// Without it nobody has keys
+    pub fn has_key<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Server, EntityId, u32) -> bool + Send + Sync + 'static,
+    {
+        self.has_key = Some(Box::new(hook));
+        self
+    }
+
+    /// Asked for a character's chance in percent to pick or bash a door. A door's pick difficulty is taken off.
+    /// Without it every attempt fails
+    pub fn door_skill<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Server, EntityId, DoorAction) -> u8 + Send + Sync + 'static,
+    {
+        self.door_skill = Some(Box::new(hook));
+        self
+    }
+
+    /// Called when a player picks a character after logging in, once the session is bound to it.
+    /// Put the character in the world here
+    pub fn on_login<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&mut Context, &Login, Bound) + Send + Sync + 'static,
+    {
+        self.on_login = Some(Box::new(hook));
+        self
+    }
+
+    /// Called by [`autosave`] for each character it saves, returning the name to save it under and its state.
+    /// Return `None` if there's nothing to save
+    pub fn on_save_character<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Server, EntityId) -> Option<(String, serde_json::Value)> + Send + Sync + 'static,
+    {
+        self.on_save_character = Some(Box::new(hook));
+        self
+    }
+
+    /// Called by [`autosave`] for a snapshot of the whole world. Nothing may change while it runs,
+    /// so the snapshot is consistent
+    pub fn on_snapshot<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Server) -> serde_json::Value + Send + Sync + 'static,
+    {
+        self.on_snapshot = Some(Box::new(hook));
+        self
+    }
+
+    pub fn build(mut self) -> Arc<Server> {
+        if self.accounts.is_some() && !self.commands.contains("password") {
+            self.commands.register("password", |ctx, _| {
+                if !ctx.change_password() {
+                    ctx.session.write("You aren't logged in to an account.\n");
+                }
+            });
+        }
+        if self.store.is_some() && !self.commands.contains("save") {
+            self.commands.register("save", |ctx, args| {
+                if !args.eq_ignore_ascii_case("all") {
+                    ctx.session.write("Save what? Try `save all`.\n");
+                    return;
+                }
+                ctx.server.audit(ctx.id, "save all");
+                ctx.server.autosave.request(SaveKind::All, Some(ctx.id));
+                ctx.session.write("Saving every character...\n");
+            });
+            self.commands.restrict("save", "admin.save");
+        }
+        if self.store.is_some() && !self.commands.contains("snapshot") {
+            self.commands.register("snapshot", |ctx, _| {
+                ctx.server.audit(ctx.id, "snapshot");
+                ctx.server.autosave.request(SaveKind::Snapshot, Some(ctx.id));
+                ctx.session.write("Taking a snapshot...\n");
+            });
+            self.commands.restrict("snapshot", "admin.snapshot");
+        }
+        if self.populate.is_some() && !self.commands.contains("reset") {
+            self.commands.register("reset", |ctx, args| {
+                let Some(area) = ctx.server.areas.get(args.trim()) else {
+                    ctx.session.write("Reset which area?\n");
+                    return;
+                };
+                let builder = ctx.session.account().is_some_and(|a| area.is_builder(a));
+                if !builder && !ctx.allows(&permission::build(&area.name)) {
+                    ctx.session.write("You can't build there.\n");
+                    return;
+                }
+                ctx.server.audit(ctx.id, &format!("reset {}", area.name));
+                if let Some(report) = ctx.server.reset_area(&area.name) {
+                    ctx.session.write(&format!("{}\n", report));
+                }
+            });
+        }
+        if !self.world.is_empty() {
+            doors::register(&mut self.commands);
+        }
+        let entities = Allocator::new();
+        for character in self.accounts.iter().flat_map(|a| a.all()).flat_map(|a| a.characters) {
+            entities.reserve(character.entity);
+        }
+        Arc::new(Server {
+            guard: Guard::new(self.config.connection_rate),
+            links: Links::new(),
+            snoops: Snoops::new(),
+            audit: AuditLog::default(),
+            accounts: self.accounts,
+            entities,
+            store: self.store,
+            autosave: Autosave::new(),
+            permissions: self.permissions,
+            world: self.world,
+            areas: self.areas,
+            populate: self.populate,
+            logins: Mutex::new(HashMap::new()),
+            config: self.config,
+            commands: self.commands,
+            registry: Registry::new(),
+            next_id: AtomicU64::new(1),
+            shutdown: watch::channel(false).0,
+            copyover: Notify::new(),
+            #[cfg(unix)]
+            listener_fds: Mutex::new(Vec::new()),
+            on_connect: self.on_connect,
+            on_restore: self.on_restore,
+            on_input: self.on_input,
+            on_disconnect: self.on_disconnect,
+            on_shutdown: self.on_shutdown,
+            on_link_dead: self.on_link_dead,
+            on_link_expired: self.on_link_expired,
+            on_audit: self.on_audit,
+            on_error: self.on_error,
+            on_login: self.on_login,
+            on_save_character: self.on_save_character,
+            on_snapshot: self.on_snapshot,
+            has_key: self.has_key,
+            door_skill: self.door_skill,
+        })
+    }
+}
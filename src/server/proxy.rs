@@ -0,0 +1,95 @@
+//! This module provides the [PROXY protocol](https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt), versions 1 and 2,
+//! which load balancers such as HAProxy use to pass on the real client address.
+//! Only connections from [`Config::trusted_proxies`](super::Config::trusted_proxies) are expected to send a header
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Starts every version 2 header
+pub const SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+/// The longest version 1 header, including `CR LF`
+const V1_MAX: usize = 107;
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, format!("invalid PROXY header: {}", reason))
+}
+
+/// Read a PROXY header from the start of `stream`, consuming exactly the header.
+/// Returns the client address, or `None` if the proxy didn't give one (e.g. its own health checks)
+pub async fn read_header<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<Option<SocketAddr>> {
+    let mut start = [0; 5];
+    stream.read_exact(&mut start).await?;
+    if &start == b"PROXY" {
+        let mut line = start.to_vec();
+        while !line.ends_with(b"\r\n") {
+            if line.len() >= V1_MAX {
+                return Err(invalid("line too long"));
+            }
+            line.push(stream.read_u8().await?);
+        }
+        return parse_v1(&line);
+    }
+    if start != SIGNATURE[..5] {
+        return Err(invalid("missing signature"));
+    }
+    let mut header = [0; 16];
+    header[..5].copy_from_slice(&start);
+    stream.read_exact(&mut header[5..]).await?;
+    let mut body = vec![0; usize::from(u16::from_be_bytes([header[14], header[15]]))];
+    stream.read_exact(&mut body).await?;
+    parse_v2(&header, &body)
+}
+
+/// Parse a complete version 1 line, e.g. `PROXY TCP4 192.0.2.1 198.51.100.1 56324 4000\r\n`
+pub fn parse_v1(line: &[u8]) -> io::Result<Option<SocketAddr>> {
+    let line = std::str::from_utf8(line).map_err(|_| invalid("not ASCII"))?;
+    let line = line.strip_suffix("\r\n").ok_or_else(|| invalid("missing CR LF"))?;
+    let mut fields = line.split(' ');
+    if fields.next() != Some("PROXY") {
+        return Err(invalid("missing PROXY"));
+    }
+    let family = fields.next().ok_or_else(|| invalid("missing protocol"))?;
+    if family == "UNKNOWN" {
+        return Ok(None);
+    }
+    let fields: Vec<_> = fields.collect();
+    let [source, _, port, _] = fields[..] else {
+        return Err(invalid("wrong number of fields"));
+    };
+    let ip: IpAddr = match family {
+        "TCP4" => source.parse::<Ipv4Addr>().map_err(|_| invalid("bad address"))?.into(),
+        "TCP6" => source.parse::<Ipv6Addr>().map_err(|_| invalid("bad address"))?.into(),
+        _ => return Err(invalid("unknown protocol")),
+    };
+    Ok(Some(SocketAddr::new(ip, port.parse().map_err(|_| invalid("bad port"))?)))
+}
+
+/// Parse a version 2 header: the 16 fixed bytes, then the address block (and any TLVs, which are ignored)
+pub fn parse_v2(header: &[u8; 16], body: &[u8]) -> io::Result<Option<SocketAddr>> {
+    if header[..12] != SIGNATURE {
+        return Err(invalid("missing signature"));
+    }
+    if header[12] >> 4 != 2 {
+        return Err(invalid("unsupported version"));
+    }
+    match header[12] & 0xf {
+        // LOCAL: the proxy talking for itself
+        0 => return Ok(None),
+        1 => {}
+        _ => return Err(invalid("unknown command")),
+    }
+    let addr = match header[13] >> 4 {
+        1 if body.len() >= 12 => {
+            let ip: [u8; 4] = body[..4].try_into().unwrap();
+            SocketAddr::new(Ipv4Addr::from(ip).into(), u16::from_be_bytes([body[8], body[9]]))
+        }
+        2 if body.len() >= 36 => {
+            let ip: [u8; 16] = body[..16].try_into().unwrap();
+            SocketAddr::new(Ipv6Addr::from(ip).into(), u16::from_be_bytes([body[32], body[33]]))
+        }
+        1 | 2 => return Err(invalid("address block too short")),
+        // Unix sockets and unspecified families carry no usable address
+        _ => return Ok(None),
+    };
+    Ok(Some(addr))
+}
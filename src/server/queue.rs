@@ -0,0 +1,156 @@
+//! This module provides the bounded output queue behind each connection, so a client that stops reading
+//! can't make the server's memory grow without limit
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// What happens to a client whose queued output goes over the high watermark
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Drop text and MSDP updates until the queue drains below the low watermark, then tell the player.
+    /// Prompts and the server's own out-of-band replies are always kept
+    #[default]
+    Drop,
+    /// Disconnect the client
+    Disconnect,
+}
+
+/// Output buffering limits for each connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputLimits {
+    /// Bytes queued for a connection before [`Overflow`] applies
+    pub high: usize,
+    /// Bytes the queue has to drain to before a dropping connection gets text again
+    pub low: usize,
+    pub overflow: Overflow,
+    /// A client that takes longer than this to accept one write is disconnected
+    pub write_timeout: Duration,
+    /// Output is coalesced and written at most this often. Zero writes as soon as nothing else is waiting
+    pub flush_interval: Duration,
+}
+
+impl Default for OutputLimits {
+    fn default() -> Self {
+        OutputLimits {
+            high: 256 * 1024,
+            low: 64 * 1024,
+            overflow: Overflow::Drop,
+            write_timeout: Duration::from_secs(30),
+            flush_interval: Duration::ZERO,
+        }
+    }
+}
+
+/// Output waiting for one connection, shared between its [`Handle`](super::Handle) and its task
+#[derive(Debug)]
+pub struct Queue {
+    limits: OutputLimits,
+    depth: AtomicUsize,
+    in_flight: AtomicUsize,
+    dropping: AtomicBool,
+    overflowed: AtomicBool,
+    /// Dropped since the player was last told
+    unreported: AtomicUsize,
+    dropped: AtomicU64,
+}
+
+impl Queue {
+    pub fn new(limits: OutputLimits) -> Self {
+        Queue {
+            limits,
+            depth: AtomicUsize::new(0),
+            in_flight: AtomicUsize::new(0),
+            dropping: AtomicBool::new(false),
+            overflowed: AtomicBool::new(false),
+            unreported: AtomicUsize::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    pub fn limits(&self) -> &OutputLimits {
+        &self.limits
+    }
+
+    /// Bytes waiting to be sent, including a write in progress
+    pub fn depth(&self) -> usize {
+        self.depth.load(Ordering::Relaxed) + self.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// Whether text is being dropped because the client fell behind
+    pub fn is_dropping(&self) -> bool {
+        self.dropping.load(Ordering::Relaxed)
+    }
+
+    /// Bytes dropped over the connection's lifetime
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Account for `len` bytes of text about to be queued. Returns `false` if they should be dropped instead
+    pub(crate) fn push(&self, len: usize) -> bool {
+        let depth = self.depth();
+        let over = match self.is_dropping() {
+            true => depth > self.limits.low,
+            false => depth > 0 && depth + len > self.limits.high,
+        };
+        if !over {
+            self.dropping.store(false, Ordering::Relaxed);
+            self.depth.fetch_add(len, Ordering::Relaxed);
+            return true;
+        }
+        match self.limits.overflow {
+            Overflow::Drop => {
+                self.dropping.store(true, Ordering::Relaxed);
+                self.record_dropped(len);
+            }
+            Overflow::Disconnect => self.overflowed.store(true, Ordering::Relaxed),
+        }
+        false
+    }
+
+    /// `len` bytes of queued text were taken by the connection's task
+    pub(crate) fn pop(&self, len: usize) {
+        if self.depth.fetch_sub(len, Ordering::Relaxed) - len <= self.limits.low {
+            self.dropping.store(false, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn set_in_flight(&self, len: usize) {
+        self.in_flight.store(len, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_dropped(&self, len: usize) {
+        if len > 0 {
+            self.unreported.fetch_add(len, Ordering::Relaxed);
+            self.dropped.fetch_add(len as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Bytes dropped since the last call, once the queue has recovered
+    pub(crate) fn take_unreported(&self) -> usize {
+        match self.is_dropping() {
+            true => 0,
+            false => self.unreported.swap(0, Ordering::Relaxed),
+        }
+    }
+
+    /// Whether the client went over the high watermark under [`Overflow::Disconnect`]
+    pub(crate) fn is_overflowed(&self) -> bool {
+        self.overflowed.load(Ordering::Relaxed)
+    }
+}
+
+/// Output queue metrics across every connected session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QueueStats {
+    pub sessions: usize,
+    /// Bytes waiting across all sessions
+    pub queued: usize,
+    /// The longest single queue, in bytes
+    pub deepest: usize,
+    /// Sessions currently dropping text
+    pub dropping: usize,
+    /// Bytes dropped by the sessions still connected
+    pub dropped: u64,
+    /// Clients disconnected for being too slow since the server started
+    pub slow_disconnects: u64,
+}
@@ -0,0 +1,191 @@
+use super::queue::{Queue, QueueStats};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::Notify;
+
+/// A unique, never reused identifier for a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SessionId(pub u64);
+
+impl Display for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Something sent to a connection's task from elsewhere in the server
+#[derive(Debug)]
+pub enum Message {
+    /// Text to write to the player
+    Text(String),
+    /// Write this text, flush and disconnect
+    Close(String),
+    /// Flush, hand over the socket and session state, and wait for [`Message::Resume`]
+    #[cfg(unix)]
+    Copyover(tokio::sync::oneshot::Sender<super::copyover::Detached>),
+    /// The copyover failed, carry on as before
+    #[cfg(unix)]
+    Resume,
+    /// Start or stop copying output to a snooper, see [`snoop`](super::snoop)
+    Snoop(bool),
+    /// Send an MSDP variable, if the client asked for MSDP
+    Msdp(String, serde_json::Value),
+}
+
+impl Message {
+    /// Bytes it takes up in the connection's [`Queue`], for those that count against it
+    pub(crate) fn queued_len(&self) -> Option<usize> {
+        match self {
+            Message::Text(text) => Some(text.len()),
+            Message::Msdp(name, value) => Some(name.len() + value.to_string().len()),
+            _ => None,
+        }
+    }
+}
+
+/// How to reach a connected session
+#[derive(Debug, Clone)]
+pub struct Handle {
+    pub addr: SocketAddr,
+    /// Whether the connection is encrypted
+    pub secure: bool,
+    sender: UnboundedSender<Message>,
+    queue: Arc<Queue>,
+}
+
+impl Handle {
+    pub fn new(addr: SocketAddr, secure: bool, sender: UnboundedSender<Message>, queue: Arc<Queue>) -> Self {
+        Handle { addr, secure, sender, queue }
+    }
+
+    /// Returns `false` if the connection is already gone.
+    /// Text and MSDP updates may be dropped without error if the client isn't keeping up,
+    /// see [`Overflow`](super::queue::Overflow)
+    pub fn send(&self, message: Message) -> bool {
+        if let Some(len) = message.queued_len() {
+            if !self.queue.push(len) {
+                return !self.sender.is_closed();
+            }
+        }
+        self.sender.send(message).is_ok()
+    }
+
+    /// The connection's output queue
+    pub fn queue(&self) -> &Queue {
+        &self.queue
+    }
+}
+
+/// Every session currently connected to a server
+#[derive(Debug, Default)]
+pub struct Registry {
+    sessions: Mutex<HashMap<SessionId, Handle>>,
+    removed: Notify,
+    slow_disconnects: AtomicU64,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry::default()
+    }
+
+    pub fn insert(&self, id: SessionId, handle: Handle) {
+        self.sessions.lock().unwrap().insert(id, handle);
+    }
+
+    pub fn remove(&self, id: SessionId) -> Option<Handle> {
+        let handle = self.sessions.lock().unwrap().remove(&id);
+        self.removed.notify_waiters();
+        handle
+    }
+
+    /// Wait until every session has disconnected
+    pub async fn wait_empty(&self) {
+        loop {
+            let removed = self.removed.notified();
+            tokio::pin!(removed);
+            removed.as_mut().enable();
+            if self.is_empty() {
+                return;
+            }
+            removed.await;
+        }
+    }
+
+    pub fn get(&self, id: SessionId) -> Option<Handle> {
+        self.sessions.lock().unwrap().get(&id).cloned()
+    }
+
+    pub fn ids(&self) -> Vec<SessionId> {
+        let mut ids: Vec<_> = self.sessions.lock().unwrap().keys().copied().collect();
+        ids.sort();
+        ids
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Send text to one session. Returns `false` if it isn't connected
+    pub fn send(&self, id: SessionId, text: &str) -> bool {
+        self.get(id).is_some_and(|h| h.send(Message::Text(text.to_string())))
+    }
+
+    /// Send an MSDP variable to one session. Returns `false` if it isn't connected
+    pub fn send_msdp(&self, id: SessionId, name: &str, value: serde_json::Value) -> bool {
+        self.get(id).is_some_and(|h| h.send(Message::Msdp(name.to_string(), value)))
+    }
+
+    /// Send text to every session
+    pub fn broadcast(&self, text: &str) {
+        self.broadcast_except(None, text);
+    }
+
+    /// Send text to every session but `except`
+    pub fn broadcast_except(&self, except: Option<SessionId>, text: &str) {
+        for (id, handle) in self.sessions.lock().unwrap().iter() {
+            if Some(*id) != except {
+                handle.send(Message::Text(text.to_string()));
+            }
+        }
+    }
+
+    /// Disconnect a session after sending it `reason`
+    pub fn kick(&self, id: SessionId, reason: &str) -> bool {
+        self.get(id).is_some_and(|h| h.send(Message::Close(reason.to_string())))
+    }
+
+    /// Output queue depth across every session
+    pub fn queue_stats(&self) -> QueueStats {
+        let mut stats = QueueStats { slow_disconnects: self.slow_disconnects.load(Ordering::Relaxed), ..Default::default() };
+        for handle in self.sessions.lock().unwrap().values() {
+            let depth = handle.queue.depth();
+            stats.sessions += 1;
+            stats.queued += depth;
+            stats.deepest = stats.deepest.max(depth);
+            stats.dropping += usize::from(handle.queue.is_dropping());
+            stats.dropped += handle.queue.dropped();
+        }
+        stats
+    }
+
+    pub(crate) fn record_slow_disconnect(&self) {
+        self.slow_disconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Disconnect every session after sending it `reason`
+    pub fn close_all(&self, reason: &str) {
+        for handle in self.sessions.lock().unwrap().values() {
+            handle.send(Message::Close(reason.to_string()));
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! This module keeps the world populated: every area resets once when the server starts, then again each time
+//! it ages past its [`reset_minutes`](crate::rooms::Area::reset_minutes), checked every
+//! [`Config::area_tick`](super::Config::area_tick). What the resets load is up to the game's
+//! [`Populate`](crate::rooms::Populate), see [`Builder::populate`](super::Builder::populate)
+use super::Server;
+use crate::rooms::area::ResetReport;
+use crate::rooms::Area;
+use std::sync::Arc;
+use tokio::time::MissedTickBehavior;
+
+/// Reset every area, then the aged ones on each tick, until the task is dropped at shutdown
+pub(crate) async fn schedule(server: Arc<Server>) {
+    for area in server.areas.all() {
+        server.run_resets(&area);
+    }
+    let Some(period) = server.config.area_tick else { return };
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        server.reset_due_areas();
+    }
+}
+
+impl Server {
+    /// Whether a player's character is in one of the area's rooms
+    pub fn area_occupied(&self, area: &Area) -> bool {
+        self.links.entities().into_iter().filter_map(|e| self.world.location(e)).any(|room| area.contains(room))
+    }
+
+    /// Reset the area called `name` now, whatever its age. `None` if there's no such area
+    pub fn reset_area(&self, name: &str) -> Option<ResetReport> {
+        Some(self.run_resets(&self.areas.get(name)?))
+    }
+
+    /// Reset the areas that have aged enough, leaving occupied ones that wait for players to leave
+    pub fn reset_due_areas(&self) -> Vec<ResetReport> {
+        let due = self.areas.due(|area| self.area_occupied(area));
+        due.iter().map(|area| self.run_resets(area)).collect()
+    }
+
+    fn run_resets(&self, area: &Area) -> ResetReport {
+        let report = match &self.populate {
+            Some(populate) => area.run_resets(&self.world, populate.as_ref()),
+            None => ResetReport { area: area.name.clone(), loaded: 0, failed: Vec::new() },
+        };
+        self.areas.mark_reset(report.clone());
+        report
+    }
+}
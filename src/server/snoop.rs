@@ -0,0 +1,110 @@
+//! This module provides [`Snoops`], which tracks administrators watching other sessions.
+//! A snooped session copies everything written to it to its snooper, each line marked with `% `
+use super::SessionId;
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::sync::Mutex;
+
+/// Why a snoop was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnoopError {
+    /// Sessions can't snoop themselves
+    Yourself,
+    /// The target isn't connected
+    NotConnected,
+    /// Someone else is already snooping the target
+    AlreadySnooped(SessionId),
+    /// The target is snooping someone, or the snooper is being snooped.
+    /// Chains would copy output from session to session, and loops forever
+    Nested,
+}
+
+impl Display for SnoopError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnoopError::Yourself => f.write_str("you can't snoop yourself"),
+            SnoopError::NotConnected => f.write_str("nobody is connected as that session"),
+            SnoopError::AlreadySnooped(by) => write!(f, "already snooped by {}", by),
+            SnoopError::Nested => f.write_str("snoops can't be nested"),
+        }
+    }
+}
+
+impl std::error::Error for SnoopError {}
+
+/// Who is snooping whom
+#[derive(Debug, Default)]
+pub struct Snoops {
+    /// Target to snooper
+    snoops: Mutex<HashMap<SessionId, SessionId>>,
+}
+
+impl Snoops {
+    pub fn new() -> Self {
+        Snoops::default()
+    }
+
+    /// The session snooping `target`
+    pub fn snooper(&self, target: SessionId) -> Option<SessionId> {
+        self.snoops.lock().unwrap().get(&target).copied()
+    }
+
+    /// The session `snooper` is watching
+    pub fn target(&self, snooper: SessionId) -> Option<SessionId> {
+        self.snoops.lock().unwrap().iter().find(|(_, s)| **s == snooper).map(|(t, _)| *t)
+    }
+
+    /// Start `snooper` watching `target`. Returns the target it was watching before, which it stops watching
+    pub(crate) fn start(&self, snooper: SessionId, target: SessionId) -> Result<Option<SessionId>, SnoopError> {
+        if snooper == target {
+            return Err(SnoopError::Yourself);
+        }
+        let mut snoops = self.snoops.lock().unwrap();
+        match snoops.get(&target) {
+            Some(&by) if by == snooper => return Ok(None),
+            Some(&by) => return Err(SnoopError::AlreadySnooped(by)),
+            None => {}
+        }
+        if snoops.contains_key(&snooper) || snoops.values().any(|&s| s == target) {
+            return Err(SnoopError::Nested);
+        }
+        let previous = snoops.iter().find(|(_, s)| **s == snooper).map(|(t, _)| *t);
+        if let Some(previous) = previous {
+            snoops.remove(&previous);
+        }
+        snoops.insert(target, snooper);
+        Ok(previous)
+    }
+
+    /// Stop `snooper` watching anyone. Returns who it was watching
+    pub(crate) fn stop(&self, snooper: SessionId) -> Option<SessionId> {
+        let mut snoops = self.snoops.lock().unwrap();
+        let target = snoops.iter().find(|(_, s)| **s == snooper).map(|(t, _)| *t)?;
+        snoops.remove(&target);
+        Some(target)
+    }
+
+    /// Forget session `id` entirely when it disconnects. Returns the session snooping it and the one it was snooping
+    pub(crate) fn remove(&self, id: SessionId) -> (Option<SessionId>, Option<SessionId>) {
+        let mut snoops = self.snoops.lock().unwrap();
+        let snooper = snoops.remove(&id);
+        let target = snoops.iter().find(|(_, s)| **s == id).map(|(t, _)| *t);
+        if let Some(target) = target {
+            snoops.remove(&target);
+        }
+        (snooper, target)
+    }
+}
+
+/// Mark every line of a snooped session's output for its snooper
+pub(crate) fn mirror(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 8);
+    for line in text.split_inclusive('\n') {
+        out.push_str("% ");
+        out.push_str(line);
+    }
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,303 @@
+//! This module provides an SSH front end, so players can connect with `ssh game@host`.
+//! Each shell channel becomes an ordinary session: window changes arrive as NAWS and telnet
+//! negotiation is stripped from the output, so the game sees exactly what a telnet client would give it
+use super::transport::Transport;
+use crate::charset::Charset;
+use crate::telnet::{self, option, Event, Parser};
+use russh::server::{Auth, Handle, Msg, Session};
+use russh::{Channel, ChannelId, ChannelMsg, Disconnect, MethodKind, MethodSet};
+use std::fmt::{self, Debug};
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::oneshot;
+
+/// Checks a user name and password
+pub type PasswordCheck = Arc<dyn Fn(&str, &str) -> bool + Send + Sync>;
+
+/// Who may open an SSH session
+#[derive(Clone, Default)]
+pub enum SshAuth {
+    /// Anyone, without a password. The game's own login runs as it does for telnet
+    #[default]
+    Open,
+    /// Only users whose password passes the check
+    Password(PasswordCheck),
+}
+
+impl Debug for SshAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SshAuth::Open => f.write_str("Open"),
+            SshAuth::Password(_) => f.write_str("Password(..)"),
+        }
+    }
+}
+
+/// Where to listen for SSH connections
+#[derive(Debug, Clone)]
+pub struct SshConfig {
+    pub addr: String,
+    /// The server's private key in OpenSSH format, e.g. made with `ssh-keygen -t ed25519 -N '' -f host_key`
+    pub host_key: PathBuf,
+    pub auth: SshAuth,
+}
+
+/// Load the host key and build the protocol settings for a listener
+pub fn server_config(config: &SshConfig) -> io::Result<Arc<russh::server::Config>> {
+    let key = russh::keys::load_secret_key(&config.host_key, None)
+        .map_err(|e| io::Error::new(ErrorKind::InvalidData, format!("{}: {}", config.host_key.display(), e)))?;
+    let methods = match config.auth {
+        SshAuth::Open => MethodSet::from(&[MethodKind::None, MethodKind::Password][..]),
+        SshAuth::Password(_) => MethodSet::from(&[MethodKind::Password][..]),
+    };
+    Ok(Arc::new(russh::server::Config {
+        methods,
+        keys: vec![key],
+        // Players idle for a long time; keepalives notice dead connections instead
+        inactivity_timeout: None,
+        keepalive_interval: Some(Duration::from_secs(60)),
+        nodelay: true,
+        ..Default::default()
+    }))
+}
+
+/// The shell channel a client opened, with the terminal size it asked for
+struct Shell {
+    channel: Channel<Msg>,
+    size: (u32, u32),
+}
+
+struct Handler {
+    auth: SshAuth,
+    channel: Option<Channel<Msg>>,
+    size: (u32, u32),
+    shell: Option<oneshot::Sender<Shell>>,
+}
+
+fn password_only() -> Auth {
+    Auth::Reject { proceed_with_methods: Some(MethodSet::from(&[MethodKind::Password][..])), partial_success: false }
+}
+
+impl russh::server::Handler for Handler {
+    type Error = russh::Error;
+
+    async fn auth_none(&mut self, _user: &str) -> Result<Auth, Self::Error> {
+        Ok(match self.auth {
+            SshAuth::Open => Auth::Accept,
+            SshAuth::Password(_) => password_only(),
+        })
+    }
+
+    async fn auth_password(&mut self, user: &str, password: &str) -> Result<Auth, Self::Error> {
+        Ok(match &self.auth {
+            SshAuth::Open => Auth::Accept,
+            SshAuth::Password(check) if check(user, password) => Auth::Accept,
+            // Let the player retry a mistyped password, up to the server's `max_auth_attempts`
+            SshAuth::Password(_) => password_only(),
+        })
+    }
+
+    async fn channel_open_session(
+        &mut self,
+        channel: Channel<Msg>,
+        reply: russh::server::ChannelOpenHandle,
+        _session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        // One game session per connection
+        if self.channel.is_none() && self.shell.is_some() {
+            self.channel = Some(channel);
+            reply.accept().await;
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn pty_request(
+        &mut self,
+        channel: ChannelId,
+        _term: &str,
+        width: u32,
+        height: u32,
+        _pix_width: u32,
+        _pix_height: u32,
+        _modes: &[(russh::Pty, u32)],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.size = (width, height);
+        session.channel_success(channel)
+    }
+
+    async fn shell_request(&mut self, channel: ChannelId, session: &mut Session) -> Result<(), Self::Error> {
+        match (self.channel.take(), self.shell.take()) {
+            (Some(c), Some(shell)) if c.id() == channel => {
+                session.channel_success(channel)?;
+                let _ = shell.send(Shell { channel: c, size: self.size });
+                Ok(())
+            }
+            _ => session.channel_failure(channel),
+        }
+    }
+}
+
+/// Run the SSH handshake on `stream` until the client opens a shell
+pub(crate) async fn accept(stream: TcpStream, config: Arc<russh::server::Config>, auth: SshAuth) -> io::Result<Ssh> {
+    let (sender, shell) = oneshot::channel();
+    let handler = Handler { auth, channel: None, size: (80, 24), shell: Some(sender) };
+    let running = russh::server::run_stream(config, stream, handler)
+        .await
+        .map_err(|e| io::Error::new(ErrorKind::InvalidData, e.to_string()))?;
+    let handle = running.handle();
+    let Shell { channel, size } =
+        shell.await.map_err(|_| io::Error::new(ErrorKind::UnexpectedEof, "SSH client left before opening a shell"))?;
+    Ok(Ssh {
+        echo_writer: Box::new(channel.make_writer()),
+        channel,
+        handle,
+        size,
+        output: Parser::new(),
+        echo: true,
+        echoed: Vec::new(),
+        replies: Vec::new(),
+        line: 0,
+        after_cr: false,
+    })
+}
+
+/// The telnet bytes for a window size
+fn naws(width: u32, height: u32) -> Vec<u8> {
+    let clamp = |n: u32| u16::try_from(n).unwrap_or(u16::MAX).to_be_bytes();
+    telnet::subnegotiation(option::NAWS, &[clamp(width), clamp(height)].concat())
+}
+
+/// An SSH shell channel carrying a session
+pub(crate) struct Ssh {
+    channel: Channel<Msg>,
+    handle: Handle,
+    size: (u32, u32),
+    output: Parser,
+    /// Whether typed characters are echoed back; the game turns it off with `IAC WILL ECHO` for passwords
+    echo: bool,
+    echoed: Vec<u8>,
+    /// Sends `echoed`, picking up where it left off if a send was cut short
+    echo_writer: Box<dyn AsyncWrite + Send + Unpin>,
+    /// Answers to the game's negotiation, as a telnet client would send them
+    replies: Vec<u8>,
+    /// Characters typed on the current line, so backspace can't erase the prompt
+    line: usize,
+    after_cr: bool,
+}
+
+impl Ssh {
+    /// A terminal expects the remote end to echo, which telnet clients do themselves
+    fn echo_input(&mut self, data: &[u8]) {
+        for &b in data {
+            let after_cr = std::mem::take(&mut self.after_cr);
+            match b {
+                b'\n' | 0 if after_cr => {}
+                b'\r' | b'\n' => {
+                    self.after_cr = b == b'\r';
+                    self.line = 0;
+                    // Like a telnet client, leave the line feed after a hidden password to the game
+                    if self.echo {
+                        self.echoed.extend_from_slice(b"\r\n");
+                    }
+                }
+                0x08 | 0x7f if self.line > 0 => {
+                    self.line -= 1;
+                    if self.echo {
+                        self.echoed.extend_from_slice(b"\x08 \x08");
+                    }
+                }
+                _ if b < 0x20 || b == 0x7f => {}
+                _ => {
+                    // Count characters, not UTF-8 continuation bytes
+                    if b & 0xc0 != 0x80 {
+                        self.line += 1;
+                    }
+                    if self.echo {
+                        self.echoed.push(b);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Send what was echoed. Cancel safe: bytes only leave `echoed` once they are sent
+    async fn flush_echo(&mut self) -> io::Result<()> {
+        while !self.echoed.is_empty() {
+            let sent = self.echo_writer.write(&self.echoed).await?;
+            self.echoed.drain(..sent);
+        }
+        Ok(())
+    }
+}
+
+impl Transport for Ssh {
+    async fn read(&mut self) -> io::Result<Option<Vec<u8>>> {
+        self.flush_echo().await?;
+        if !self.replies.is_empty() {
+            return Ok(Some(std::mem::take(&mut self.replies)));
+        }
+        loop {
+            match self.channel.wait().await {
+                Some(ChannelMsg::Data { data }) => {
+                    self.echo_input(&data);
+                    return Ok(Some(telnet::escape(&data)));
+                }
+                Some(ChannelMsg::WindowChange { col_width, row_height, .. }) => {
+                    return Ok(Some(naws(col_width, row_height)));
+                }
+                Some(ChannelMsg::Eof | ChannelMsg::Close) | None => return Ok(None),
+                Some(_) => {}
+            }
+        }
+    }
+
+    async fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.flush_echo().await?;
+        let mut out = Vec::new();
+        for event in self.output.parse(data) {
+            match event {
+                Event::Data(bytes) => out.extend(bytes),
+                Event::Will(option::ECHO) => {
+                    self.echo = false;
+                    self.replies.extend_from_slice(&[telnet::IAC, telnet::DO, option::ECHO]);
+                }
+                Event::Wont(option::ECHO) => {
+                    self.echo = true;
+                    self.replies.extend_from_slice(&[telnet::IAC, telnet::DONT, option::ECHO]);
+                }
+                _ => {}
+            }
+        }
+        if out.is_empty() {
+            return Ok(());
+        }
+        self.channel.data_bytes(out).await.map_err(|e| io::Error::new(ErrorKind::BrokenPipe, e.to_string()))
+    }
+
+    async fn close(&mut self) -> io::Result<()> {
+        let _ = self.channel.eof().await;
+        let _ = self.channel.close().await;
+        let _ = self.handle.disconnect(Disconnect::ByApplication, String::new(), String::new()).await;
+        Ok(())
+    }
+
+    fn preamble(&self) -> Vec<u8> {
+        let mut preamble = vec![telnet::IAC, telnet::WILL, option::NAWS];
+        preamble.extend(naws(self.size.0, self.size.1));
+        preamble
+    }
+
+    fn charset(&self) -> Option<Charset> {
+        Some(Charset::Utf8)
+    }
+
+    fn secure(&self) -> bool {
+        true
+    }
+}
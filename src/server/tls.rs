@@ -0,0 +1,84 @@
+//! TLS for telnet, using [rustls](https://docs.rs/rustls). Certificates are read from PEM files and
+//! reloaded when the process receives `SIGHUP`, without dropping connected players
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::ServerConfig;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tokio_rustls::TlsAcceptor;
+
+/// Where to listen for TLS connections and which certificate to present
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    pub addr: String,
+    /// PEM file with the certificate chain, leaf first
+    pub cert: PathBuf,
+    /// PEM file with the private key
+    pub key: PathBuf,
+}
+
+/// The certificate currently presented to new connections
+#[derive(Debug)]
+pub struct Certificates {
+    cert: PathBuf,
+    key: PathBuf,
+    current: RwLock<Arc<ServerConfig>>,
+}
+
+fn invalid(e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+fn load(cert: &Path, key: &Path) -> io::Result<Arc<ServerConfig>> {
+    let chain = CertificateDer::pem_file_iter(cert)
+        .map_err(invalid)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(invalid)?;
+    let key = PrivateKeyDer::from_pem_file(key).map_err(invalid)?;
+    let config = ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(invalid)?
+        .with_no_client_auth()
+        .with_single_cert(chain, key)
+        .map_err(invalid)?;
+    Ok(Arc::new(config))
+}
+
+impl Certificates {
+    pub fn load(cert: &Path, key: &Path) -> io::Result<Self> {
+        Ok(Certificates {
+            cert: cert.to_owned(),
+            key: key.to_owned(),
+            current: RwLock::new(load(cert, key)?),
+        })
+    }
+
+    /// Re-read the PEM files. On error the previous certificate stays in use
+    pub fn reload(&self) -> io::Result<()> {
+        let config = load(&self.cert, &self.key)?;
+        *self.current.write().unwrap() = config;
+        Ok(())
+    }
+
+    pub(crate) fn acceptor(&self) -> TlsAcceptor {
+        TlsAcceptor::from(self.current.read().unwrap().clone())
+    }
+}
+
+/// Reload `certificates` every time the process gets `SIGHUP`. Failures go to the
+/// [`on_error`](super::Builder::on_error) hook
+#[cfg(unix)]
+pub(crate) async fn reload_on_hangup(server: Arc<super::Server>, certificates: Vec<Arc<Certificates>>) {
+    use tokio::signal::unix::{signal, SignalKind};
+    let Ok(mut hangup) = signal(SignalKind::hangup()) else {
+        return;
+    };
+    while hangup.recv().await.is_some() {
+        for certificates in &certificates {
+            if let Err(e) = certificates.reload() {
+                server.report_error(&format!("Couldn't reload TLS certificate {}: {}", certificates.cert.display(), e));
+            }
+        }
+    }
+}
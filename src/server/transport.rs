@@ -0,0 +1,106 @@
+use crate::charset::Charset;
+use std::future::Future;
+use std::io;
+#[cfg(unix)]
+use std::os::fd::{AsFd, BorrowedFd, OwnedFd};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Carries telnet bytes between a [`Session`](crate::session::Session) and a client, whatever the wire format
+pub(crate) trait Transport: Send {
+    /// Read the next chunk of telnet bytes, `None` once the client has gone. Must be cancel safe
+    fn read(&mut self) -> impl Future<Output = io::Result<Option<Vec<u8>>>> + Send;
+
+    fn write(&mut self, data: &[u8]) -> impl Future<Output = io::Result<()>> + Send;
+
+    /// Flush and close the connection
+    fn close(&mut self) -> impl Future<Output = io::Result<()>> + Send;
+
+    /// Telnet bytes fed to the session before any input, standing in for negotiation the client can't do itself
+    fn preamble(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// A charset the session must use regardless of configuration
+    fn charset(&self) -> Option<Charset> {
+        None
+    }
+
+    /// Whether the connection is encrypted
+    fn secure(&self) -> bool {
+        false
+    }
+
+    /// A duplicate of the socket, for handing the connection to a new process.
+    /// `None` if the connection has state outside the socket, like TLS or WebSocket framing
+    #[cfg(unix)]
+    fn duplicate_fd(&self) -> Option<OwnedFd> {
+        None
+    }
+}
+
+/// A byte stream that may be backed by a plain socket
+pub(crate) trait RawSocket {
+    /// The socket, if all of the connection's state lives in it
+    #[cfg(unix)]
+    fn raw_socket(&self) -> Option<BorrowedFd<'_>> {
+        None
+    }
+}
+
+impl RawSocket for TcpStream {
+    #[cfg(unix)]
+    fn raw_socket(&self) -> Option<BorrowedFd<'_>> {
+        Some(self.as_fd())
+    }
+}
+
+#[cfg(feature = "tls")]
+impl RawSocket for tokio_rustls::server::TlsStream<TcpStream> {}
+
+/// Raw telnet over a byte stream, such as a TCP socket
+pub(crate) struct Stream<S> {
+    inner: S,
+    buf: Vec<u8>,
+    secure: bool,
+}
+
+impl<S> Stream<S> {
+    pub(crate) fn new(inner: S) -> Self {
+        Stream { inner, buf: vec![0; 4096], secure: false }
+    }
+
+    /// A stream that is encrypted, e.g. by TLS
+    #[cfg_attr(not(feature = "tls"), allow(dead_code))]
+    pub(crate) fn secure(inner: S) -> Self {
+        Stream { secure: true, ..Stream::new(inner) }
+    }
+}
+
+impl<S> Transport for Stream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + RawSocket,
+{
+    async fn read(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let n = self.inner.read(&mut self.buf).await?;
+        Ok((n > 0).then(|| self.buf[..n].to_vec()))
+    }
+
+    async fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.inner.write_all(data).await
+    }
+
+    async fn close(&mut self) -> io::Result<()> {
+        self.inner.flush().await?;
+        self.inner.shutdown().await
+    }
+
+    fn secure(&self) -> bool {
+        self.secure
+    }
+
+    #[cfg(unix)]
+    fn duplicate_fd(&self) -> Option<OwnedFd> {
+        self.inner.raw_socket()?.try_clone_to_owned().ok()
+    }
+}
@@ -0,0 +1,215 @@
+//! WebSocket clients share sessions, commands and the registry with telnet. The mode is picked with the
+//! `Sec-WebSocket-Protocol` header:
+//!
+//! * `telnet` (or no subprotocol): binary frames carry raw telnet bytes, exactly as over TCP
+//! * `json`: text frames carry one [`Frame`] each, with MSDP transcoded to JSON objects
+//!
+//! Any WebSocket client can be used to try it locally, e.g. `websocat --protocol json ws://127.0.0.1:4001`
+//! and then typing `{"type": "text", "data": "look"}`
+use super::transport::Transport;
+use crate::charset::Charset;
+use crate::msdp;
+use crate::telnet::{self, option, Event, Parser};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::io;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::{Error as WsError, Message as WsMessage};
+use tokio_tungstenite::WebSocketStream;
+
+/// Subprotocol for raw telnet over binary frames
+pub const PROTOCOL_TELNET: &str = "telnet";
+/// Subprotocol for structured JSON frames
+pub const PROTOCOL_JSON: &str = "json";
+
+/// One message in `json` mode, in either direction
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Frame {
+    /// Game text. From the client, one command line
+    Text { data: String },
+    /// Text the client should treat as a prompt
+    Prompt { data: String },
+    /// MSDP variables, keyed by name
+    Msdp { data: Map<String, Value> },
+    Gmcp {
+        package: String,
+        #[serde(default)]
+        data: Value,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Telnet,
+    Json,
+}
+
+pub(crate) struct WebSocket {
+    ws: WebSocketStream<TcpStream>,
+    mode: Mode,
+    output: Parser,
+}
+
+/// Perform the WebSocket handshake, picking the mode from the requested subprotocols
+pub(crate) async fn accept(stream: TcpStream) -> io::Result<WebSocket> {
+    let mut mode = Mode::Telnet;
+    // The error type is dictated by tungstenite's callback signature
+    #[allow(clippy::result_large_err)]
+    let callback = |request: &Request, mut response: Response| {
+        let requested = request
+            .headers()
+            .get_all("Sec-WebSocket-Protocol")
+            .iter()
+            .filter_map(|h| h.to_str().ok())
+            .flat_map(|h| h.split(','))
+            .map(str::trim)
+            .find_map(|p| [PROTOCOL_JSON, PROTOCOL_TELNET].into_iter().find(|&known| known == p));
+        if let Some(protocol) = requested {
+            if protocol == PROTOCOL_JSON {
+                mode = Mode::Json;
+            }
+            response.headers_mut().insert("Sec-WebSocket-Protocol", HeaderValue::from_static(protocol));
+        }
+        Ok(response)
+    };
+    let ws = tokio_tungstenite::accept_hdr_async(stream, callback).await.map_err(io::Error::other)?;
+    Ok(WebSocket { ws, mode, output: Parser::new() })
+}
+
+impl WebSocket {
+    /// Turn a frame from the client into the telnet bytes a session expects
+    fn frame_to_telnet(frame: Frame) -> Vec<u8> {
+        match frame {
+            Frame::Text { data } | Frame::Prompt { data } => format!("{}\r\n", data.trim_end_matches(['\r', '\n'])).into_bytes(),
+            Frame::Msdp { data } => {
+                let mut payload = Vec::new();
+                for (name, value) in data {
+                    let Ok(value) = msdp::to_vec(&value) else { continue };
+                    payload.push(1);
+                    payload.extend_from_slice(name.as_bytes());
+                    payload.push(2);
+                    payload.extend(value);
+                }
+                telnet::subnegotiation(option::MSDP, &payload)
+            }
+            Frame::Gmcp { package, data } => {
+                telnet::subnegotiation(option::GMCP, format!("{} {}", package, data).as_bytes())
+            }
+        }
+    }
+
+    /// Turn session output back into frames
+    fn telnet_to_frames(&mut self, data: &[u8]) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        let mut text = String::new();
+        let flush = |text: &mut String, frames: &mut Vec<Frame>| {
+            if !text.is_empty() {
+                frames.push(Frame::Text { data: std::mem::take(text) });
+            }
+        };
+        for event in self.output.parse(data) {
+            match event {
+                Event::Data(bytes) => text.extend(String::from_utf8_lossy(&bytes).chars().filter(|&c| c != '\r')),
+                Event::Command(telnet::EOR | telnet::GA) => {
+                    // Only the last line is the prompt itself
+                    let start = text.rfind('\n').map_or(0, |i| i + 1);
+                    let prompt = text.split_off(start);
+                    flush(&mut text, &mut frames);
+                    frames.push(Frame::Prompt { data: prompt });
+                }
+                Event::Subnegotiation(option::MSDP, payload) => {
+                    flush(&mut text, &mut frames);
+                    frames.push(Frame::Msdp { data: msdp_to_json(&payload) });
+                }
+                Event::Subnegotiation(option::GMCP, payload) => {
+                    flush(&mut text, &mut frames);
+                    let payload = String::from_utf8_lossy(&payload);
+                    let (package, body) = payload.split_once(' ').unwrap_or((&payload, ""));
+                    let data = serde_json::from_str(body).unwrap_or(Value::Null);
+                    frames.push(Frame::Gmcp { package: package.to_string(), data });
+                }
+                _ => {}
+            }
+        }
+        flush(&mut text, &mut frames);
+        frames
+    }
+}
+
+/// Decode the variables in an MSDP message one at a time, so one the codec rejects doesn't lose the others.
+/// An empty value becomes an empty string
+pub(crate) fn msdp_to_json(payload: &[u8]) -> Map<String, Value> {
+    let mut data = Map::new();
+    for pair in msdp::variables::pairs(payload) {
+        let table = [&[3][..], pair.raw, &[4]].concat();
+        match msdp::from_slice::<Map<String, Value>>(&table) {
+            // The codec lowercases keys for struct fields; MSDP names are conventionally uppercase
+            Ok(vars) => data.extend(vars.into_iter().map(|(k, v)| (k.to_ascii_uppercase(), v))),
+            Err(_) if pair.values.is_empty() => drop(data.insert(pair.name, Value::String(String::new()))),
+            Err(_) => {}
+        }
+    }
+    data
+}
+
+impl Transport for WebSocket {
+    async fn read(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            let message = match self.ws.next().await {
+                None | Some(Err(WsError::ConnectionClosed | WsError::AlreadyClosed)) => return Ok(None),
+                Some(Err(e)) => return Err(io::Error::other(e)),
+                Some(Ok(message)) => message,
+            };
+            match (self.mode, message) {
+                (_, WsMessage::Close(_)) => return Ok(None),
+                (Mode::Telnet, WsMessage::Binary(data)) => return Ok(Some(data.to_vec())),
+                (Mode::Telnet, WsMessage::Text(text)) => return Ok(Some(text.as_bytes().to_vec())),
+                (Mode::Json, WsMessage::Text(text)) => {
+                    if let Ok(frame) = serde_json::from_str(&text) {
+                        return Ok(Some(WebSocket::frame_to_telnet(frame)));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    async fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        match self.mode {
+            Mode::Telnet => self.ws.feed(WsMessage::binary(data.to_vec())).await.map_err(io::Error::other)?,
+            Mode::Json => {
+                for frame in self.telnet_to_frames(data) {
+                    let json = serde_json::to_string(&frame)?;
+                    self.ws.feed(WsMessage::text(json)).await.map_err(io::Error::other)?;
+                }
+            }
+        }
+        self.ws.flush().await.map_err(io::Error::other)
+    }
+
+    async fn close(&mut self) -> io::Result<()> {
+        match self.ws.close(None).await {
+            Ok(()) | Err(WsError::ConnectionClosed | WsError::AlreadyClosed) => Ok(()),
+            Err(e) => Err(io::Error::other(e)),
+        }
+    }
+
+    fn preamble(&self) -> Vec<u8> {
+        match self.mode {
+            Mode::Telnet => Vec::new(),
+            // JSON clients always get out-of-band data and prompt markers
+            Mode::Json => [option::MSDP, option::GMCP, option::EOR]
+                .iter()
+                .flat_map(|&opt| [telnet::IAC, telnet::DO, opt])
+                .collect(),
+        }
+    }
+
+    fn charset(&self) -> Option<Charset> {
+        (self.mode == Mode::Json).then_some(Charset::Utf8)
+    }
+}
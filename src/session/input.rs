@@ -0,0 +1,73 @@
+const BS: char = '\x08';
+const DEL: char = '\x7f';
+
+/// A complete line of input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub text: String,
+    /// The player typed more than the line limit and the rest was dropped
+    pub truncated: bool,
+}
+
+/// Assembles decoded input into lines: normalizes `CR LF`, `CR NUL`, bare `CR` and bare `LF`
+/// endings, applies backspace/DEL editing, drops other control characters and caps line length
+#[derive(Debug, Clone)]
+pub struct LineAssembler {
+    line: String,
+    len: usize,
+    limit: usize,
+    truncated: bool,
+    after_cr: bool,
+}
+
+impl LineAssembler {
+    /// `limit` is the longest line accepted, in characters
+    pub fn new(limit: usize) -> Self {
+        LineAssembler { line: String::new(), len: 0, limit, truncated: false, after_cr: false }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    /// Feed decoded text, returning every line it completes
+    pub fn push(&mut self, text: &str) -> Vec<Line> {
+        let mut lines = Vec::new();
+        for c in text.chars() {
+            let after_cr = std::mem::take(&mut self.after_cr);
+            match c {
+                '\n' | '\0' if after_cr => {}
+                '\r' | '\n' => {
+                    self.after_cr = c == '\r';
+                    lines.push(Line {
+                        text: std::mem::take(&mut self.line),
+                        truncated: std::mem::take(&mut self.truncated),
+                    });
+                    self.len = 0;
+                }
+                BS | DEL => {
+                    if self.line.pop().is_some() {
+                        self.len -= 1;
+                    }
+                }
+                '\t' => self.add(' '),
+                c if c.is_control() => {}
+                c => self.add(c),
+            }
+        }
+        lines
+    }
+
+    fn add(&mut self, c: char) {
+        if self.len < self.limit {
+            self.line.push(c);
+            self.len += 1;
+        } else {
+            self.truncated = true;
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::time::{Duration, Instant};
+
+/// At most `count` events per `period`, allowing bursts of up to `count` at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    pub count: u32,
+    pub period: Duration,
+}
+
+impl Rate {
+    pub const fn new(count: u32, period: Duration) -> Self {
+        Rate { count, period }
+    }
+}
+
+/// A token bucket enforcing a [`Rate`]
+#[derive(Debug, Clone)]
+pub struct Bucket {
+    rate: Rate,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    /// A full bucket
+    pub fn new(rate: Rate) -> Self {
+        Bucket { rate, tokens: f64::from(rate.count), updated: Instant::now() }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        let per_second = f64::from(self.rate.count) / self.rate.period.as_secs_f64().max(f64::EPSILON);
+        self.tokens = (self.tokens + elapsed * per_second).min(f64::from(self.rate.count));
+        self.updated = now;
+    }
+
+    /// Take a token if one is left
+    pub fn take(&mut self) -> bool {
+        self.take_at(Instant::now())
+    }
+
+    pub fn take_at(&mut self, now: Instant) -> bool {
+        self.take_many_at(1, now)
+    }
+
+    /// Take `n` tokens at once if that many are left, e.g. one per byte
+    pub fn take_many(&mut self, n: usize) -> bool {
+        self.take_many_at(n, Instant::now())
+    }
+
+    pub fn take_many_at(&mut self, n: usize, now: Instant) -> bool {
+        self.refill(now);
+        if self.tokens >= n as f64 {
+            self.tokens -= n as f64;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Whether the bucket has refilled completely, i.e. it has seen no recent use
+    pub fn is_full_at(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= f64::from(self.rate.count)
+    }
+}
+
+/// Flood protection for one session. `None` disables a limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Lines accepted from the player; the rest are dropped
+    pub commands: Option<Rate>,
+    /// MSDP and GMCP messages accepted from the client; the rest are dropped before decoding
+    pub oob: Option<Rate>,
+    /// Bytes of MSDP and GMCP payload accepted from the client, counted on top of `oob`. A message the budget
+    /// can't cover is dropped whole. Anything over [`MAX_SUBNEGOTIATION`](crate::telnet::MAX_SUBNEGOTIATION)
+    /// never gets this far
+    pub oob_bytes: Option<Rate>,
+    /// Dropped lines and messages tolerated before the session counts as flooding and is disconnected
+    pub flood: Option<Rate>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            commands: Some(Rate::new(20, Duration::from_secs(2))),
+            oob: Some(Rate::new(50, Duration::from_secs(1))),
+            oob_bytes: Some(Rate::new(64 * 1024, Duration::from_secs(1))),
+            flood: Some(Rate::new(50, Duration::from_secs(10))),
+        }
+    }
+}
+
+impl Limits {
+    /// No limits at all
+    pub const NONE: Limits = Limits { commands: None, oob: None, oob_bytes: None, flood: None };
+}
@@ -0,0 +1,701 @@
+//! This module provides [`Session`], the per-connection protocol state sitting between a socket and the game
+mod input;
+mod limit;
+#[cfg(test)]
+mod tests;
+
+pub use input::{Line, LineAssembler};
+pub use limit::{Bucket, Limits, Rate};
+
+use crate::charset::{self, Charset, Decoder};
+use crate::entity::EntityId;
+use crate::msdp;
+use crate::mxp::{self, Mxp};
+use crate::sound::{self, Kind, Media};
+use crate::telnet::{self, option, Change, Event, Options, Parser};
+use bytes::{Bytes, BytesMut};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::net::SocketAddr;
+
+/// Something the game should handle, decoded from the client's input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    /// A complete line typed by the player, ready for [`Commands::dispatch`](crate::commands::Commands::dispatch)
+    Line(String),
+    /// An MSDP payload, already transcoded to UTF-8 for [`msdp::from_slice`]
+    Msdp(Vec<u8>),
+    /// A GMCP message other than `Core.Supports.*`, which the session handles itself
+    Gmcp { package: String, data: Value },
+    /// The client's window was resized, reported with NAWS or an SSH window change
+    WindowSize { width: u16, height: u16 },
+}
+
+/// The telnet state, text encoding and pending output of one connection.<br/>
+/// A session does no I/O itself: feed it bytes with [`Session::receive`] and send whatever [`Session::take_output`] returns
+#[derive(Debug)]
+pub struct Session {
+    parser: Parser,
+    options: Options,
+    decoder: Decoder,
+    lines: LineAssembler,
+    charset_requested: bool,
+    gmcp_supports: HashMap<String, u32>,
+    msdp_reported: BTreeSet<String>,
+    msdp_values: BTreeMap<String, String>,
+    /// Variables the player may not see, neither sent nor asked for
+    msdp_hidden: BTreeSet<String>,
+    secure: bool,
+    window_size: Option<(u16, u16)>,
+    peer: Option<SocketAddr>,
+    entity: Option<EntityId>,
+    original: Option<EntityId>,
+    account: Option<String>,
+    /// A copy of everything written, while an administrator snoops this session
+    snooped: Option<String>,
+    commands: Option<Bucket>,
+    oob: Option<Bucket>,
+    oob_bytes: Option<Bucket>,
+    flood: Option<Bucket>,
+    throttled: bool,
+    flooding: bool,
+    /// Text for the player, which may be dropped for a client that can't keep up
+    text: BytesMut,
+    /// Prompts, negotiation and out-of-band messages, which are never dropped
+    output: BytesMut,
+}
+
+/// Everything needed to recreate a [`Session`] on a connection that is already set up, e.g. after a copyover
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionState {
+    pub local_options: Vec<u8>,
+    pub remote_options: Vec<u8>,
+    pub charset: Charset,
+    pub gmcp_supports: HashMap<String, u32>,
+    pub msdp_reported: BTreeSet<String>,
+    pub secure: bool,
+    pub window_size: Option<(u16, u16)>,
+    pub line_limit: usize,
+    pub entity: Option<EntityId>,
+    pub original: Option<EntityId>,
+    pub account: Option<String>,
+}
+
+/// The MSDP variables a client subscribed to and the last value reported for each,
+/// carried from a dropped connection to the one that takes over its character
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reports {
+    variables: BTreeSet<String>,
+    values: BTreeMap<String, String>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session::new()
+    }
+}
+
+impl Session {
+    /// The longest line accepted from a player by default, in characters
+    pub const DEFAULT_LINE_LIMIT: usize = 1024;
+
+    pub fn new() -> Self {
+        Session::with_charset(Charset::default())
+    }
+
+    /// Create a session that uses `charset` until the client negotiates another one
+    pub fn with_charset(charset: Charset) -> Self {
+        let mut options = Options::new();
+        options.support_local(option::CHARSET);
+        options.support_remote(option::CHARSET);
+        options.support_remote(option::NAWS);
+        options.support_local(option::MSDP);
+        options.support_local(option::EOR);
+        options.support_local(option::SGA);
+        options.support_local(option::MXP);
+        options.support_local(option::MSP);
+        options.support_local(option::GMCP);
+        Session {
+            parser: Parser::new(),
+            options,
+            decoder: Decoder::new(charset),
+            lines: LineAssembler::new(Session::DEFAULT_LINE_LIMIT),
+            charset_requested: false,
+            gmcp_supports: HashMap::new(),
+            msdp_reported: BTreeSet::new(),
+            msdp_values: BTreeMap::new(),
+            msdp_hidden: BTreeSet::new(),
+            secure: false,
+            window_size: None,
+            peer: None,
+            entity: None,
+            original: None,
+            account: None,
+            snooped: None,
+            commands: None,
+            oob: None,
+            oob_bytes: None,
+            flood: None,
+            throttled: false,
+            flooding: false,
+            text: BytesMut::new(),
+            output: BytesMut::new(),
+        }
+    }
+
+    /// Recreate a session from [`Session::state`]. No negotiation is sent, the client already went through it
+    pub fn from_state(state: SessionState) -> Self {
+        let mut session = Session::with_charset(state.charset);
+        session.options.restore(&state.local_options, &state.remote_options);
+        session.charset_requested = true;
+        session.gmcp_supports = state.gmcp_supports;
+        session.msdp_reported = state.msdp_reported;
+        session.secure = state.secure;
+        session.window_size = state.window_size;
+        session.entity = state.entity;
+        session.original = state.original;
+        session.account = state.account;
+        session.set_line_limit(state.line_limit);
+        session
+    }
+
+    pub fn state(&self) -> SessionState {
+        SessionState {
+            local_options: self.options.enabled_local(),
+            remote_options: self.options.enabled_remote(),
+            charset: self.charset(),
+            gmcp_supports: self.gmcp_supports.clone(),
+            msdp_reported: self.msdp_reported.clone(),
+            secure: self.secure,
+            window_size: self.window_size,
+            line_limit: self.lines.limit(),
+            entity: self.entity,
+            original: self.original,
+            account: self.account.clone(),
+        }
+    }
+
+    /// Offer the options this session supports. Call once when the connection opens
+    pub fn negotiate(&mut self) {
+        self.options.enable_local(option::CHARSET, &mut self.output);
+        self.options.enable_local(option::MSDP, &mut self.output);
+        self.options.enable_local(option::EOR, &mut self.output);
+        self.options.enable_local(option::MXP, &mut self.output);
+        self.options.enable_local(option::MSP, &mut self.output);
+        self.options.enable_local(option::GMCP, &mut self.output);
+        self.options.enable_remote(option::NAWS, &mut self.output);
+    }
+
+    pub fn charset(&self) -> Charset {
+        self.decoder.charset()
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// The entity (usually a character) this session controls
+    pub fn entity(&self) -> Option<EntityId> {
+        self.entity
+    }
+
+    /// Take control of `entity`, returning the one previously controlled
+    pub fn attach(&mut self, entity: EntityId) -> Option<EntityId> {
+        self.entity.replace(entity)
+    }
+
+    pub fn detach(&mut self) -> Option<EntityId> {
+        self.entity.take()
+    }
+
+    /// The player's own character, while the session possesses another entity
+    pub fn original(&self) -> Option<EntityId> {
+        self.original
+    }
+
+    /// Remember the character to return to after possession, returning the one remembered before
+    pub fn set_original(&mut self, original: Option<EntityId>) -> Option<EntityId> {
+        std::mem::replace(&mut self.original, original)
+    }
+
+    /// The account the player logged in to, see [`Nanny`](crate::account::Nanny)
+    pub fn account(&self) -> Option<&str> {
+        self.account.as_deref()
+    }
+
+    pub fn set_account(&mut self, account: Option<String>) {
+        self.account = account;
+    }
+
+    /// Ask the client to stop echoing what the player types, e.g. for a password, with `IAC WILL ECHO`,
+    /// or to start again with `IAC WONT ECHO`
+    pub fn hide_input(&mut self, hidden: bool) {
+        if hidden {
+            self.options.enable_local(option::ECHO, &mut self.output);
+        } else {
+            self.options.disable_local(option::ECHO, &mut self.output);
+        }
+    }
+
+    /// Start or stop copying output for a snooper, see [`Session::take_snooped`]
+    pub fn set_snooped(&mut self, snooped: bool) {
+        self.snooped = snooped.then(String::new);
+    }
+
+    pub fn is_snooped(&self) -> bool {
+        self.snooped.is_some()
+    }
+
+    /// The text and prompts written since the last call, while the session is snooped
+    pub fn take_snooped(&mut self) -> Option<String> {
+        self.snooped.as_mut().filter(|s| !s.is_empty()).map(std::mem::take)
+    }
+
+    /// Whether the connection is encrypted, so passwords can be sent safely
+    pub fn is_secure(&self) -> bool {
+        self.secure
+    }
+
+    pub fn set_secure(&mut self, secure: bool) {
+        self.secure = secure;
+    }
+
+    /// The client's window size in characters, `(width, height)`, if it reported one
+    pub fn window_size(&self) -> Option<(u16, u16)> {
+        self.window_size
+    }
+
+    /// The client's address, as reported by a trusted proxy if there is one
+    pub fn peer(&self) -> Option<SocketAddr> {
+        self.peer
+    }
+
+    pub fn set_peer(&mut self, peer: SocketAddr) {
+        self.peer = Some(peer);
+    }
+
+    /// Change the longest line accepted from the player. Longer lines are truncated and the player is told
+    pub fn set_line_limit(&mut self, limit: usize) {
+        self.lines.set_limit(limit);
+    }
+
+    /// Apply flood protection. Sessions start without any
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.commands = limits.commands.map(Bucket::new);
+        self.oob = limits.oob.map(Bucket::new);
+        self.oob_bytes = limits.oob_bytes.map(Bucket::new);
+        self.flood = limits.flood.map(Bucket::new);
+    }
+
+    /// Whether the client kept sending input past its limits for long enough that it should be disconnected
+    pub fn is_flooding(&self) -> bool {
+        self.flooding
+    }
+
+    /// Count an input costing `cost` tokens against `bucket`, recording a strike against the flood limit if it's
+    /// dropped
+    fn admit(bucket: &mut Option<Bucket>, cost: usize, flood: &mut Option<Bucket>, flooding: &mut bool) -> bool {
+        if bucket.as_mut().is_none_or(|b| b.take_many(cost)) {
+            return true;
+        }
+        if flood.as_mut().is_some_and(|f| !f.take()) {
+            *flooding = true;
+        }
+        false
+    }
+
+    /// Process bytes read from the client
+    pub fn receive(&mut self, data: &[u8]) -> Vec<Input> {
+        let mut inputs = Vec::new();
+        for event in self.parser.parse(data) {
+            match event {
+                Event::Data(bytes) => {
+                    let text = self.decoder.decode(&bytes);
+                    for line in self.lines.push(&text) {
+                        if !Session::admit(&mut self.commands, 1, &mut self.flood, &mut self.flooding) {
+                            if !self.throttled {
+                                self.throttled = true;
+                                self.write("You are sending commands too fast, some were ignored.\n");
+                            }
+                            continue;
+                        }
+                        self.throttled = false;
+                        if line.truncated {
+                            self.write(&format!(
+                                "Your input was too long and was cut to {} characters.\n",
+                                line.text.chars().count()
+                            ));
+                        }
+                        inputs.push(Input::Line(line.text));
+                    }
+                }
+                Event::Will(opt) => self.negotiated(telnet::WILL, opt),
+                Event::Wont(opt) => self.negotiated(telnet::WONT, opt),
+                Event::Do(opt) => self.negotiated(telnet::DO, opt),
+                Event::Dont(opt) => self.negotiated(telnet::DONT, opt),
+                Event::Subnegotiation(option::CHARSET, data) => self.charset_message(&data),
+                Event::Subnegotiation(option::NAWS, data)
+                    if self.options.remote_enabled(option::NAWS) && data.len() == 4 =>
+                {
+                    let (width, height) = (u16::from_be_bytes([data[0], data[1]]), u16::from_be_bytes([data[2], data[3]]));
+                    self.window_size = Some((width, height));
+                    inputs.push(Input::WindowSize { width, height });
+                }
+                Event::Subnegotiation(option::MSDP | option::GMCP, ref data)
+                    if !(Session::admit(&mut self.oob, 1, &mut self.flood, &mut self.flooding)
+                        && Session::admit(&mut self.oob_bytes, data.len(), &mut self.flood, &mut self.flooding)) => {}
+                Event::Subnegotiation(option::MSDP, data) if self.options.local_enabled(option::MSDP) => {
+                    let data = self.filter_msdp(self.charset().decode(&data).into_bytes());
+                    if !data.is_empty() {
+                        self.msdp_command(&data);
+                        inputs.push(Input::Msdp(data));
+                    }
+                }
+                Event::Subnegotiation(option::GMCP, data) if self.options.local_enabled(option::GMCP) => {
+                    inputs.extend(self.gmcp_message(&data));
+                }
+                Event::Subnegotiation(..) | Event::Command(_) => {}
+            }
+        }
+        inputs
+    }
+
+    fn negotiated(&mut self, command: u8, opt: u8) {
+        let change = self.options.receive(command, opt, &mut self.output);
+        if let Some(Change::Local(option::MXP, true)) = change {
+            self.output.extend(telnet::subnegotiation(option::MXP, &[]));
+        }
+        if let Some(Change::Local(option::CHARSET, true) | Change::Remote(option::CHARSET, true)) = change {
+            if !self.charset_requested {
+                self.charset_requested = true;
+                self.output.extend(telnet::subnegotiation(option::CHARSET, &charset::request(&Charset::ALL)));
+            }
+        }
+    }
+
+    fn charset_message(&mut self, data: &[u8]) {
+        match charset::Message::parse(data) {
+            charset::Message::Accepted(name) => {
+                if let Some(c) = Charset::from_name(&name) {
+                    self.decoder.set_charset(c);
+                }
+            }
+            charset::Message::Request(names) => {
+                let reply = match names.iter().find_map(|n| Charset::from_name(n).map(|c| (n, c))) {
+                    Some((name, c)) => {
+                        self.decoder.set_charset(c);
+                        [&[charset::ACCEPTED][..], name.as_bytes()].concat()
+                    }
+                    None => vec![charset::REJECTED],
+                };
+                self.output.extend(telnet::subnegotiation(option::CHARSET, &reply));
+            }
+            charset::Message::TranslationTable => {
+                self.output.extend(telnet::subnegotiation(option::CHARSET, &[charset::TTABLE_REJECTED]));
+            }
+            charset::Message::Rejected | charset::Message::Unknown => {}
+        }
+    }
+
+    /// Track `REPORT`, `UNREPORT` and `RESET` so reported variables survive reconnects and copyovers
+    fn msdp_command(&mut self, data: &[u8]) {
+        for pair in data.split(|&b| b == 1).filter(|p| !p.is_empty()) {
+            let (name, values) = match pair.iter().position(|&b| b == 2) {
+                Some(i) => (&pair[..i], &pair[i..]),
+                None => (pair, &[][..]),
+            };
+            let mut values = values
+                .split(|&b| b == 2)
+                .map(|v| v.iter().filter(|&&b| b != 5 && b != 6).copied().collect::<Vec<_>>())
+                .filter(|v| !v.is_empty())
+                .map(|v| String::from_utf8_lossy(&v).to_ascii_uppercase());
+            match &name.to_ascii_uppercase()[..] {
+                b"REPORT" => self.msdp_reported.extend(values),
+                b"UNREPORT" => {
+                    for v in values {
+                        self.msdp_reported.remove(&v);
+                        self.msdp_values.remove(&v);
+                    }
+                }
+                b"RESET" if values.any(|v| v == "REPORTED_VARIABLES" || v == "REPORTABLE_VARIABLES") => {
+                    self.msdp_reported.clear();
+                    self.msdp_values.clear();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Keep these MSDP variables from the player: they are never sent, and requests for them are dropped
+    /// before they become [`Input::Msdp`]. Replaces the previous set, and stops reporting the variables
+    pub fn hide_msdp(&mut self, variables: impl IntoIterator<Item = String>) {
+        self.msdp_hidden = variables.into_iter().map(|v| v.to_ascii_uppercase()).collect();
+        for variable in &self.msdp_hidden {
+            self.msdp_reported.remove(variable);
+            self.msdp_values.remove(variable);
+        }
+    }
+
+    pub fn is_msdp_hidden(&self, variable: &str) -> bool {
+        self.msdp_hidden.contains(&variable.to_ascii_uppercase())
+    }
+
+    /// Remove hidden variables from an MSDP message: pairs that set them, and values of `REPORT`, `UNREPORT` and `SEND`
+    fn filter_msdp(&self, data: Vec<u8>) -> Vec<u8> {
+        if self.msdp_hidden.is_empty() {
+            return data;
+        }
+        let hidden = |value: &[u8]| self.msdp_hidden.contains(&String::from_utf8_lossy(value).to_ascii_uppercase());
+        let mut filtered = Vec::new();
+        for pair in msdp::variables::pairs(&data) {
+            if self.msdp_hidden.contains(&pair.name) {
+                continue;
+            }
+            if !matches!(pair.name.as_str(), "REPORT" | "UNREPORT" | "SEND") || !pair.values.iter().any(|v| hidden(v)) {
+                filtered.extend_from_slice(pair.raw);
+                continue;
+            }
+            let shown: Vec<_> = pair.values.into_iter().filter(|v| !hidden(v)).collect();
+            if !shown.is_empty() {
+                filtered.push(1);
+                filtered.extend_from_slice(pair.name.as_bytes());
+                for value in shown {
+                    filtered.push(2);
+                    filtered.extend_from_slice(value);
+                }
+            }
+        }
+        filtered
+    }
+
+    /// MSDP variables the client asked to have reported when they change
+    pub fn msdp_reported(&self) -> impl Iterator<Item = &str> {
+        self.msdp_reported.iter().map(String::as_str)
+    }
+
+    pub fn is_reported(&self, variable: &str) -> bool {
+        self.msdp_reported.contains(&variable.to_ascii_uppercase())
+    }
+
+    /// Reported variables and their last values, see [`Session::resume_reports`]
+    pub fn reports(&self) -> Reports {
+        Reports { variables: self.msdp_reported.clone(), values: self.msdp_values.clone() }
+    }
+
+    /// Take over the subscriptions of a dropped connection and send the client every value it was last told
+    pub fn resume_reports(&mut self, reports: Reports) {
+        self.msdp_reported.extend(reports.variables);
+        for (name, value) in reports.values {
+            if self.msdp_reported.contains(&name) {
+                self.msdp_values.entry(name).or_insert(value);
+            }
+        }
+        if self.options.local_enabled(option::MSDP) {
+            for payload in self.msdp_values.values() {
+                self.output.extend(telnet::subnegotiation(option::MSDP, &self.charset().encode(payload)));
+            }
+        }
+    }
+
+    fn gmcp_message(&mut self, data: &[u8]) -> Option<Input> {
+        // GMCP is always UTF-8, whatever the session charset is
+        let text = String::from_utf8_lossy(data);
+        let (package, body) = text.split_once(' ').unwrap_or((&text, ""));
+        let data = serde_json::from_str(body).unwrap_or(Value::Null);
+        match package.to_ascii_lowercase().as_str() {
+            "core.supports.set" => {
+                self.gmcp_supports.clear();
+                self.gmcp_support(&data, true);
+            }
+            "core.supports.add" => self.gmcp_support(&data, true),
+            "core.supports.remove" => self.gmcp_support(&data, false),
+            _ => return Some(Input::Gmcp { package: package.to_string(), data }),
+        }
+        None
+    }
+
+    fn gmcp_support(&mut self, data: &Value, add: bool) {
+        for entry in data.as_array().into_iter().flatten().filter_map(Value::as_str) {
+            let (module, version) = entry.split_once(' ').unwrap_or((entry, "1"));
+            let module = module.to_ascii_lowercase();
+            if add {
+                self.gmcp_supports.insert(module, version.trim().parse().unwrap_or(1));
+            } else {
+                self.gmcp_supports.remove(&module);
+            }
+        }
+    }
+
+    /// Whether the client announced support for a GMCP module, such as `Client.Media`
+    pub fn gmcp_supports(&self, module: &str) -> bool {
+        self.options.local_enabled(option::GMCP) && self.gmcp_supports.contains_key(&module.to_ascii_lowercase())
+    }
+
+    /// Queue text for the player, encoded in the session's charset. Line feeds are sent as `CR LF`.
+    /// With MXP on, the text is escaped so it is never read as markup, see [`Session::write_mxp`]
+    pub fn write(&mut self, text: &str) {
+        if let Some(snooped) = &mut self.snooped {
+            snooped.push_str(text);
+        }
+        let encoded = self.encode_text(text);
+        self.text.extend(encoded);
+    }
+
+    fn encode_text(&self, text: &str) -> Vec<u8> {
+        if self.options.local_enabled(option::MXP) {
+            return self.encode_markup(&mxp::escape(text));
+        }
+        self.encode_markup(text)
+    }
+
+    /// Like [`Session::encode_text`], but MXP tags are left as they are
+    fn encode_markup(&self, text: &str) -> Vec<u8> {
+        let mut crlf = String::with_capacity(text.len());
+        let mut prev = '\0';
+        for c in text.chars() {
+            if c == '\n' && prev != '\r' {
+                crlf.push('\r');
+            }
+            crlf.push(c);
+            prev = c;
+        }
+        telnet::escape(&self.charset().encode(&crlf))
+    }
+
+    /// Start building MXP output, which falls back to plain text if the client didn't negotiate MXP
+    pub fn mxp(&self) -> Mxp {
+        Mxp::new(self.options.local_enabled(option::MXP))
+    }
+
+    /// Queue output built with [`Session::mxp`]
+    pub fn write_mxp(&mut self, mxp: Mxp) {
+        let text = mxp.finish();
+        if let Some(snooped) = &mut self.snooped {
+            snooped.push_str(&text);
+        }
+        let encoded = self.encode_markup(&text);
+        self.text.extend(encoded);
+    }
+
+    /// Queue a prompt, followed by `IAC EOR` if the client negotiated EOR, or `IAC GA` unless it suppressed go-ahead
+    pub fn prompt(&mut self, text: &str) {
+        if let Some(snooped) = &mut self.snooped {
+            snooped.push_str(text);
+        }
+        // Prompts go with the out-of-band output so they survive when text is dropped, after any pending text
+        let encoded = self.encode_text(text);
+        self.output.extend(encoded);
+        if self.options.local_enabled(option::EOR) {
+            self.output.extend_from_slice(&[telnet::IAC, telnet::EOR]);
+        } else if !self.options.local_enabled(option::SGA) {
+            self.output.extend_from_slice(&[telnet::IAC, telnet::GA]);
+        }
+    }
+
+    /// Send an MSDP variable, if the client negotiated MSDP and it isn't hidden, see [`Session::hide_msdp`].
+    /// String values are encoded in the session's charset
+    pub fn send_msdp<T>(&mut self, name: &str, value: &T) -> msdp::Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        if !self.options.local_enabled(option::MSDP) || self.is_msdp_hidden(name) {
+            return Ok(());
+        }
+        let mut payload = vec![1];
+        payload.extend(msdp::to_vec(&name)?);
+        payload.push(2);
+        payload.extend(msdp::to_vec(value)?);
+        // MSDP's delimiters are ASCII control bytes, so the whole payload can be transcoded at once
+        let text = String::from_utf8(payload).map_err(|_| msdp::Error::Parse("MSDP output isn't valid UTF-8"))?;
+        self.output.extend(telnet::subnegotiation(option::MSDP, &self.charset().encode(&text)));
+        let name = name.to_ascii_uppercase();
+        if self.msdp_reported.contains(&name) {
+            self.msdp_values.insert(name, text);
+        }
+        Ok(())
+    }
+
+    /// Send a GMCP message, if the client negotiated GMCP
+    pub fn send_gmcp<T>(&mut self, package: &str, data: &T) -> serde_json::Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        if !self.options.local_enabled(option::GMCP) {
+            return Ok(());
+        }
+        let payload = format!("{} {}", package, serde_json::to_string(data)?);
+        self.output.extend(telnet::subnegotiation(option::GMCP, payload.as_bytes()));
+        Ok(())
+    }
+
+    fn media_over_gmcp(&self) -> bool {
+        self.gmcp_supports("Client.Media")
+    }
+
+    /// Play a sound effect with MCMP or MSP, whichever the client supports.
+    /// Returns `false` if it supports neither
+    pub fn play_sound(&mut self, name: &str, volume: u8) -> bool {
+        self.play(&Media::sound(name).volume(volume))
+    }
+
+    /// Play a music track with MCMP or MSP, whichever the client supports
+    pub fn play_music(&mut self, name: &str, volume: u8) -> bool {
+        self.play(&Media::music(name).volume(volume))
+    }
+
+    /// Play `media` with MCMP or MSP, whichever the client supports
+    pub fn play(&mut self, media: &Media) -> bool {
+        if self.media_over_gmcp() {
+            self.send_gmcp("Client.Media.Play", &media.mcmp()).is_ok()
+        } else if self.options.local_enabled(option::MSP) {
+            self.write(&format!("{}\n", media.msp()));
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Stop playing media, either all of it or only one kind
+    pub fn stop_media(&mut self, kind: Option<Kind>) -> bool {
+        if self.media_over_gmcp() {
+            self.send_gmcp("Client.Media.Stop", &sound::mcmp_stop(kind)).is_ok()
+        } else if self.options.local_enabled(option::MSP) {
+            for kind in kind.map_or(vec![Kind::Sound, Kind::Music], |k| vec![k]) {
+                self.write(&format!("{}\n", sound::msp_stop(kind)));
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Ask an MCMP client to download a file ahead of time. MSP has no equivalent
+    pub fn load_media(&mut self, name: &str, url: &str) -> bool {
+        self.media_over_gmcp()
+            && self.send_gmcp("Client.Media.Load", &serde_json::json!({ "name": name, "url": url })).is_ok()
+    }
+
+    /// Take everything queued for the client: text first, then prompts and out-of-band messages
+    pub fn take_output(&mut self) -> Bytes {
+        let mut out = self.text.split();
+        out.unsplit(self.output.split());
+        out.freeze()
+    }
+
+    pub fn has_output(&self) -> bool {
+        !self.text.is_empty() || !self.output.is_empty()
+    }
+
+    /// Bytes of text waiting in [`Session::take_output`], not counting prompts and out-of-band messages
+    pub fn pending_text(&self) -> usize {
+        self.text.len()
+    }
+
+    /// Drop the text waiting to be sent, keeping prompts and out-of-band messages. Returns how many bytes were dropped
+    pub fn discard_text(&mut self) -> usize {
+        let len = self.text.len();
+        self.text.clear();
+        len
+    }
+}
@@ -0,0 +1,142 @@
+use super::{Input, LineAssembler, Session};
+use crate::charset::Charset;
+use crate::telnet::{option, DO, EOR, GA, IAC, SB, SE};
+
+#[test]
+fn charset_negotiation() {
+    let mut session = Session::new();
+    session.negotiate();
+    session.take_output();
+    session.receive(&[IAC, DO, option::CHARSET]);
+    let out = session.take_output();
+    assert!(out.starts_with(&[IAC, SB, option::CHARSET, 1, b';']));
+    session.receive(&[&[IAC, SB, option::CHARSET, 2][..], b"ISO-8859-1", &[IAC, SE]].concat());
+    assert_eq!(session.charset(), Charset::Latin1);
+    session.write("café – naïve\n");
+    assert_eq!(&session.take_output()[..], b"caf\xe9 - na\xefve\r\n");
+    assert_eq!(session.receive(b"\xe9t\xe9\r\n"), vec![Input::Line("été".to_string())]);
+}
+
+#[test]
+fn msdp_is_transcoded() {
+    let mut session = Session::with_charset(Charset::Latin1);
+    session.receive(&[IAC, DO, option::MSDP]);
+    session.take_output();
+    session.send_msdp("ROOM_NAME", "Forêt").unwrap();
+    assert_eq!(
+        &session.take_output()[..],
+        &[&[IAC, SB, option::MSDP, 1][..], b"ROOM_NAME\x02For\xeat", &[IAC, SE]].concat()[..]
+    );
+    let input = session.receive(&[&[IAC, SB, option::MSDP, 1][..], b"SAY\x02\xe9", &[IAC, SE]].concat());
+    assert_eq!(input, vec![Input::Msdp("\x01SAY\x02é".as_bytes().to_vec())]);
+}
+
+#[test]
+fn prompt_terminators() {
+    let mut session = Session::new();
+    session.prompt("> ");
+    assert_eq!(&session.take_output()[..], &[b'>', b' ', IAC, GA]);
+    session.receive(&[IAC, DO, option::SGA]);
+    session.take_output();
+    session.prompt("> ");
+    assert_eq!(&session.take_output()[..], b"> ");
+    session.receive(&[IAC, DO, option::EOR]);
+    session.take_output();
+    session.prompt("> ");
+    assert_eq!(&session.take_output()[..], &[b'>', b' ', IAC, EOR]);
+}
+
+#[test]
+fn sound_picks_protocol() {
+    let mut session = Session::new();
+    assert!(!session.play_sound("combat/hit.wav", 50));
+    session.receive(&[IAC, DO, option::MSP]);
+    session.take_output();
+    assert!(session.play_sound("combat/hit.wav", 50));
+    assert_eq!(&session.take_output()[..], b"!!SOUND(combat/hit.wav V=50 L=1 P=50)\r\n");
+    session.receive(&[IAC, DO, option::GMCP]);
+    session.receive(&[&[IAC, SB, option::GMCP][..], br#"Core.Supports.Set ["Client.Media 1"]"#, &[IAC, SE]].concat());
+    session.take_output();
+    assert!(session.play_sound("combat/hit.wav", 50));
+    let out = session.take_output();
+    assert!(out.starts_with(&[&[IAC, SB, option::GMCP][..], b"Client.Media.Play {"].concat()));
+}
+
+#[test]
+fn line_discipline() {
+    let mut lines = LineAssembler::new(5);
+    let texts = |lines: Vec<super::Line>| lines.into_iter().map(|l| (l.text, l.truncated)).collect::<Vec<_>>();
+    assert_eq!(texts(lines.push("look\r")), vec![("look".to_string(), false)]);
+    assert_eq!(texts(lines.push("\0nortx\x08h\r\n")), vec![("north".to_string(), false)]);
+    assert_eq!(texts(lines.push("s\x1bay\tabcdef\n\n")), vec![("say a".to_string(), true), (String::new(), false)]);
+}
+
+#[test]
+fn state_round_trip() {
+    let mut session = Session::new();
+    session.receive(&[IAC, DO, option::MSDP]);
+    session.receive(&[&[IAC, SB, option::MSDP, 1][..], b"REPORT\x02\x05\x02HEALTH\x02ROOM\x06", &[IAC, SE]].concat());
+    session.receive(&[&[IAC, SB, option::MSDP, 1][..], b"UNREPORT\x02room", &[IAC, SE]].concat());
+    session.attach(crate::entity::EntityId(7));
+    let restored = Session::from_state(session.state());
+    assert!(restored.options().local_enabled(option::MSDP));
+    assert_eq!(restored.msdp_reported().collect::<Vec<_>>(), vec!["HEALTH"]);
+    assert_eq!(restored.state(), session.state());
+}
+
+#[test]
+fn flood_limits() {
+    use super::{Limits, Rate};
+    use std::time::Duration;
+
+    let mut session = Session::new();
+    let rate = Some(Rate::new(2, Duration::from_secs(3600)));
+    session.set_limits(Limits { commands: rate, oob: rate, oob_bytes: None, flood: rate });
+    session.receive(&[IAC, DO, option::GMCP]);
+    session.take_output();
+    let input = session.receive(b"look\r\nlook\r\nlook\r\n");
+    assert_eq!(input.len(), 2);
+    assert_eq!(&session.take_output()[..], b"You are sending commands too fast, some were ignored.\r\n");
+    let gmcp = [&[IAC, SB, option::GMCP][..], b"Char.Ping", &[IAC, SE]].concat();
+    assert_eq!(session.receive(&gmcp.repeat(3)).len(), 2);
+    assert!(!session.is_flooding());
+    session.receive(b"look\r\n");
+    assert!(session.is_flooding());
+
+    // One big message costs as much as many small ones
+    let mut session = Session::new();
+    let bytes = Some(Rate::new(1000, Duration::from_secs(3600)));
+    session.set_limits(Limits { oob_bytes: bytes, ..Limits::NONE });
+    session.receive(&[IAC, DO, option::GMCP]);
+    let blob = [&[IAC, SB, option::GMCP][..], b"Core.Hello ", &[b'x'; 600], &[IAC, SE]].concat();
+    assert_eq!(session.receive(&blob).len(), 1);
+    assert!(session.receive(&blob).is_empty());
+    assert_eq!(session.receive(&gmcp).len(), 1);
+}
+
+#[test]
+fn prompts_survive_dropped_text() {
+    let mut session = Session::new();
+    session.write("lots of text\n");
+    session.prompt("> ");
+    session.write("more text\n");
+    assert_eq!(session.pending_text(), 25);
+    assert_eq!(&session.take_output()[..], &[&b"lots of text\r\nmore text\r\n> "[..], &[IAC, GA]].concat()[..]);
+    session.write("dropped\n");
+    session.prompt("> ");
+    assert_eq!(session.discard_text(), 9);
+    assert_eq!(&session.take_output()[..], &[b'>', b' ', IAC, GA]);
+}
+
+#[test]
+fn plain_text_is_escaped_for_mxp() {
+    let mut session = Session::new();
+    session.receive(&[IAC, DO, option::MXP]);
+    session.take_output();
+    session.write("<send href='quit'>\n");
+    assert_eq!(&session.take_output()[..], b"&lt;send href='quit'&gt;\r\n");
+    let mut mxp = session.mxp();
+    mxp.send("look", "look");
+    session.write_mxp(mxp);
+    assert_eq!(&session.take_output()[..], b"\x1b[1z<send href=\"look\">look</send>\x1b[2z");
+}
@@ -0,0 +1,152 @@
+//! This module provides sound and music triggers for [MSP](https://www.zuggsoft.com/zmud/msp.htm) and [MCMP](https://wiki.mudlet.org/w/Standards:MUD_Client_Media_Protocol) clients
+#[cfg(test)]
+mod tests;
+
+use serde_json::{json, Map, Value};
+
+/// Whether a media file is a sound effect or background music
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Sound,
+    Music,
+}
+
+impl Kind {
+    fn msp(&self) -> &'static str {
+        match self {
+            Kind::Sound => "SOUND",
+            Kind::Music => "MUSIC",
+        }
+    }
+
+    fn mcmp(&self) -> &'static str {
+        match self {
+            Kind::Sound => "sound",
+            Kind::Music => "music",
+        }
+    }
+}
+
+/// A sound or piece of music to play
+#[derive(Debug, Clone, PartialEq)]
+pub struct Media {
+    name: String,
+    kind: Kind,
+    volume: u8,
+    loops: i32,
+    priority: u8,
+    tag: Option<String>,
+    url: Option<String>,
+    key: Option<String>,
+}
+
+impl Media {
+    /// A sound effect, relative to the client's sound directory (e.g. `combat/hit.wav`)
+    pub fn sound(name: &str) -> Self {
+        Media::new(name, Kind::Sound)
+    }
+
+    /// A music track
+    pub fn music(name: &str) -> Self {
+        Media::new(name, Kind::Music)
+    }
+
+    fn new(name: &str, kind: Kind) -> Self {
+        Media {
+            name: name.to_string(),
+            kind,
+            volume: 100,
+            loops: 1,
+            priority: 50,
+            tag: None,
+            url: None,
+            key: None,
+        }
+    }
+
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    /// Volume from 0 to 100
+    pub fn volume(mut self, volume: u8) -> Self {
+        self.volume = volume.min(100);
+        self
+    }
+
+    /// How many times to play, `-1` to repeat forever
+    pub fn loops(mut self, loops: i32) -> Self {
+        self.loops = loops;
+        self
+    }
+
+    /// Priority from 0 to 100; a sound can interrupt others with lower priority
+    pub fn priority(mut self, priority: u8) -> Self {
+        self.priority = priority.min(100);
+        self
+    }
+
+    /// A category the client can use to group or filter media
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    /// Where the client can download the file if it doesn't have it
+    pub fn url(mut self, url: &str) -> Self {
+        self.url = Some(url.to_string());
+        self
+    }
+
+    /// An identifier that lets MCMP clients stop or replace this media later
+    pub fn key(mut self, key: &str) -> Self {
+        self.key = Some(key.to_string());
+        self
+    }
+
+    /// The `!!SOUND(...)`/`!!MUSIC(...)` trigger for this media
+    pub fn msp(&self) -> String {
+        let mut out = format!("!!{}({} V={} L={}", self.kind.msp(), self.name, self.volume, self.loops);
+        match self.kind {
+            Kind::Sound => out.push_str(&format!(" P={}", self.priority)),
+            Kind::Music => out.push_str(" C=1"),
+        }
+        if let Some(tag) = &self.tag {
+            out.push_str(&format!(" T={}", tag));
+        }
+        if let Some(url) = &self.url {
+            out.push_str(&format!(" U={}", url));
+        }
+        out.push(')');
+        out
+    }
+
+    /// The body of a `Client.Media.Play` message for this media
+    pub fn mcmp(&self) -> Value {
+        let mut body = Map::new();
+        body.insert("name".into(), json!(self.name));
+        body.insert("type".into(), json!(self.kind.mcmp()));
+        body.insert("volume".into(), json!(self.volume));
+        body.insert("loops".into(), json!(self.loops));
+        body.insert("priority".into(), json!(self.priority));
+        for (field, value) in [("tag", &self.tag), ("url", &self.url), ("key", &self.key)] {
+            if let Some(value) = value {
+                body.insert(field.into(), json!(value));
+            }
+        }
+        Value::Object(body)
+    }
+}
+
+/// The MSP trigger that stops all media of `kind`
+pub fn msp_stop(kind: Kind) -> String {
+    format!("!!{}(Off)", kind.msp())
+}
+
+/// The body of a `Client.Media.Stop` message, stopping all media or only one kind
+pub fn mcmp_stop(kind: Option<Kind>) -> Value {
+    match kind {
+        Some(kind) => json!({ "type": kind.mcmp() }),
+        None => json!({}),
+    }
+}
@@ -0,0 +1,13 @@
+use super::Media;
+use serde_json::json;
+
+#[test]
+fn triggers() {
+    let hit = Media::sound("combat/hit.wav").volume(80).tag("combat");
+    assert_eq!(hit.msp(), "!!SOUND(combat/hit.wav V=80 L=1 P=50 T=combat)");
+    assert_eq!(
+        hit.mcmp(),
+        json!({"name": "combat/hit.wav", "type": "sound", "volume": 80, "loops": 1, "priority": 50, "tag": "combat"})
+    );
+    assert_eq!(Media::music("town.mid").loops(-1).msp(), "!!MUSIC(town.mid V=100 L=-1 C=1)");
+}
@@ -0,0 +1,125 @@
+//! One JSON file per key, at `<root>/<table>/<key>.json`.
+//! Keys are escaped so that any key makes a safe file name, on case-insensitive file systems too
+use super::{Backend, StoreError};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A [`Backend`] keeping each value in its own file
+#[derive(Debug)]
+pub struct JsonFiles {
+    root: PathBuf,
+    /// Makes temporary file names unique when the same key is written twice at once
+    writes: AtomicU64,
+}
+
+/// Keep lowercase letters, digits, `-` and `_`, and write anything else as `%XX` for each byte.
+/// The empty key is a lone `%`, since `.json` would look like a temporary file
+fn escape(key: &str) -> String {
+    if key.is_empty() {
+        return "%".to_string();
+    }
+    let mut escaped = String::new();
+    for byte in key.bytes() {
+        match byte {
+            b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    escaped
+}
+
+fn unescape(name: &str) -> Option<String> {
+    if name == "%" {
+        return Some(String::new());
+    }
+    let mut bytes = Vec::new();
+    let mut rest = name.as_bytes();
+    while let Some((&first, tail)) = rest.split_first() {
+        if first == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(first);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Missing files are missing values, not errors
+fn missing<T>(result: io::Result<T>) -> Result<Option<T>, StoreError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+impl JsonFiles {
+    /// Store files under `root`, creating it if needed
+    pub fn new(root: impl AsRef<Path>) -> Result<Self, StoreError> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(&root)?;
+        Ok(JsonFiles { root, writes: AtomicU64::new(0) })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path(&self, table: &str, key: &str) -> PathBuf {
+        self.root.join(table).join(escape(key) + ".json")
+    }
+}
+
+impl Backend for JsonFiles {
+    fn get(&self, table: &str, key: &str) -> Result<Option<Vec<u8>>, StoreError> {
+        missing(fs::read(self.path(table, key)))
+    }
+
+    /// Write a temporary file beside the real one, flush it to disk, then rename it over the real one
+    fn put(&self, table: &str, key: &str, value: &[u8]) -> Result<(), StoreError> {
+        let dir = self.root.join(table);
+        fs::create_dir_all(&dir)?;
+        let write = self.writes.fetch_add(1, Ordering::Relaxed);
+        let temp = dir.join(format!(".{}.{}.{}.tmp", escape(key), std::process::id(), write));
+        let result = (|| {
+            let mut file = File::create(&temp)?;
+            file.write_all(value)?;
+            file.sync_all()?;
+            fs::rename(&temp, self.path(table, key))
+        })();
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result?;
+        // The rename itself is only durable once the directory is
+        #[cfg(unix)]
+        File::open(&dir)?.sync_all()?;
+        Ok(())
+    }
+
+    fn delete(&self, table: &str, key: &str) -> Result<bool, StoreError> {
+        Ok(missing(fs::remove_file(self.path(table, key)))?.is_some())
+    }
+
+    /// Temporary files left by a crash start with `.` and are skipped
+    fn keys(&self, table: &str) -> Result<Vec<String>, StoreError> {
+        let Some(entries) = missing(fs::read_dir(self.root.join(table)))? else {
+            return Ok(Vec::new());
+        };
+        let mut keys = Vec::new();
+        for entry in entries {
+            let name = entry?.file_name();
+            let Some(name) = name.to_str() else { continue };
+            if let Some(key) = name.strip_suffix(".json").filter(|n| !n.starts_with('.')).and_then(unescape) {
+                keys.push(key);
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+}
@@ -0,0 +1,121 @@
+//! Saved records carry the schema version they were written at, as `{"version": 2, "data": {...}}`.
+//! Register a [`Migrations`] chain with [`Store::migrations`](super::Store::migrations) and older records are
+//! upgraded one version at a time as they load. Records from before versioning count as version 0
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+/// Upgrades a record's data by one version
+pub type Migration = Box<dyn Fn(Value) -> Result<Value, String> + Send + Sync>;
+
+/// Why a stored record couldn't be read at the current version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Failure {
+    /// Written by a newer release, which knows migrations this one doesn't
+    TooNew(u32),
+    /// Not JSON, a migration failed, or the result doesn't deserialize. `version` is what the record was stored at
+    Bad { version: Option<u32>, reason: String },
+}
+
+/// The migration chain of each table
+#[derive(Default)]
+pub struct Migrations {
+    tables: BTreeMap<String, Vec<Migration>>,
+}
+
+impl fmt::Debug for Migrations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let versions: BTreeMap<_, _> = self.tables.iter().map(|(t, m)| (t, m.len())).collect();
+        f.debug_struct("Migrations").field("versions", &versions).finish()
+    }
+}
+
+/// Wrap a record's data with its version
+pub(crate) fn envelope(version: u32, data: Value) -> Value {
+    serde_json::json!({ "version": version, "data": data })
+}
+
+/// Split a stored record into its version and data
+fn open(value: Value) -> Result<(u32, Value), String> {
+    match value {
+        Value::Object(mut map) if map.len() == 2 && map.contains_key("version") && map.contains_key("data") => {
+            let version = map["version"].as_u64().and_then(|v| u32::try_from(v).ok());
+            let version = version.ok_or("the version isn't a number")?;
+            Ok((version, map.remove("data").unwrap_or_default()))
+        }
+        legacy => Ok((0, legacy)),
+    }
+}
+
+impl Migrations {
+    pub fn new() -> Self {
+        Migrations::default()
+    }
+
+    /// Add the migration that upgrades `table` records from `version - 1` to `version`.
+    /// Versions start at 1 and each table's must be added in order, anything else panics
+    pub fn add<F>(mut self, table: &str, version: u32, migration: F) -> Self
+    where
+        F: Fn(Value) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        let chain = self.tables.entry(table.to_string()).or_default();
+        assert_eq!(version as usize, chain.len() + 1, "migrations for '{}' must be added in order", table);
+        chain.push(Box::new(migration));
+        self
+    }
+
+    /// The version records in `table` are saved at
+    pub fn version(&self, table: &str) -> u32 {
+        self.tables.get(table).map_or(0, |chain| chain.len() as u32)
+    }
+
+    /// Tables with at least one migration
+    pub fn tables(&self) -> impl Iterator<Item = &str> {
+        self.tables.keys().map(String::as_str)
+    }
+
+    /// Parse a stored record and bring it up to the current version. Returns the data and the version it was at
+    pub(crate) fn upgrade(&self, table: &str, bytes: &[u8]) -> Result<(Value, u32), Failure> {
+        let bad = |version, reason: String| Failure::Bad { version, reason };
+        let value = serde_json::from_slice(bytes).map_err(|e| bad(None, format!("bad JSON: {}", e)))?;
+        let (stored, mut data) = open(value).map_err(|e| bad(None, e))?;
+        let current = self.version(table);
+        if stored > current {
+            return Err(Failure::TooNew(stored));
+        }
+        let chain = self.tables.get(table).map_or(&[][..], Vec::as_slice);
+        for (from, migration) in chain.iter().enumerate().skip(stored as usize) {
+            data = migration(data).map_err(|e| bad(Some(stored), format!("migrating to version {}: {}", from + 1, e)))?;
+        }
+        Ok((data, stored))
+    }
+}
+
+/// What [`Store::migrate_all`](super::Store::migrate_all) did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MigrationReport {
+    /// Records upgraded and saved back
+    pub migrated: usize,
+    /// Records already at the current version
+    pub current: usize,
+    /// `table/key: reason` for each record moved to quarantine
+    pub quarantined: Vec<String>,
+    /// `table/key` for each record written by a newer release, left alone
+    pub too_new: Vec<String>,
+}
+
+impl Display for MigrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} migrated, {} already current", self.migrated, self.current)?;
+        if !self.too_new.is_empty() {
+            write!(f, ", {} from a newer release", self.too_new.len())?;
+        }
+        if !self.quarantined.is_empty() {
+            write!(f, ", {} quarantined:", self.quarantined.len())?;
+            for record in &self.quarantined {
+                write!(f, "\n  {}", record)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,308 @@
+//! This module provides [`Store`], where the game keeps accounts, characters, world state and other keyed blobs.
+//! A [`Backend`] does the actual storage: [`JsonFiles`] keeps one serde_json file per key,
+//! [`Sqlite`] keeps everything in one SQLite database. Backends block, so [`Store`] runs them on tokio's blocking pool.
+//! Values saved through [`Store::save`] carry a schema version and are upgraded by [`Migrations`] as they load
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), lumina::store::StoreError> {
+//! let store = lumina::store::Store::json("data")?;
+//! let (accounts, skipped) = store.load_accounts().await?;
+//! for e in skipped {
+//!     eprintln!("Skipping account: {}", e);
+//! }
+//! let server = lumina::server::Server::builder().accounts(accounts).store(store).build();
+//! # Ok(())
+//! # }
+//! ```
+mod json;
+pub mod migrate;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(test)]
+mod tests;
+
+pub use json::JsonFiles;
+pub use migrate::{MigrationReport, Migrations};
+#[cfg(feature = "sqlite")]
+pub use sqlite::Sqlite;
+
+use crate::account::{Account, AccountError, Accounts};
+use migrate::Failure;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::{self, Display};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Why a load or save failed
+#[derive(Debug)]
+pub enum StoreError {
+    Io(io::Error),
+    /// A value couldn't be serialized
+    Json(serde_json::Error),
+    #[cfg(feature = "sqlite")]
+    Sqlite(rusqlite::Error),
+    /// Table names are lowercase letters, digits and `_`
+    InvalidTable(String),
+    /// The stored accounts don't agree with each other, e.g. two own the same character name
+    Accounts(AccountError),
+    /// The record couldn't be read or migrated, and was moved to the quarantine table
+    Quarantined { table: String, key: String, reason: String },
+    /// The record was saved by a newer release, at a version this one has no migrations for
+    TooNew { table: String, key: String, version: u32 },
+}
+
+impl Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Io(e) => write!(f, "{}", e),
+            StoreError::Json(e) => write!(f, "bad JSON: {}", e),
+            #[cfg(feature = "sqlite")]
+            StoreError::Sqlite(e) => write!(f, "SQLite: {}", e),
+            StoreError::InvalidTable(table) => write!(f, "'{}' isn't a valid table name", table),
+            StoreError::Accounts(e) => write!(f, "stored accounts conflict: {}", e),
+            StoreError::Quarantined { table, key, reason } => {
+                write!(f, "{}/{} was quarantined: {}", table, key, reason)
+            }
+            StoreError::TooNew { table, key, version } => {
+                write!(f, "{}/{} is at version {}, newer than this release knows", table, key, version)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<io::Error> for StoreError {
+    fn from(e: io::Error) -> Self {
+        StoreError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(e: serde_json::Error) -> Self {
+        StoreError::Json(e)
+    }
+}
+
+/// Storage for blobs grouped into tables. Methods may block on disk, call them through a [`Store`].
+/// Every write is atomic: after a crash a key holds either its old value or its new one
+pub trait Backend: Send + Sync + 'static {
+    fn get(&self, table: &str, key: &str) -> Result<Option<Vec<u8>>, StoreError>;
+    fn put(&self, table: &str, key: &str, value: &[u8]) -> Result<(), StoreError>;
+    /// Returns whether the key existed
+    fn delete(&self, table: &str, key: &str) -> Result<bool, StoreError>;
+    /// Every key in the table, sorted
+    fn keys(&self, table: &str) -> Result<Vec<String>, StoreError>;
+}
+
+/// Tables used by [`Store`] itself. Pick other names for blobs
+pub mod tables {
+    pub const ACCOUNTS: &str = "accounts";
+    pub const CHARACTERS: &str = "characters";
+    pub const WORLD: &str = "world";
+    /// Records that failed to load, keyed `table/key`, exactly as they were stored
+    pub const QUARANTINE: &str = "quarantine";
+}
+
+fn check_table(table: &str) -> Result<(), StoreError> {
+    let valid = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_';
+    if table.is_empty() || !table.chars().all(valid) {
+        return Err(StoreError::InvalidTable(table.to_string()));
+    }
+    Ok(())
+}
+
+/// The async face of a [`Backend`]. Cheap to clone, clones share the backend
+#[derive(Clone)]
+pub struct Store {
+    backend: Arc<dyn Backend>,
+    migrations: Arc<Migrations>,
+}
+
+impl fmt::Debug for Store {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Store")
+    }
+}
+
+impl Store {
+    pub fn new(backend: impl Backend) -> Self {
+        Store { backend: Arc::new(backend), migrations: Arc::default() }
+    }
+
+    /// Upgrade old records with `migrations` as they load, and save new ones at the latest version
+    pub fn migrations(mut self, migrations: Migrations) -> Self {
+        self.migrations = Arc::new(migrations);
+        self
+    }
+
+    /// A store of JSON files under `dir`, created if missing
+    pub fn json(dir: impl AsRef<Path>) -> Result<Self, StoreError> {
+        Ok(Store::new(JsonFiles::new(dir)?))
+    }
+
+    /// A store in the SQLite database at `path`, created if missing
+    #[cfg(feature = "sqlite")]
+    pub fn sqlite(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        Ok(Store::new(Sqlite::open(path)?))
+    }
+
+    /// Run `f` against the backend on the blocking pool
+    async fn run<T, F>(&self, f: F) -> Result<T, StoreError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn Backend) -> Result<T, StoreError> + Send + 'static,
+    {
+        let backend = self.backend.clone();
+        tokio::task::spawn_blocking(move || f(&*backend)).await.expect("storage backend panicked")
+    }
+
+    pub async fn get(&self, table: &str, key: &str) -> Result<Option<Vec<u8>>, StoreError> {
+        check_table(table)?;
+        let (table, key) = (table.to_string(), key.to_string());
+        self.run(move |b| b.get(&table, &key)).await
+    }
+
+    pub async fn put(&self, table: &str, key: &str, value: Vec<u8>) -> Result<(), StoreError> {
+        check_table(table)?;
+        let (table, key) = (table.to_string(), key.to_string());
+        self.run(move |b| b.put(&table, &key, &value)).await
+    }
+
+    /// Returns whether the key existed
+    pub async fn delete(&self, table: &str, key: &str) -> Result<bool, StoreError> {
+        check_table(table)?;
+        let (table, key) = (table.to_string(), key.to_string());
+        self.run(move |b| b.delete(&table, &key)).await
+    }
+
+    /// Every key in the table, sorted
+    pub async fn keys(&self, table: &str) -> Result<Vec<String>, StoreError> {
+        check_table(table)?;
+        let table = table.to_string();
+        self.run(move |b| b.keys(&table)).await
+    }
+
+    /// Load a value saved with [`save`](Store::save), migrating it if it is older than the current version.
+    /// A record that can't be read is moved to [`tables::QUARANTINE`] and loads as [`StoreError::Quarantined`]
+    pub async fn load<T: DeserializeOwned>(&self, table: &str, key: &str) -> Result<Option<T>, StoreError> {
+        let Some(bytes) = self.get(table, key).await? else {
+            return Ok(None);
+        };
+        let read = self.migrations.upgrade(table, &bytes).and_then(|(data, version)| {
+            serde_json::from_value(data).map_err(|e| Failure::Bad { version: Some(version), reason: e.to_string() })
+        });
+        match read {
+            Ok(value) => Ok(Some(value)),
+            Err(failure) => Err(self.fail(table, key, bytes, failure).await),
+        }
+    }
+
+    /// Save a value as JSON, at the table's current version
+    pub async fn save<T: Serialize + ?Sized>(&self, table: &str, key: &str, value: &T) -> Result<(), StoreError> {
+        let record = migrate::envelope(self.migrations.version(table), serde_json::to_value(value)?);
+        self.put(table, key, serde_json::to_vec_pretty(&record)?).await
+    }
+
+    /// Turn a failed read into an error, quarantining the record unless a newer release wrote it
+    async fn fail(&self, table: &str, key: &str, bytes: Vec<u8>, failure: Failure) -> StoreError {
+        let (table, key) = (table.to_string(), key.to_string());
+        let reason = match failure {
+            Failure::TooNew(version) => return StoreError::TooNew { table, key, version },
+            Failure::Bad { version: Some(version), reason } => format!("version {}, {}", version, reason),
+            Failure::Bad { version: None, reason } => reason,
+        };
+        if let Err(e) = self.quarantine(&table, &key, bytes).await {
+            return e;
+        }
+        StoreError::Quarantined { table, key, reason }
+    }
+
+    /// Move a record to [`tables::QUARANTINE`], copying before deleting so it is never lost
+    async fn quarantine(&self, table: &str, key: &str, bytes: Vec<u8>) -> Result<(), StoreError> {
+        self.put(tables::QUARANTINE, &format!("{}/{}", table, key), bytes).await?;
+        self.delete(table, key).await?;
+        Ok(())
+    }
+
+    /// Every quarantined record, as `table/key`
+    pub async fn quarantined(&self) -> Result<Vec<String>, StoreError> {
+        self.keys(tables::QUARANTINE).await
+    }
+
+    /// Upgrade every record in every table with migrations and save it back, quarantining those that fail.
+    /// Meant to run offline, with the game stopped, see `examples/migrate.rs`
+    pub async fn migrate_all(&self) -> Result<MigrationReport, StoreError> {
+        let mut report = MigrationReport::default();
+        let tables: Vec<String> = self.migrations.tables().map(str::to_string).collect();
+        for table in tables {
+            let current = self.migrations.version(&table);
+            for key in self.keys(&table).await? {
+                let Some(bytes) = self.get(&table, &key).await? else { continue };
+                match self.migrations.upgrade(&table, &bytes) {
+                    Ok((_, version)) if version == current => report.current += 1,
+                    Ok((data, _)) => {
+                        let record = migrate::envelope(current, data);
+                        self.put(&table, &key, serde_json::to_vec_pretty(&record)?).await?;
+                        report.migrated += 1;
+                    }
+                    Err(failure) => match self.fail(&table, &key, bytes, failure).await {
+                        StoreError::TooNew { .. } => report.too_new.push(format!("{}/{}", table, key)),
+                        StoreError::Quarantined { reason, .. } => {
+                            report.quarantined.push(format!("{}/{}: {}", table, key, reason))
+                        }
+                        e => return Err(e),
+                    },
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    pub async fn save_account(&self, account: &Account) -> Result<(), StoreError> {
+        self.save(tables::ACCOUNTS, &account.name.to_ascii_lowercase(), account).await
+    }
+
+    pub async fn load_account(&self, name: &str) -> Result<Option<Account>, StoreError> {
+        self.load(tables::ACCOUNTS, &name.to_ascii_lowercase()).await
+    }
+
+    pub async fn delete_account(&self, name: &str) -> Result<bool, StoreError> {
+        self.delete(tables::ACCOUNTS, &name.to_ascii_lowercase()).await
+    }
+
+    /// Every saved account, ready for [`Builder::accounts`](crate::server::Builder::accounts), and the ones skipped.
+    /// Accounts that can't be read are quarantined and left out, so one bad file doesn't stop every login;
+    /// each comes back as a [`StoreError::Quarantined`] for the game to log
+    pub async fn load_accounts(&self) -> Result<(Accounts, Vec<StoreError>), StoreError> {
+        let (mut accounts, mut skipped) = (Vec::new(), Vec::new());
+        for key in self.keys(tables::ACCOUNTS).await? {
+            match self.load::<Account>(tables::ACCOUNTS, &key).await {
+                Ok(account) => accounts.extend(account),
+                Err(e @ StoreError::Quarantined { .. }) => skipped.push(e),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok((Accounts::from_accounts(accounts).map_err(StoreError::Accounts)?, skipped))
+    }
+
+    /// Save a character's state in the game, whatever the game keeps for it
+    pub async fn save_character<T: Serialize + ?Sized>(&self, name: &str, state: &T) -> Result<(), StoreError> {
+        self.save(tables::CHARACTERS, &name.to_ascii_lowercase(), state).await
+    }
+
+    pub async fn load_character<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>, StoreError> {
+        self.load(tables::CHARACTERS, &name.to_ascii_lowercase()).await
+    }
+
+    /// Save part of the world, e.g. an area's rooms keyed by the area's name
+    pub async fn save_world<T: Serialize + ?Sized>(&self, key: &str, state: &T) -> Result<(), StoreError> {
+        self.save(tables::WORLD, key, state).await
+    }
+
+    pub async fn load_world<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, StoreError> {
+        self.load(tables::WORLD, key).await
+    }
+}
@@ -0,0 +1,66 @@
+//! Every table in one SQLite database, as rows of `(tbl, key, value)`
+use super::{Backend, StoreError};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::Mutex;
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(e: rusqlite::Error) -> Self {
+        StoreError::Sqlite(e)
+    }
+}
+
+/// A [`Backend`] keeping values in a SQLite database file
+#[derive(Debug)]
+pub struct Sqlite {
+    connection: Mutex<Connection>,
+}
+
+impl Sqlite {
+    /// Open the database at `path`, creating it if needed
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        let connection = Connection::open(path)?;
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS blobs (
+                tbl TEXT NOT NULL,
+                key TEXT NOT NULL,
+                value BLOB NOT NULL,
+                PRIMARY KEY (tbl, key)
+            ) WITHOUT ROWID",
+        )?;
+        Ok(Sqlite { connection: Mutex::new(connection) })
+    }
+}
+
+impl Backend for Sqlite {
+    fn get(&self, table: &str, key: &str) -> Result<Option<Vec<u8>>, StoreError> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare_cached("SELECT value FROM blobs WHERE tbl = ?1 AND key = ?2")?;
+        Ok(statement.query_row(params![table, key], |row| row.get(0)).optional()?)
+    }
+
+    /// A single statement, so SQLite makes it atomic
+    fn put(&self, table: &str, key: &str, value: &[u8]) -> Result<(), StoreError> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare_cached(
+            "INSERT INTO blobs (tbl, key, value) VALUES (?1, ?2, ?3)
+             ON CONFLICT (tbl, key) DO UPDATE SET value = excluded.value",
+        )?;
+        statement.execute(params![table, key, value])?;
+        Ok(())
+    }
+
+    fn delete(&self, table: &str, key: &str) -> Result<bool, StoreError> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare_cached("DELETE FROM blobs WHERE tbl = ?1 AND key = ?2")?;
+        Ok(statement.execute(params![table, key])? > 0)
+    }
+
+    fn keys(&self, table: &str) -> Result<Vec<String>, StoreError> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare_cached("SELECT key FROM blobs WHERE tbl = ?1 ORDER BY key")?;
+        let keys = statement.query_map(params![table], |row| row.get(0))?;
+        Ok(keys.collect::<Result<_, _>>()?)
+    }
+}
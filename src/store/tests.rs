@@ -0,0 +1,121 @@
+use super::{tables, JsonFiles, MigrationReport, Migrations, Store, StoreError};
+use crate::account::{Account, Character};
+use crate::entity::EntityId;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+fn scratch(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("lumina-store-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&path);
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+async fn exercise(store: &Store) {
+    assert_eq!(store.get("notes", "missing").await.unwrap(), None);
+    store.put("notes", "a/../b", b"first".to_vec()).await.unwrap();
+    store.put("notes", "a/../b", b"second".to_vec()).await.unwrap();
+    store.put("notes", "Zed", vec![0, 255]).await.unwrap();
+    store.put("notes", "", b"empty".to_vec()).await.unwrap();
+    assert_eq!(store.get("notes", "a/../b").await.unwrap().as_deref(), Some(&b"second"[..]));
+    assert_eq!(store.keys("notes").await.unwrap(), ["", "Zed", "a/../b"]);
+    assert!(store.delete("notes", "").await.unwrap());
+    assert!(store.delete("notes", "Zed").await.unwrap());
+    assert!(!store.delete("notes", "Zed").await.unwrap());
+    assert!(matches!(store.keys("../etc").await, Err(StoreError::InvalidTable(_))));
+
+    let mut alice = Account::new("Alice", "$argon2id$hash".to_string());
+    alice.characters.push(Character { name: "Ann".to_string(), entity: EntityId(7), answers: BTreeMap::new() });
+    store.save_account(&alice).await.unwrap();
+    store.save_account(&Account::new("Bob", String::new())).await.unwrap();
+    assert_eq!(store.load_account("ALICE").await.unwrap(), Some(alice));
+    store.put(tables::ACCOUNTS, "carl", b"{".to_vec()).await.unwrap();
+    let (accounts, skipped) = store.load_accounts().await.unwrap();
+    assert_eq!(accounts.len(), 2);
+    assert!(matches!(&skipped[..], [StoreError::Quarantined { key, .. }] if key == "carl"), "{:?}", skipped);
+    assert_eq!(accounts.owner("ann").as_deref(), Some("Alice"));
+    assert!(store.delete_account("bob").await.unwrap());
+
+    store.save_character("Ann", &serde_json::json!({"room": 3001, "gold": 12})).await.unwrap();
+    let ann: serde_json::Value = store.load_character("ann").await.unwrap().unwrap();
+    assert_eq!(ann["gold"], 12);
+    store.save_world("midgaard", &vec![3001, 3002]).await.unwrap();
+    assert_eq!(store.load_world::<Vec<u32>>("midgaard").await.unwrap(), Some(vec![3001, 3002]));
+    store.put(tables::WORLD, "broken", b"{".to_vec()).await.unwrap();
+    assert!(matches!(store.load_world::<Vec<u32>>("broken").await, Err(StoreError::Quarantined { .. })));
+    assert_eq!(store.quarantined().await.unwrap(), ["accounts/carl", "world/broken"]);
+    assert_eq!(store.get(tables::WORLD, "broken").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn json_files() {
+    let dir = scratch("json");
+    let store = Store::json(&dir).unwrap();
+    exercise(&store).await;
+    // a temporary file left by a crash is not a key
+    std::fs::write(dir.join("notes").join(".b.1.0.tmp"), "x").unwrap();
+    assert_eq!(store.keys("notes").await.unwrap(), ["a/../b"]);
+    assert!(dir.join("accounts").join("alice.json").exists());
+    assert_eq!(JsonFiles::new(&dir).unwrap().root(), dir);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn sqlite() {
+    let path = scratch("sqlite.db");
+    exercise(&Store::sqlite(&path).unwrap()).await;
+    let reopened = Store::sqlite(&path).unwrap();
+    assert_eq!(reopened.keys(tables::ACCOUNTS).await.unwrap(), ["alice"]);
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+}
+
+#[tokio::test]
+async fn migrations() {
+    let dir = scratch("migrations");
+    let old = Store::json(&dir).unwrap();
+    // before versioning, then at version 1
+    old.put("pets", "rex", br#"{"name": "Rex"}"#.to_vec()).await.unwrap();
+    old.put("pets", "tom", br#"{"version": 1, "data": {"name": "Tom", "legs": 4}}"#.to_vec()).await.unwrap();
+    old.put("pets", "bad", br#"{"version": 1, "data": {"name": "Bad", "legs": "four"}}"#.to_vec()).await.unwrap();
+    old.put("pets", "new", br#"{"version": 9, "data": {}}"#.to_vec()).await.unwrap();
+    old.put("pets", "junk", b"not json".to_vec()).await.unwrap();
+
+    let migrations = Migrations::new()
+        .add("pets", 1, |mut pet| {
+            pet["legs"] = 4.into();
+            Ok(pet)
+        })
+        .add("pets", 2, |mut pet| {
+            let legs = pet["legs"].as_u64().ok_or("legs isn't a number")?;
+            pet["feet"] = legs.into();
+            Ok(pet)
+        });
+    assert_eq!(migrations.version("pets"), 2);
+    let store = Store::json(&dir).unwrap().migrations(migrations);
+
+    let rex: serde_json::Value = store.load("pets", "rex").await.unwrap().unwrap();
+    assert_eq!(rex, serde_json::json!({"name": "Rex", "legs": 4, "feet": 4}));
+    let error = store.load::<serde_json::Value>("pets", "bad").await.unwrap_err();
+    assert!(error.to_string().contains("migrating to version 2: legs isn't a number"), "{}", error);
+    assert!(matches!(store.load::<serde_json::Value>("pets", "new").await, Err(StoreError::TooNew { version: 9, .. })));
+
+    store.save("pets", "ann", &serde_json::json!({"name": "Ann", "legs": 2, "feet": 2})).await.unwrap();
+    let saved: serde_json::Value = serde_json::from_slice(&old.get("pets", "ann").await.unwrap().unwrap()).unwrap();
+    assert_eq!(saved["version"], 2);
+
+    let report = store.migrate_all().await.unwrap();
+    assert_eq!((report.migrated, report.current), (2, 1));
+    assert_eq!(report.too_new, ["pets/new"]);
+    assert_eq!(report.quarantined.len(), 1);
+    assert!(report.quarantined[0].starts_with("pets/junk: bad JSON"));
+    assert_eq!(store.quarantined().await.unwrap(), ["pets/bad", "pets/junk"]);
+    assert_eq!(store.keys("pets").await.unwrap(), ["ann", "new", "rex", "tom"]);
+    let tom: serde_json::Value = serde_json::from_slice(&old.get("pets", "tom").await.unwrap().unwrap()).unwrap();
+    assert_eq!(tom, serde_json::json!({"version": 2, "data": {"name": "Tom", "legs": 4, "feet": 4}}));
+    let again = MigrationReport { current: 3, too_new: vec!["pets/new".to_string()], ..Default::default() };
+    assert_eq!(store.migrate_all().await.unwrap(), again);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
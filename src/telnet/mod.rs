@@ -0,0 +1,52 @@
+//! This module provides a small [telnet](https://www.rfc-editor.org/rfc/rfc854) engine: a streaming parser and option negotiation state
+mod options;
+mod parser;
+#[cfg(test)]
+mod tests;
+
+pub use options::{Change, Options};
+pub use parser::{Event, Parser, MAX_SUBNEGOTIATION};
+
+pub const EOR: u8 = 239;
+pub const SE: u8 = 240;
+pub const NOP: u8 = 241;
+pub const GA: u8 = 249;
+pub const SB: u8 = 250;
+pub const WILL: u8 = 251;
+pub const WONT: u8 = 252;
+pub const DO: u8 = 253;
+pub const DONT: u8 = 254;
+pub const IAC: u8 = 255;
+
+/// Telnet option codes
+pub mod option {
+    pub const ECHO: u8 = 1;
+    pub const SGA: u8 = 3;
+    pub const EOR: u8 = 25;
+    pub const NAWS: u8 = 31;
+    pub const CHARSET: u8 = 42;
+    pub const MSDP: u8 = 69;
+    pub const MSP: u8 = 90;
+    pub const MXP: u8 = 91;
+    pub const GMCP: u8 = 201;
+}
+
+/// Double every `IAC` in `data` so it can be sent as plain text
+pub fn escape(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    for &b in data {
+        if b == IAC {
+            out.push(IAC);
+        }
+        out.push(b);
+    }
+    out
+}
+
+/// Build an `IAC SB <option> <data> IAC SE` sequence
+pub fn subnegotiation(option: u8, data: &[u8]) -> Vec<u8> {
+    let mut out = vec![IAC, SB, option];
+    out.extend(escape(data));
+    out.extend_from_slice(&[IAC, SE]);
+    out
+}
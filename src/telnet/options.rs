@@ -0,0 +1,178 @@
+use super::{DO, DONT, WILL, WONT};
+use bytes::BufMut;
+
+/// Negotiation state of one side of an option, as in the [Q method](https://www.rfc-editor.org/rfc/rfc1143).
+/// While waiting for an answer, `true` means the opposite was asked for meanwhile and is sent once it arrives
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Q {
+    #[default]
+    No,
+    WantYes(bool),
+    Yes,
+    WantNo(bool),
+}
+
+impl Q {
+    /// Ask for the option to be on or off. `true` if the request should be sent now
+    fn ask(&mut self, on: bool) -> bool {
+        let (next, send) = match (*self, on) {
+            (Q::No, true) => (Q::WantYes(false), true),
+            (Q::Yes, false) => (Q::WantNo(false), true),
+            (Q::WantNo(_), true) => (Q::WantNo(true), false),
+            (Q::WantYes(_), false) => (Q::WantYes(true), false),
+            (Q::WantNo(_), false) => (Q::WantNo(false), false),
+            (Q::WantYes(_), true) => (Q::WantYes(false), false),
+            (q, _) => (q, false),
+        };
+        *self = next;
+        send
+    }
+}
+
+/// An option that was switched on or off by a negotiation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    /// We perform the option (`WILL`/`DO` from the client)
+    Local(u8, bool),
+    /// The client performs the option (`DO`/`WILL` from the client)
+    Remote(u8, bool),
+}
+
+/// Which options are enabled on either side of a connection, and which ones we agree to
+#[derive(Debug, Clone)]
+pub struct Options {
+    local: [Q; 256],
+    remote: [Q; 256],
+    supported_local: [bool; 256],
+    supported_remote: [bool; 256],
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            local: [Q::No; 256],
+            remote: [Q::No; 256],
+            supported_local: [false; 256],
+            supported_remote: [false; 256],
+        }
+    }
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Options::default()
+    }
+
+    /// Agree to perform `option` when the client asks us to
+    pub fn support_local(&mut self, option: u8) {
+        self.supported_local[usize::from(option)] = true;
+    }
+
+    /// Agree to let the client perform `option`
+    pub fn support_remote(&mut self, option: u8) {
+        self.supported_remote[usize::from(option)] = true;
+    }
+
+    pub fn local_enabled(&self, option: u8) -> bool {
+        self.local[usize::from(option)] == Q::Yes
+    }
+
+    pub fn remote_enabled(&self, option: u8) -> bool {
+        self.remote[usize::from(option)] == Q::Yes
+    }
+
+    /// Every option we currently perform
+    pub fn enabled_local(&self) -> Vec<u8> {
+        (0..=255).filter(|&o| self.local_enabled(o)).collect()
+    }
+
+    /// Every option the client currently performs
+    pub fn enabled_remote(&self) -> Vec<u8> {
+        (0..=255).filter(|&o| self.remote_enabled(o)).collect()
+    }
+
+    /// Mark options as already enabled, e.g. when a connection is handed over from another process
+    pub fn restore(&mut self, local: &[u8], remote: &[u8]) {
+        for &o in local {
+            self.local[usize::from(o)] = Q::Yes;
+        }
+        for &o in remote {
+            self.remote[usize::from(o)] = Q::Yes;
+        }
+    }
+
+    /// Offer to perform `option`, writing `IAC WILL` to `out` if it isn't already on
+    pub fn enable_local(&mut self, option: u8, out: &mut impl BufMut) {
+        if self.local[usize::from(option)].ask(true) {
+            out.put_slice(&[super::IAC, WILL, option]);
+        }
+    }
+
+    /// Ask the client to perform `option`, writing `IAC DO` to `out` if it isn't already on
+    pub fn enable_remote(&mut self, option: u8, out: &mut impl BufMut) {
+        if self.remote[usize::from(option)].ask(true) {
+            out.put_slice(&[super::IAC, DO, option]);
+        }
+    }
+
+    /// Stop performing `option`, writing `IAC WONT` to `out` if it was on
+    pub fn disable_local(&mut self, option: u8, out: &mut impl BufMut) {
+        if self.local[usize::from(option)].ask(false) {
+            out.put_slice(&[super::IAC, WONT, option]);
+        }
+    }
+
+    /// Handle a `WILL`/`WONT`/`DO`/`DONT` from the client, writing any reply to `out`
+    pub fn receive(&mut self, command: u8, option: u8, out: &mut impl BufMut) -> Option<Change> {
+        let i = usize::from(option);
+        let (q, supported, accept, refuse) = match command {
+            DO | DONT => (&mut self.local[i], self.supported_local[i], WILL, WONT),
+            _ => (&mut self.remote[i], self.supported_remote[i], DO, DONT),
+        };
+        let wants = matches!(command, WILL | DO);
+        let before = *q == Q::Yes;
+        let reply = match (*q, wants) {
+            (Q::No, true) if supported => {
+                *q = Q::Yes;
+                Some(accept)
+            }
+            (Q::No, true) => Some(refuse),
+            (Q::Yes, false) => {
+                *q = Q::No;
+                Some(refuse)
+            }
+            (Q::WantYes(false), true) => {
+                *q = Q::Yes;
+                None
+            }
+            // Turned on, but we've changed our mind since asking
+            (Q::WantYes(true), true) => {
+                *q = Q::WantNo(false);
+                Some(refuse)
+            }
+            (Q::WantYes(_), false) | (Q::WantNo(false), false) => {
+                *q = Q::No;
+                None
+            }
+            (Q::WantNo(true), false) => {
+                *q = Q::WantYes(false);
+                Some(accept)
+            }
+            // Agreeing to a refusal is an error, the option ends up as we last asked
+            (Q::WantNo(opposite), true) => {
+                *q = if opposite { Q::Yes } else { Q::No };
+                None
+            }
+            (Q::No, false) | (Q::Yes, true) => None,
+        };
+        if let Some(reply) = reply {
+            out.put_slice(&[super::IAC, reply, option]);
+        }
+        let after = *q == Q::Yes;
+        let change = match command {
+            DO | DONT => Change::Local(option, after),
+            _ => Change::Remote(option, after),
+        };
+        (before != after).then_some(change)
+    }
+}
@@ -0,0 +1,118 @@
+use super::{DO, DONT, IAC, SB, SE, WILL, WONT};
+
+/// The longest subnegotiation payload kept. A longer one is dropped whole, up to its `IAC SE`
+pub const MAX_SUBNEGOTIATION: usize = 64 * 1024;
+
+/// Something received from the client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// Plain data, with escaped `IAC`s already collapsed
+    Data(Vec<u8>),
+    Will(u8),
+    Wont(u8),
+    Do(u8),
+    Dont(u8),
+    /// The option and payload of an `IAC SB ... IAC SE` sequence
+    Subnegotiation(u8, Vec<u8>),
+    /// Any other `IAC` command, such as `GA` or `NOP`
+    Command(u8),
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+enum State {
+    #[default]
+    Data,
+    Iac,
+    Negotiation(u8),
+    Sb,
+    SbData(u8),
+    SbIac(u8),
+    /// Inside a subnegotiation that grew too long, waiting for its end
+    SbSkip,
+    SbSkipIac,
+}
+
+/// A streaming telnet parser. Sequences split across reads are carried over to the next call
+#[derive(Debug, Default)]
+pub struct Parser {
+    state: State,
+    data: Vec<u8>,
+    sb: Vec<u8>,
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Parser::default()
+    }
+
+    /// Feed the next chunk of input, returning every complete event in it
+    pub fn parse(&mut self, input: &[u8]) -> Vec<Event> {
+        let mut events = Vec::new();
+        for &b in input {
+            self.state = match (self.state, b) {
+                (State::Data, IAC) => State::Iac,
+                (State::Data, _) => {
+                    self.data.push(b);
+                    State::Data
+                }
+                (State::Iac, IAC) => {
+                    self.data.push(IAC);
+                    State::Data
+                }
+                (State::Iac, WILL | WONT | DO | DONT) => State::Negotiation(b),
+                (State::Iac, SB) => State::Sb,
+                (State::Iac, _) => {
+                    self.flush(&mut events);
+                    events.push(Event::Command(b));
+                    State::Data
+                }
+                (State::Negotiation(cmd), _) => {
+                    self.flush(&mut events);
+                    events.push(match cmd {
+                        WILL => Event::Will(b),
+                        WONT => Event::Wont(b),
+                        DO => Event::Do(b),
+                        _ => Event::Dont(b),
+                    });
+                    State::Data
+                }
+                (State::Sb, _) => {
+                    self.sb.clear();
+                    State::SbData(b)
+                }
+                (State::SbData(opt), IAC) => State::SbIac(opt),
+                (State::SbData(_), _) if self.sb.len() >= MAX_SUBNEGOTIATION => {
+                    self.sb = Vec::new();
+                    State::SbSkip
+                }
+                (State::SbData(opt), _) => {
+                    self.sb.push(b);
+                    State::SbData(opt)
+                }
+                (State::SbIac(opt), SE) => {
+                    self.flush(&mut events);
+                    events.push(Event::Subnegotiation(opt, std::mem::take(&mut self.sb)));
+                    State::Data
+                }
+                (State::SbIac(opt), _) => {
+                    // `IAC IAC` is an escaped 255, anything else is a protocol error we tolerate
+                    if b == IAC {
+                        self.sb.push(IAC);
+                    }
+                    State::SbData(opt)
+                }
+                (State::SbSkip, IAC) => State::SbSkipIac,
+                (State::SbSkipIac, SE) => State::Data,
+                (State::SbSkip | State::SbSkipIac, _) => State::SbSkip,
+            };
+        }
+        self.flush(&mut events);
+        events
+    }
+
+    fn flush(&mut self, events: &mut Vec<Event>) {
+        if !self.data.is_empty() {
+            events.push(Event::Data(std::mem::take(&mut self.data)));
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use super::{option, Change, Event, Options, Parser, MAX_SUBNEGOTIATION, DO, DONT, IAC, SB, SE, WILL, WONT};
+
+#[test]
+fn split_sequences() {
+    let mut parser = Parser::new();
+    assert_eq!(parser.parse(&[b'h', b'i', IAC]), vec![Event::Data(b"hi".to_vec())]);
+    assert_eq!(parser.parse(&[IAC, IAC, SB, option::MSDP, 1, IAC, IAC]), vec![Event::Data(vec![IAC])]);
+    assert_eq!(
+        parser.parse(&[IAC, SE, IAC, WILL, option::CHARSET]),
+        vec![Event::Subnegotiation(option::MSDP, vec![1, IAC]), Event::Will(option::CHARSET)]
+    );
+}
+
+#[test]
+fn oversized_subnegotiation_is_dropped() {
+    let mut parser = Parser::new();
+    assert!(parser.parse(&[IAC, SB, option::MSDP]).is_empty());
+    for _ in 0..4 {
+        assert!(parser.parse(&vec![b'x'; MAX_SUBNEGOTIATION]).is_empty());
+    }
+    assert_eq!(parser.parse(&[IAC, IAC, IAC, SE, b'o', b'k']), vec![Event::Data(b"ok".to_vec())]);
+    assert_eq!(parser.parse(&[IAC, SB, option::MSDP, 1, IAC, SE]), vec![Event::Subnegotiation(option::MSDP, vec![1])]);
+}
+
+#[test]
+fn negotiation_does_not_loop() {
+    let mut options = Options::new();
+    options.support_local(option::MSDP);
+    let mut out = Vec::new();
+    options.enable_local(option::MSDP, &mut out);
+    assert_eq!(out, [IAC, WILL, option::MSDP]);
+    out.clear();
+    assert_eq!(options.receive(DO, option::MSDP, &mut out), Some(Change::Local(option::MSDP, true)));
+    assert!(out.is_empty());
+    assert_eq!(options.receive(DO, option::CHARSET, &mut out), None);
+    assert_eq!(out, [IAC, WONT, option::CHARSET]);
+    out.clear();
+    assert_eq!(options.receive(DONT, option::MSDP, &mut out), Some(Change::Local(option::MSDP, false)));
+    assert_eq!(out, [IAC, WONT, option::MSDP]);
+}
+
+#[test]
+fn requests_queue_behind_pending_ones() {
+    let mut options = Options::new();
+    options.support_local(option::ECHO);
+    let mut out = Vec::new();
+    options.enable_local(option::ECHO, &mut out);
+    options.disable_local(option::ECHO, &mut out);
+    assert_eq!(out, [IAC, WILL, option::ECHO]);
+    out.clear();
+    assert_eq!(options.receive(DO, option::ECHO, &mut out), None);
+    assert_eq!(out, [IAC, WONT, option::ECHO]);
+    out.clear();
+    assert_eq!(options.receive(DONT, option::ECHO, &mut out), None);
+    assert!(!options.local_enabled(option::ECHO) && out.is_empty());
+
+    options.restore(&[option::ECHO], &[]);
+    options.disable_local(option::ECHO, &mut out);
+    options.enable_local(option::ECHO, &mut out);
+    assert_eq!(out, [IAC, WONT, option::ECHO]);
+    out.clear();
+    assert_eq!(options.receive(DONT, option::ECHO, &mut out), None);
+    assert_eq!(out, [IAC, WILL, option::ECHO]);
+    out.clear();
+    assert_eq!(options.receive(DO, option::ECHO, &mut out), Some(Change::Local(option::ECHO, true)));
+    assert!(out.is_empty());
+
+    options.disable_local(option::ECHO, &mut out);
+    out.clear();
+    assert_eq!(options.receive(DO, option::ECHO, &mut out), None);
+    assert!(!options.local_enabled(option::ECHO) && out.is_empty());
+}